@@ -0,0 +1,67 @@
+use bracket_lib::prelude::*;
+
+// Pictures drawn in REXPaint, built into the game so the page has them too
+const TITLE: &[u8] = include_bytes!("../resources/title.xp");
+const MENU: &[u8] = include_bytes!("../resources/menu.xp");
+const CORNERS: &[u8] = include_bytes!("../resources/corners.xp");
+
+// How far the arena's corners go from the colour of the floor towards their
+// own, so they suit every theme
+const CORNER_TINT: f32 = 0.25;
+
+pub struct Art {
+    title: XpFile,
+    menu: XpFile,
+    corners: XpFile,
+}
+
+impl Art {
+    pub fn load() -> Self {
+        Art {
+            title: read(TITLE),
+            menu: read(MENU),
+            corners: read(CORNERS),
+        }
+    }
+
+    // Draws the name of the game onto the active console, centred, from row
+    // `y` down, and returns the row under it
+    pub fn draw_title(&self, ctx: &mut BTerm, y: i32) -> i32 {
+        let (width, _) = ctx.get_char_size();
+        let layer = &self.title.layers[0];
+        ctx.render_xp_sprite(&self.title, (width as i32 - layer.width as i32) / 2, y);
+        y + layer.height as i32
+    }
+
+    // Draws the frame around the main menu onto the active console, leaving
+    // the middle for the menu itself
+    pub fn draw_menu(&self, ctx: &mut BTerm) {
+        ctx.render_xp_sprite(&self.menu, 0, 0);
+    }
+
+    // Draws the corners of the arena onto the active console, tinted towards
+    // `floor`. The console has to have no background of its own, so the floor
+    // shows through.
+    pub fn draw_corners(&self, ctx: &mut BTerm, floor: RGB) {
+        let layer = &self.corners.layers[0];
+        for y in 0..layer.height {
+            for x in 0..layer.width {
+                let Some(cell) = layer.get(x, y).filter(|cell| !cell.bg.is_transparent()) else {
+                    continue;
+                };
+                let color = RGB::from_u8(cell.fg.r, cell.fg.g, cell.fg.b);
+                ctx.set(
+                    x as i32,
+                    y as i32,
+                    floor.lerp(color, CORNER_TINT),
+                    RGB::named(BLACK),
+                    cell.ch as FontCharType,
+                );
+            }
+        }
+    }
+}
+
+fn read(mut bytes: &[u8]) -> XpFile {
+    XpFile::read(&mut bytes).expect("bundled art is a REXPaint file")
+}
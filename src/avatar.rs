@@ -0,0 +1,106 @@
+use bracket_lib::prelude::*;
+use rock_paper_scissors::pool::Handle;
+use rock_paper_scissors::simulation::Simulation;
+use rock_paper_scissors::{Hand, Real, Vec2f};
+
+use crate::render;
+
+// Speed gained per tick while a direction is held, and the most the avatar
+// can build up, both relative to the match speed
+const ACCELERATION: Real = 0.1;
+const TOP_SPEED: Real = 1.2;
+const FRAME_COLOR: (u8, u8, u8) = (250, 220, 90);
+const FRAME_RADIUS: i32 = 2;
+
+// Keys that push the avatar along each direction
+const UP_KEYS: [VirtualKeyCode; 2] = [VirtualKeyCode::Up, VirtualKeyCode::W];
+const DOWN_KEYS: [VirtualKeyCode; 2] = [VirtualKeyCode::Down, VirtualKeyCode::S];
+const LEFT_KEYS: [VirtualKeyCode; 2] = [VirtualKeyCode::Left, VirtualKeyCode::A];
+const RIGHT_KEYS: [VirtualKeyCode; 2] = [VirtualKeyCode::Right, VirtualKeyCode::D];
+
+// The particle the player steers. It plays by the usual rules, so it wins
+// over whatever it beats on contact, and the match is lost as soon as it is
+// won over itself.
+pub struct Avatar {
+    handle: Handle,
+    pub hand: Hand,
+    // Direction held down this frame, a unit step along each axis at most
+    thrust: Vec2f,
+    pub converted_at: Option<u64>,
+}
+
+impl Avatar {
+    // Takes over the first particle of the match, so the hand it plays
+    // follows from the seed
+    pub fn new(sim: &Simulation) -> Option<Self> {
+        let (handle, particle) = sim.particles.iter().next()?;
+        Some(Avatar {
+            handle,
+            hand: particle.hand,
+            thrust: Vec2f { x: 0.0, y: 0.0 },
+            converted_at: None,
+        })
+    }
+
+    // Reads the direction keys held down right now
+    pub fn steer(&mut self, input: &Input) {
+        let held = |keys: [VirtualKeyCode; 2]| keys.iter().any(|&key| input.is_key_pressed(key));
+        let axis = |negative, positive| match (held(negative), held(positive)) {
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            _ => 0.0,
+        };
+        self.thrust = Vec2f {
+            x: axis(LEFT_KEYS, RIGHT_KEYS),
+            y: axis(UP_KEYS, DOWN_KEYS),
+        };
+    }
+
+    // Speeds the avatar up along the held direction, ahead of a tick
+    pub fn push(&self, sim: &mut Simulation) {
+        let speed = sim.params.speed;
+        let Some(particle) = sim.particles.get_mut(self.handle) else {
+            return;
+        };
+        let velocity = particle
+            .velocity
+            .plus(self.thrust.product(ACCELERATION * speed));
+        let top_speed = TOP_SPEED * speed;
+        particle.velocity = if velocity.norm() > top_speed {
+            velocity.product(top_speed / velocity.norm())
+        } else {
+            velocity
+        };
+    }
+
+    // Catches up on the tick the simulation just ran. If the avatar was won
+    // over during it, returns the hand it lost to, which it now plays.
+    pub fn update(&mut self, sim: &Simulation) -> Option<Hand> {
+        if self.converted_at.is_some() {
+            return None;
+        }
+        let hand = sim.particles.get(self.handle)?.hand;
+        if hand == self.hand {
+            return None;
+        }
+        self.converted_at = Some(sim.ticks);
+        Some(hand)
+    }
+
+    pub fn position(&self, sim: &Simulation) -> Option<Vec2f> {
+        sim.particles
+            .get(self.handle)
+            .map(|particle| particle.position)
+    }
+
+    // Frames the avatar on the active console, with positions relative to
+    // `origin` on screen
+    pub fn draw(&self, ctx: &mut BTerm, sim: &Simulation, origin: Point) {
+        let Some(position) = self.position(sim) else {
+            return;
+        };
+        let center = origin + Point::new(position.x as i32, position.y as i32);
+        render::draw_frame(ctx, center, FRAME_RADIUS, RGB::from(FRAME_COLOR));
+        ctx.print(center.x - 1, center.y - FRAME_RADIUS - 1, "You");
+    }
+}
@@ -0,0 +1,42 @@
+use rock_paper_scissors::Hand;
+
+// What a winning bet on an even split pays for a match that takes
+// `PAR_TICKS`. Quicker wins pay more and slower ones less, within limits.
+const STAKE: f32 = 100.0;
+const PAR_TICKS: f32 = 500.0;
+const MIN_PACE: f32 = 0.5;
+const MAX_PACE: f32 = 2.0;
+
+// The hand the player expects to win, placed before the match starts
+#[derive(Copy, Clone, Debug)]
+pub struct Bet {
+    pub hand: Hand,
+    odds: f32,
+}
+
+impl Bet {
+    pub fn new(hand: Hand, counts: &[usize]) -> Self {
+        Bet {
+            hand,
+            odds: odds(hand, counts),
+        }
+    }
+
+    // Points won once `winner` takes the match after `ticks`
+    pub fn payout(&self, winner: Hand, ticks: u64) -> u32 {
+        if winner != self.hand {
+            return 0;
+        }
+        let pace = (PAR_TICKS / ticks.max(1) as f32).clamp(MIN_PACE, MAX_PACE);
+        (STAKE * self.odds * pace).round() as u32
+    }
+}
+
+// How much a bet on `hand` is multiplied by, for how outnumbered it starts
+// among the hands counted: 1x on an even split, more for an underdog and
+// less for a favourite
+pub fn odds(hand: Hand, counts: &[usize]) -> f32 {
+    let total: usize = counts.iter().sum();
+    let backing = counts[hand.index()].max(1);
+    total as f32 / (counts.len() * backing) as f32
+}
@@ -0,0 +1,87 @@
+use bracket_lib::prelude::*;
+use rock_paper_scissors::simulation::Simulation;
+
+use crate::{SCREEN_WIDTH, UI_LAYER};
+
+// Branches past this many don't fit the picker's number keys
+pub const MAX_BRANCHES: usize = 9;
+const PANEL_WIDTH: i32 = 20;
+const PANEL_X: i32 = SCREEN_WIDTH - PANEL_WIDTH;
+const PANEL_COLOR: (u8, u8, u8) = (24, 44, 44);
+const ACTIVE_COLOR: (u8, u8, u8) = (250, 230, 90);
+
+// What-if branches of a match. The match being played is the active branch
+// and lives outside, in the game state; every other branch is parked here,
+// frozen at the tick it was left at.
+pub struct Branches {
+    parked: Vec<Option<Simulation>>,
+    // Tick each branch was forked at, or None for the original
+    forked_at: Vec<Option<u64>>,
+    active: usize,
+}
+
+impl Branches {
+    pub fn new() -> Self {
+        Branches {
+            parked: vec![None],
+            forked_at: vec![None],
+            active: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.parked.len()
+    }
+
+    // Parks a copy of `sim` as it is now and carries on with `sim` as a new
+    // branch. Returns false if there is no room for another one.
+    pub fn fork(&mut self, sim: &Simulation) -> bool {
+        if self.len() == MAX_BRANCHES {
+            return false;
+        }
+        self.parked[self.active] = Some(sim.clone());
+        self.parked.push(None);
+        self.forked_at.push(Some(sim.ticks));
+        self.active = self.len() - 1;
+        true
+    }
+
+    // Parks `sim` and swaps in branch `i` in its place
+    pub fn switch(&mut self, i: usize, sim: &mut Simulation) -> bool {
+        if i == self.active || i >= self.len() {
+            return false;
+        }
+        let Some(next) = self.parked[i].take() else {
+            return false;
+        };
+        self.parked[self.active] = Some(std::mem::replace(sim, next));
+        self.active = i;
+        true
+    }
+
+    // Lists the branches in a corner panel on the UI layer, with the active
+    // one highlighted
+    pub fn draw(&self, ctx: &mut BTerm) {
+        ctx.set_active_console(UI_LAYER);
+        let background = RGB::from(PANEL_COLOR);
+        let rows = self.len() as i32 + 2;
+        for y in 0..rows {
+            for x in PANEL_X..SCREEN_WIDTH {
+                ctx.set(x, y, WHITE, background, to_cp437(' '));
+            }
+        }
+        ctx.print_color(PANEL_X + 1, 0, WHITE, background, "Branches (1-9)");
+        for (i, forked_at) in self.forked_at.iter().enumerate() {
+            let label = match forked_at {
+                None => format!("{}: original", i + 1),
+                Some(tick) => format!("{}: fork @ {}", i + 1, tick),
+            };
+            let color = if i == self.active {
+                RGB::from(ACTIVE_COLOR)
+            } else {
+                RGB::named(WHITE)
+            };
+            ctx.print_color(PANEL_X + 1, 1 + i as i32, color, background, label);
+        }
+    }
+}
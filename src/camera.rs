@@ -0,0 +1,196 @@
+use std::collections::VecDeque;
+
+use bracket_lib::prelude::*;
+use rock_paper_scissors::simulation::{Arena, Conversion};
+use rock_paper_scissors::{Real, Vec2f};
+
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Tiles per step when panning with the keyboard
+const PAN_STEP: i32 = 5;
+
+// Side of the square regions activity is tallied over, in tiles
+const HOTSPOT_CELL: i32 = 10;
+// Share of a region's activity still remembered after a tick
+const HOTSPOT_DECAY: f32 = 0.97;
+// Activity a region needs before it is pointed at from the edge of the screen
+const HOTSPOT_THRESHOLD: f32 = 1.0;
+
+// Ticks conversions are counted over to tell a big clash, and how many it
+// takes: this share of the particles, or the least number, whichever is more
+const SURGE_WINDOW: usize = 10;
+const SURGE_SHARE: f32 = 0.1;
+const SURGE_MIN: usize = 5;
+// How long the screen shakes after a big clash, and how far towards white
+// the floor flashes at first
+const SHAKE_MS: f32 = 350.0;
+const PULSE_BRIGHTNESS: f32 = 0.25;
+
+// Which part of the arena is on screen. Worlds no bigger than the screen
+// always show in full.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Camera {
+    // World tile drawn in the top-left corner of the screen
+    pub x: i32,
+    pub y: i32,
+    // How far the picture is knocked off that along each axis while the
+    // screen shakes
+    pub jolt: (i32, i32),
+}
+
+impl Camera {
+    // Where the world's origin ends up on screen
+    pub fn origin(&self) -> Point {
+        Point::new(self.jolt.0 - self.x, self.jolt.1 - self.y)
+    }
+
+    pub fn pan(&mut self, dx: i32, dy: i32, arena: &Arena) {
+        let max_x = (arena.width.ceil() as i32 - SCREEN_WIDTH).max(0);
+        let max_y = (arena.height.ceil() as i32 - SCREEN_HEIGHT).max(0);
+        self.x = (self.x + dx * PAN_STEP).clamp(0, max_x);
+        self.y = (self.y + dy * PAN_STEP).clamp(0, max_y);
+    }
+
+    // Centers the view on `position`, as far as the arena allows
+    pub fn look_at(&mut self, position: &Vec2f, arena: &Arena) {
+        self.x = position.x as i32 - SCREEN_WIDTH / 2;
+        self.y = position.y as i32 - SCREEN_HEIGHT / 2;
+        self.pan(0, 0, arena);
+    }
+
+    // Whether anything drawn around `position` can show up on screen
+    pub fn sees(&self, position: &Vec2f) -> bool {
+        let (x, y) = (position.x as i32 - self.x, position.y as i32 - self.y);
+        (-1..=SCREEN_WIDTH).contains(&x) && (-1..=SCREEN_HEIGHT).contains(&y)
+    }
+}
+
+// Conversions over the last few ticks, to shake the screen and flash the
+// floor when a lot of them come at once
+pub struct Surge {
+    counts: VecDeque<usize>,
+    // Time left shaking
+    shake_ms: f32,
+    rng: RandomNumberGenerator,
+}
+
+impl Surge {
+    pub fn new() -> Self {
+        Surge {
+            counts: VecDeque::with_capacity(SURGE_WINDOW),
+            shake_ms: 0.0,
+            // Only for show, so the match's own numbers stay untouched
+            rng: RandomNumberGenerator::new(),
+        }
+    }
+
+    // Counts the conversions of the tick that just ran, out of `particles`,
+    // and starts shaking if that makes a big clash. The count starts over
+    // then, so one clash shakes once.
+    pub fn update(&mut self, conversions: usize, particles: usize) {
+        if self.counts.len() == SURGE_WINDOW {
+            self.counts.pop_front();
+        }
+        self.counts.push_back(conversions);
+        let needed = ((particles as f32 * SURGE_SHARE) as usize).max(SURGE_MIN);
+        if self.counts.iter().sum::<usize>() >= needed {
+            self.shake_ms = SHAKE_MS;
+            self.counts.clear();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+        self.shake_ms = 0.0;
+    }
+
+    pub fn shaking(&self) -> bool {
+        self.shake_ms > 0.0
+    }
+
+    // Knocks `camera` up to a tile off each way at random while the screen
+    // shakes, and winds the shake down by a frame of `frame_ms`
+    pub fn shake(&mut self, camera: &mut Camera, frame_ms: f32) {
+        camera.jolt = if self.shaking() {
+            (self.rng.range(-1, 2), self.rng.range(-1, 2))
+        } else {
+            (0, 0)
+        };
+        self.shake_ms = (self.shake_ms - frame_ms).max(0.0);
+    }
+
+    // The floor, lit up for as long as the screen shakes
+    pub fn floor(&self, arena: RGB) -> RGB {
+        let share = self.shake_ms / SHAKE_MS;
+        arena.lerp(RGB::named(WHITE), PULSE_BRIGHTNESS * share)
+    }
+}
+
+// Recent conversions tallied over coarse regions of the arena, so the busy
+// ones can be pointed out when they are off screen.
+pub struct Hotspots {
+    columns: i32,
+    rows: i32,
+    heat: Vec<f32>,
+}
+
+impl Hotspots {
+    pub fn new(arena: &Arena) -> Self {
+        let columns = (arena.width / HOTSPOT_CELL as Real).ceil() as i32 + 1;
+        let rows = (arena.height / HOTSPOT_CELL as Real).ceil() as i32 + 1;
+        Hotspots {
+            columns,
+            rows,
+            heat: vec![0.0; (columns * rows) as usize],
+        }
+    }
+
+    // Fades old activity and adds this tick's conversions
+    pub fn update(&mut self, conversions: &[Conversion]) {
+        for heat in self.heat.iter_mut() {
+            *heat *= HOTSPOT_DECAY;
+        }
+        for Conversion { position, .. } in conversions {
+            let column = (position.x as i32 / HOTSPOT_CELL).clamp(0, self.columns - 1);
+            let row = (position.y as i32 / HOTSPOT_CELL).clamp(0, self.rows - 1);
+            self.heat[(row * self.columns + column) as usize] += 1.0;
+        }
+    }
+
+    // Marks the edge of the screen in the direction of every busy region out
+    // of view, brighter the busier it is
+    pub fn draw_indicators(&self, ctx: &mut BTerm, camera: &Camera) {
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let heat = self.heat[(row * self.columns + column) as usize];
+                if heat < HOTSPOT_THRESHOLD {
+                    continue;
+                }
+
+                let x = column * HOTSPOT_CELL + HOTSPOT_CELL / 2 - camera.x;
+                let y = row * HOTSPOT_CELL + HOTSPOT_CELL / 2 - camera.y;
+                let glyph = if x < 0 {
+                    '<'
+                } else if x >= SCREEN_WIDTH {
+                    '>'
+                } else if y < 0 {
+                    '^'
+                } else if y >= SCREEN_HEIGHT {
+                    'v'
+                } else {
+                    continue;
+                };
+
+                let intensity = (heat / (4.0 * HOTSPOT_THRESHOLD)).min(1.0);
+                let color = RGB::from_f32(1.0, 1.0 - 0.8 * intensity, 0.2);
+                ctx.set(
+                    x.clamp(0, SCREEN_WIDTH - 1),
+                    y.clamp(0, SCREEN_HEIGHT - 1),
+                    color,
+                    RGB::named(BLACK),
+                    to_cp437(glyph),
+                );
+            }
+        }
+    }
+}
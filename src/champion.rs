@@ -0,0 +1,123 @@
+use bracket_lib::prelude::*;
+use rock_paper_scissors::pool::Handle;
+use rock_paper_scissors::simulation::Simulation;
+
+use crate::render;
+
+pub const MAX_NAME_LEN: usize = 12;
+// How long the latest news about the champion stays on the HUD
+const NEWS_TICKS: u64 = 100;
+const FRAME_COLOR: (u8, u8, u8) = (120, 200, 250);
+const FRAME_RADIUS: i32 = 2;
+
+// One particle the player named and follows through the match. Handles are
+// never reused, so once the particle is removed the name can't jump to
+// whatever takes its slot.
+pub struct Champion {
+    pub name: String,
+    handle: Handle,
+    // Particles it won over, and how often it changed hands itself
+    converted: u32,
+    times_converted: u32,
+    // What its hand was called while it was last seen
+    hand_name: String,
+    removed_at: Option<u64>,
+    // The latest thing that happened to it, and when
+    news: Option<(String, u64)>,
+}
+
+impl Champion {
+    pub fn new(name: String, handle: Handle, sim: &Simulation) -> Option<Self> {
+        let hand = sim.particles.get(handle)?.hand;
+        Some(Champion {
+            name,
+            handle,
+            converted: 0,
+            times_converted: 0,
+            hand_name: sim.params.rules.name(hand).to_string(),
+            removed_at: None,
+            news: None,
+        })
+    }
+
+    // Catches up on the tick the simulation just ran
+    pub fn update(&mut self, sim: &Simulation) {
+        if self.removed_at.is_some() {
+            return;
+        }
+        let Some(particle) = sim.particles.get(self.handle) else {
+            self.removed_at = Some(sim.ticks);
+            self.news = Some((format!("{} left the arena", self.name), sim.ticks));
+            return;
+        };
+        for conversion in &sim.conversions {
+            if conversion.winner == self.handle {
+                self.converted += 1;
+                self.news = Some((
+                    format!(
+                        "{} won over a {}",
+                        self.name,
+                        sim.params.rules.name(conversion.lost)
+                    ),
+                    sim.ticks,
+                ));
+            } else if conversion.loser == self.handle {
+                self.times_converted += 1;
+                self.news = Some((
+                    format!(
+                        "{} turned to {}",
+                        self.name,
+                        sim.params.rules.name(particle.hand)
+                    ),
+                    sim.ticks,
+                ));
+            }
+        }
+        self.hand_name = sim.params.rules.name(particle.hand).to_string();
+    }
+
+    pub fn stat_line(&self) -> String {
+        let state = match self.removed_at {
+            Some(tick) => format!("gone since tick {}", tick),
+            None => self.hand_name.clone(),
+        };
+        format!(
+            "{} ({}): {} won over, converted {} times",
+            self.name, state, self.converted, self.times_converted
+        )
+    }
+
+    // The latest news, while it is still fresh
+    pub fn news(&self, ticks: u64) -> Option<&str> {
+        self.news
+            .as_ref()
+            .filter(|(_, at)| ticks.saturating_sub(*at) < NEWS_TICKS)
+            .map(|(news, _)| news.as_str())
+    }
+
+    // How the champion's match went, for the end screen
+    pub fn fate(&self) -> String {
+        match self.removed_at {
+            Some(tick) => format!(
+                "{} left the arena at tick {} after winning over {}",
+                self.name, tick, self.converted
+            ),
+            None => format!(
+                "{} finished as {}, winning over {} and converted {} times",
+                self.name, self.hand_name, self.converted, self.times_converted
+            ),
+        }
+    }
+
+    // Frames the champion on the active console, with positions relative to
+    // `origin` on screen, and labels it with its name
+    pub fn draw(&self, ctx: &mut BTerm, sim: &Simulation, origin: Point) {
+        let Some(particle) = sim.particles.get(self.handle) else {
+            return;
+        };
+        let center = origin + Point::new(particle.position.x as i32, particle.position.y as i32);
+        render::draw_frame(ctx, center, FRAME_RADIUS, RGB::from(FRAME_COLOR));
+        let x = center.x - self.name.len() as i32 / 2;
+        ctx.print(x, center.y - FRAME_RADIUS - 1, &self.name);
+    }
+}
@@ -0,0 +1,303 @@
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const USAGE: &str = "\
+Usage: rock-paper-scissors [options]
+       rock-paper-scissors simulate [MATCHES] [options]
+
+  --config PATH            read settings from PATH instead of rps.toml
+  --particles N            particles in a match (default 25)
+  --populations N,N,...    particles each hand starts with, in the order of the
+                           rules, instead of drawing every particle's hand
+  --placement KIND         random (default), corners to start each hand in a
+                           cluster of its own, or rings for one ring each
+  --width W                arena width (default 60)
+  --height H               arena height (default 60)
+  --boundary KIND          walls (default), or wrap to have particles leaving
+                           by one edge come back by the opposite one
+  --max-velocity V         fastest a particle starts along each axis (default 1)
+  --speed S                multiplier on every particle's velocity (default 1)
+  --radius R               particle radius (default 1.5)
+  --size-spread F          how far radii vary either way, as a share of the
+                           radius (default 0)
+  --restitution E          share of their closing speed particles keep when
+                           they bounce off each other (default 1)
+  --conversion-chance C    chance that a winning hand converts (default 1)
+  --immunity-ticks T       ticks a converted particle can't be converted (default 0)
+  --hit-points N           hits a particle takes before it is converted (default 1)
+  --frame-ms MS            milliseconds per physics tick (default 60)
+  --shrink S               tiles the walls close in by each tick (default 0)
+  --steering A             how much particles speed up each tick towards what
+                           they beat and away from what beats them (default 0)
+  --perception R           how far steering particles can see (default 12)
+  --alignment W            how much particles match the velocity of their own
+                           hand nearby (default 0)
+  --cohesion W             how much they head for the middle of it (default 0)
+  --separation W           how much they keep their distance from it (default 0)
+  --mutation P             chance a particle takes up another hand each tick;
+                           matches with any never end (default 0)
+  --pickup-chance P        chance a power-up turns up on the field each tick
+                           (default 0)
+  --map PATH               put the obstacles, zones and portals of the map file at PATH in the arena
+  --fields FIELDS          forces pushing particles around, like
+                           \"wind 0.01 0; vortex 30 30 0.05 15\"
+  --broad-phase KIND       spatial_hash (default) or pair_cache
+  --rules RULES            classic (default) or lizard_spock
+  --seed N                 play every match from seed N
+  --csv                    write the populations of every match to a CSV file
+  --headless               play one match without a window and print the result
+  --terminal               play one match in this terminal instead of a window
+                           (builds with --features terminal only)
+  --ticks N                stop a headless or terminal match after N ticks
+  --jobs N                 threads simulate plays on (default: one per core)
+  --check-config [PATH]    check a config file and exit
+  --help                   show this message
+
+Settings given here win over the [simulation] section of the config file.
+
+simulate plays MATCHES matches (default 1000) without a window, from seeds N,
+N + 1 and so on, and prints how often each hand won and how long matches took.";
+
+// Options that set a key of the [simulation] section, named alike
+const SETTING_OPTIONS: [&str; 27] = [
+    "--particles",
+    "--populations",
+    "--placement",
+    "--width",
+    "--height",
+    "--boundary",
+    "--max-velocity",
+    "--speed",
+    "--radius",
+    "--size-spread",
+    "--restitution",
+    "--conversion-chance",
+    "--immunity-ticks",
+    "--hit-points",
+    "--frame-ms",
+    "--shrink",
+    "--steering",
+    "--perception",
+    "--alignment",
+    "--cohesion",
+    "--separation",
+    "--mutation",
+    "--pickup-chance",
+    "--map",
+    "--fields",
+    "--broad-phase",
+    "--rules",
+];
+
+// Matches simulate plays unless told otherwise
+const DEFAULT_MATCHES: usize = 1000;
+
+// What the command line asked for
+#[derive(Debug, Default)]
+pub struct Options {
+    pub config: Option<PathBuf>,
+    // Settings keys and their values, in the order given, to apply over the
+    // config file
+    pub settings: Vec<(String, String)>,
+    pub seed: Option<u64>,
+    pub csv: bool,
+    pub headless: bool,
+    pub terminal: bool,
+    pub ticks: Option<u64>,
+    // Set to play this many matches headless and sum them up
+    pub simulate: Option<usize>,
+    pub jobs: Option<usize>,
+    // Set to check a config file, at the given path or the usual one
+    pub check_config: Option<Option<PathBuf>>,
+    pub help: bool,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.iter().peekable();
+        if args.next_if(|arg| *arg == "simulate").is_some() {
+            let matches = args.next_if(|next| !next.starts_with("--"));
+            options.simulate = Some(match matches {
+                Some(matches) => number("simulate", matches, 1..=10_000_000)?,
+                None => DEFAULT_MATCHES,
+            });
+        }
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .map(String::as_str)
+                    .ok_or_else(|| format!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                option if SETTING_OPTIONS.contains(&option) => {
+                    let key = option.trim_start_matches("--").replace('-', "_");
+                    options.settings.push((key, value()?.to_string()));
+                }
+                "--config" => options.config = Some(PathBuf::from(value()?)),
+                "--seed" => options.seed = Some(number(arg, value()?, 0..=u64::MAX)?),
+                "--ticks" => options.ticks = Some(number(arg, value()?, 0..=u64::MAX)?),
+                "--jobs" => options.jobs = Some(number(arg, value()?, 1..=1024)?),
+                "--csv" => options.csv = true,
+                "--headless" => options.headless = true,
+                "--terminal" => options.terminal = true,
+                "--check-config" => {
+                    let path = args.next_if(|next| !next.starts_with("--"));
+                    options.check_config = Some(path.map(PathBuf::from));
+                }
+                "--help" | "-h" => options.help = true,
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+// Reads the value of `option`, which must be within `range`
+fn number<T>(option: &str, value: &str, range: RangeInclusive<T>) -> Result<T, String>
+where
+    T: FromStr + PartialOrd + std::fmt::Display,
+{
+    let number: T = value
+        .parse()
+        .map_err(|_| format!("{} needs a number, not {}", option, value))?;
+    if !range.contains(&number) {
+        return Err(format!(
+            "{} must be from {} to {}, not {}",
+            option,
+            range.start(),
+            range.end(),
+            value
+        ));
+    }
+    Ok(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        let args: Vec<String> = args.split_whitespace().map(str::to_string).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn simulate_takes_an_optional_match_count() {
+        let cases: &[(&str, Option<usize>)] = &[
+            ("", None),
+            ("--headless", None),
+            ("simulate", Some(DEFAULT_MATCHES)),
+            ("simulate 50", Some(50)),
+            ("simulate --seed 3", Some(DEFAULT_MATCHES)),
+            ("simulate 7 --jobs 2", Some(7)),
+        ];
+        for &(args, simulate) in cases {
+            assert_eq!(parse(args).unwrap().simulate, simulate, "{:?}", args);
+        }
+        let options = parse("simulate 7 --jobs 2").unwrap();
+        assert_eq!(options.jobs, Some(2));
+
+        // Only as the first word
+        assert_eq!(
+            parse("--headless simulate").unwrap_err(),
+            "unknown option simulate"
+        );
+        assert!(parse("simulate 0").is_err());
+        assert!(parse("simulate many").is_err());
+    }
+
+    #[test]
+    fn check_config_takes_an_optional_path() {
+        let cases: &[(&str, Option<Option<&str>>)] = &[
+            ("", None),
+            ("--check-config", Some(None)),
+            ("--check-config other.toml", Some(Some("other.toml"))),
+            ("--check-config --seed 1", Some(None)),
+            ("--seed 1 --check-config my.toml", Some(Some("my.toml"))),
+        ];
+        for &(args, check_config) in cases {
+            let expected = check_config.map(|path| path.map(PathBuf::from));
+            assert_eq!(parse(args).unwrap().check_config, expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn numbers_are_checked() {
+        let options = parse("--seed 18446744073709551615 --ticks 0 --jobs 1024").unwrap();
+        assert_eq!(options.seed, Some(u64::MAX));
+        assert_eq!(options.ticks, Some(0));
+        assert_eq!(options.jobs, Some(1024));
+
+        let cases: &[(&str, &str)] = &[
+            ("--seed -1", "--seed needs a number, not -1"),
+            ("--seed x", "--seed needs a number, not x"),
+            (
+                "--seed 18446744073709551616",
+                "--seed needs a number, not 18446744073709551616",
+            ),
+            ("--ticks 1.5", "--ticks needs a number, not 1.5"),
+            ("--jobs 0", "--jobs must be from 1 to 1024, not 0"),
+            ("--jobs 1025", "--jobs must be from 1 to 1024, not 1025"),
+            ("--jobs all", "--jobs needs a number, not all"),
+            ("--seed", "--seed needs a value"),
+            ("--headless --ticks", "--ticks needs a value"),
+        ];
+        for &(args, message) in cases {
+            assert_eq!(parse(args).unwrap_err(), message, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn unknown_options_are_errors() {
+        for args in [
+            "--fast",
+            "-x",
+            "--Seed 3",
+            "--headless stray",
+            "--help --particle 5",
+        ] {
+            let err = parse(args).unwrap_err();
+            assert!(err.starts_with("unknown option"), "{:?}: {}", args, err);
+        }
+    }
+
+    #[test]
+    fn settings_keep_their_order_and_key_names() {
+        let options =
+            parse("--particles 40 --max-velocity 2 --map maps/maze.map --particles 50").unwrap();
+        let settings: Vec<(&str, &str)> = options
+            .settings
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            settings,
+            [
+                ("particles", "40"),
+                ("max_velocity", "2"),
+                ("map", "maps/maze.map"),
+                ("particles", "50"),
+            ]
+        );
+        assert_eq!(parse("--rules").unwrap_err(), "--rules needs a value");
+    }
+
+    #[test]
+    fn every_setting_option_is_a_settings_key() {
+        for option in SETTING_OPTIONS {
+            let key = option.trim_start_matches("--").replace('-', "_");
+            let mut settings = crate::settings::Settings::default();
+            // A bad value is fine, an unknown key is not
+            if let Err(err) = settings.set_from_str(&key, "\u{0}") {
+                assert!(!err.starts_with("unknown key"), "{}: {}", option, err);
+            }
+            assert!(
+                USAGE.contains(option),
+                "{} is missing from the usage",
+                option
+            );
+        }
+    }
+}
@@ -0,0 +1,818 @@
+use std::collections::{HashMap, HashSet};
+use std::thread;
+
+use crate::physics::{Particle, Real, Vec2f, PARTICLE_RADIUS};
+use crate::pool::{Handle, Pool};
+use crate::simulation::Arena;
+
+// Extra distance, on top of the contact distance, within which pairs are kept
+// as candidates. A particle only needs its pairs refreshed once it could end
+// the coming tick more than half of this from where they were last computed.
+const PAIR_CACHE_MARGIN: Real = 4.0 * PARTICLE_RADIUS;
+
+// Candidate pairs to test before the narrow phase is worth splitting up.
+const PARALLEL_NARROW_PHASE_THRESHOLD: usize = 4096;
+
+// Contacts to resolve before the arena is split into islands.
+const ISLAND_THRESHOLD: usize = 1024;
+// Width of the strips the arena is split into for islands. It is fixed, so
+// the split is the same whatever machine the match runs on.
+const ISLAND_WIDTH: Real = 32.0;
+
+// Which broad phase a simulation gathers candidate pairs with. Both find
+// every pair that can touch this tick, so matches play out the same either
+// way; they only differ in how fast they get there.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BroadPhaseKind {
+    // Compares every pair, but only again for particles that moved far
+    PairCache,
+    // Only compares particles in neighbouring cells of a uniform grid
+    #[default]
+    SpatialHash,
+}
+
+#[derive(Clone)]
+pub enum BroadPhase {
+    PairCache(PairCache),
+    SpatialHash(SpatialHash),
+}
+
+impl BroadPhase {
+    pub fn new(kind: BroadPhaseKind) -> Self {
+        match kind {
+            BroadPhaseKind::PairCache => BroadPhase::PairCache(PairCache::new()),
+            BroadPhaseKind::SpatialHash => BroadPhase::SpatialHash(SpatialHash::new()),
+        }
+    }
+
+    pub fn kind(&self) -> BroadPhaseKind {
+        match self {
+            BroadPhase::PairCache(_) => BroadPhaseKind::PairCache,
+            BroadPhase::SpatialHash(_) => BroadPhaseKind::SpatialHash,
+        }
+    }
+
+    // Candidate pairs, each with the lower handle first and sorted. Must
+    // run before particles move.
+    pub fn update(&mut self, particles: &Pool<Particle>, arena: &Arena) {
+        profile_scope!("broad_phase");
+        match self {
+            BroadPhase::PairCache(cache) => cache.update(particles, arena),
+            BroadPhase::SpatialHash(hash) => hash.update(particles, arena),
+        }
+    }
+
+    pub fn pairs(&self) -> &[(Handle, Handle)] {
+        match self {
+            BroadPhase::PairCache(cache) => cache.pairs(),
+            BroadPhase::SpatialHash(hash) => hash.pairs(),
+        }
+    }
+}
+
+// Neighbouring cells to pair a cell with, half of them so that each pair of
+// cells is only visited once
+const HALF_NEIGHBOURHOOD: [(i64, i64); 4] = [(1, -1), (1, 0), (1, 1), (0, 1)];
+
+// Particles bucketed into square cells, rebuilt every tick. Cells are as
+// wide as the furthest two particles can be and still touch by the end of
+// the tick, so only particles in the same or adjacent cells are paired. In a
+// wrapping arena the cells are stretched to fit it exactly, so the ones along
+// an edge neighbour the ones along the opposite edge.
+#[derive(Clone, Default)]
+pub struct SpatialHash {
+    pairs: Vec<(Handle, Handle)>,
+    cells: HashMap<(i64, i64), Vec<Handle>>,
+}
+
+impl SpatialHash {
+    pub fn new() -> Self {
+        SpatialHash::default()
+    }
+
+    pub fn pairs(&self) -> &[(Handle, Handle)] {
+        &self.pairs
+    }
+
+    pub fn update(&mut self, particles: &Pool<Particle>, arena: &Arena) {
+        let reach = particles
+            .values()
+            .map(|particle| particle.radius + particle.reach(arena))
+            .fold(0.0, Real::max);
+        let cell = (2.0 * reach).max(Real::EPSILON);
+        // Cells across and down a wrapping arena, and how wide they are
+        let fit = |size: Real| ((size / cell).floor() as i64).max(1);
+        let columns = fit(arena.width);
+        let rows = fit(arena.height);
+        let (cell_width, cell_height) = if arena.wraps() {
+            (arena.width / columns as Real, arena.height / rows as Real)
+        } else {
+            (cell, cell)
+        };
+
+        self.cells.clear();
+        for (handle, particle) in particles.iter() {
+            let mut key = (
+                (particle.position.x / cell_width).floor() as i64,
+                (particle.position.y / cell_height).floor() as i64,
+            );
+            if arena.wraps() {
+                key = (key.0.clamp(0, columns - 1), key.1.clamp(0, rows - 1));
+            }
+            self.cells.entry(key).or_default().push(handle);
+        }
+
+        self.pairs.clear();
+        for (&(x, y), handles) in &self.cells {
+            for (i, &lhs) in handles.iter().enumerate() {
+                for &rhs in &handles[i + 1..] {
+                    self.pairs.push((lhs.min(rhs), lhs.max(rhs)));
+                }
+            }
+            for (dx, dy) in HALF_NEIGHBOURHOOD {
+                let mut neighbour = (x + dx, y + dy);
+                if arena.wraps() {
+                    neighbour = (
+                        neighbour.0.rem_euclid(columns),
+                        neighbour.1.rem_euclid(rows),
+                    );
+                }
+                // A wrapping arena only a cell or two across can lead back
+                // to the same cell
+                if neighbour == (x, y) {
+                    continue;
+                }
+                let Some(neighbours) = self.cells.get(&neighbour) else {
+                    continue;
+                };
+                for &lhs in handles {
+                    for &rhs in neighbours {
+                        self.pairs.push((lhs.min(rhs), lhs.max(rhs)));
+                    }
+                }
+            }
+        }
+        self.pairs.sort_unstable();
+        // Or pair the same two cells twice
+        self.pairs.dedup();
+    }
+}
+
+// Broad-phase candidate pairs kept across ticks. Each particle has an anchor,
+// the position its pairs were computed from; pairs are only recomputed for
+// particles that wandered away from their anchor, and the whole list is only
+// rebuilt when most of them did or the set of particles changed.
+//
+// The cache is updated before particles move, and covers every pair that can
+// touch by the end of the tick.
+#[derive(Clone, Default)]
+pub struct PairCache {
+    pairs: Vec<(Handle, Handle)>,
+    handles: Vec<Handle>,
+    anchors: HashMap<Handle, Anchor>,
+}
+
+#[derive(Clone)]
+struct Anchor {
+    position: Vec2f,
+    radius: Real,
+    // How much further than half the margin the particle can move in a tick.
+    // Such particles are re-anchored every tick and reach further for pairs.
+    excess: Real,
+}
+
+impl Anchor {
+    fn new(particle: &Particle, arena: &Arena) -> Self {
+        Anchor {
+            position: particle.position,
+            radius: particle.radius,
+            excess: (particle.reach(arena) - PAIR_CACHE_MARGIN / 2.0).max(0.0),
+        }
+    }
+}
+
+impl PairCache {
+    pub fn new() -> Self {
+        PairCache::default()
+    }
+
+    pub fn pairs(&self) -> &[(Handle, Handle)] {
+        &self.pairs
+    }
+
+    pub fn update(&mut self, particles: &Pool<Particle>, arena: &Arena) {
+        let handles = particles.handles();
+        if handles != self.handles {
+            self.handles = handles;
+            self.rebuild(particles, arena);
+            return;
+        }
+
+        let moved: Vec<Handle> = particles
+            .iter()
+            .filter(|(handle, particle)| {
+                let drift = arena
+                    .offset(self.anchors[handle].position, particle.position)
+                    .norm();
+                drift + particle.reach(arena) > PAIR_CACHE_MARGIN / 2.0
+            })
+            .map(|(handle, _)| handle)
+            .collect();
+
+        if moved.is_empty() {
+            return;
+        }
+        if 2 * moved.len() > self.handles.len() {
+            self.rebuild(particles, arena);
+            return;
+        }
+
+        for &handle in &moved {
+            self.anchors
+                .insert(handle, Anchor::new(&particles[handle], arena));
+        }
+
+        let moved: HashSet<Handle> = moved.into_iter().collect();
+        self.pairs
+            .retain(|(lhs, rhs)| !moved.contains(lhs) && !moved.contains(rhs));
+        for &lhs in &moved {
+            for &rhs in &self.handles {
+                // Pairs between two moved particles are found from the lower one
+                if lhs == rhs || (moved.contains(&rhs) && rhs < lhs) {
+                    continue;
+                }
+                if self.is_candidate(lhs, rhs, arena) {
+                    self.pairs.push((lhs.min(rhs), lhs.max(rhs)));
+                }
+            }
+        }
+        self.pairs.sort_unstable();
+    }
+
+    fn rebuild(&mut self, particles: &Pool<Particle>, arena: &Arena) {
+        self.anchors = particles
+            .iter()
+            .map(|(handle, particle)| (handle, Anchor::new(particle, arena)))
+            .collect();
+
+        self.pairs.clear();
+        for (i, &lhs) in self.handles.iter().enumerate() {
+            for &rhs in &self.handles[i + 1..] {
+                if self.is_candidate(lhs, rhs, arena) {
+                    self.pairs.push((lhs, rhs));
+                }
+            }
+        }
+    }
+
+    fn is_candidate(&self, lhs: Handle, rhs: Handle, arena: &Arena) -> bool {
+        let (lhs, rhs) = (&self.anchors[&lhs], &self.anchors[&rhs]);
+        arena.offset(lhs.position, rhs.position).norm()
+            < lhs.radius + rhs.radius + PAIR_CACHE_MARGIN + lhs.excess + rhs.excess
+    }
+}
+
+// Keeps the candidate pairs that are within reach of each other this tick:
+// pairs whose gap is wider than the contact distance plus how far both
+// particles can travel can't touch by the end of it. Must run before
+// particles move.
+pub fn prune_out_of_reach(
+    particles: &Pool<Particle>,
+    arena: &Arena,
+    pairs: &[(Handle, Handle)],
+) -> Vec<(Handle, Handle)> {
+    profile_scope!("prune");
+    pairs
+        .iter()
+        .copied()
+        .filter(|&(lhs, rhs)| {
+            let (lhs, rhs) = (&particles[lhs], &particles[rhs]);
+            let reach = lhs.radius + rhs.radius + lhs.reach(arena) + rhs.reach(arena);
+            let gap = arena.offset(lhs.position, rhs.position);
+            gap.scalar_product(&gap) < reach * reach
+        })
+        .collect()
+}
+
+// Where each particle was before the tick, for telling which ones passed
+// through each other on the way to where they are now
+pub type Starts = HashMap<Handle, Vec2f>;
+
+// Narrow phase: the candidate pairs that touched during the tick, in the same
+// order as `pairs`. Large inputs are split across threads and stitched back
+// together in chunk order, so the result is identical to the serial scan.
+pub fn find_contacts(
+    particles: &Pool<Particle>,
+    starts: &Starts,
+    arena: &Arena,
+    pairs: &[(Handle, Handle)],
+) -> Vec<(Handle, Handle)> {
+    profile_scope!("narrow_phase");
+    // Without threads to spare, as in the browser, it all stays on this one
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    if pairs.len() < PARALLEL_NARROW_PHASE_THRESHOLD || workers < 2 {
+        return contacts(particles, starts, arena, pairs);
+    }
+
+    split_contacts(particles, starts, arena, pairs, workers)
+}
+
+// The same contacts as `contacts`, found on `workers` threads at once
+fn split_contacts(
+    particles: &Pool<Particle>,
+    starts: &Starts,
+    arena: &Arena,
+    pairs: &[(Handle, Handle)],
+    workers: usize,
+) -> Vec<(Handle, Handle)> {
+    let chunk_size = pairs.len().div_ceil(workers);
+    thread::scope(|scope| {
+        let jobs: Vec<_> = pairs
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(|| contacts(particles, starts, arena, chunk)))
+            .collect();
+        jobs.into_iter()
+            .flat_map(|job| job.join().unwrap())
+            .collect()
+    })
+}
+
+fn contacts(
+    particles: &Pool<Particle>,
+    starts: &Starts,
+    arena: &Arena,
+    pairs: &[(Handle, Handle)],
+) -> Vec<(Handle, Handle)> {
+    pairs
+        .iter()
+        .copied()
+        .filter(|&(lhs, rhs)| {
+            let (l, r) = (&particles[lhs], &particles[rhs]);
+            contact_time(arena, l, r, start(starts, lhs, l), start(starts, rhs, r)).is_some()
+        })
+        .collect()
+}
+
+fn start(starts: &Starts, handle: Handle, particle: &Particle) -> Vec2f {
+    starts.get(&handle).copied().unwrap_or(particle.position)
+}
+
+// How far through the tick two particles touched. Ones that met on the way
+// here are taken from the moment they did, whether they passed through each
+// other or ended up overlapping; ones that already touched when it started
+// are taken as they are now (1).
+fn contact_time(
+    arena: &Arena,
+    l: &Particle,
+    r: &Particle,
+    l_start: Vec2f,
+    r_start: Vec2f,
+) -> Option<Real> {
+    let (r, l_start, r_start) = beside(arena, l, r, l_start, r_start);
+    l.time_of_impact(&r, l_start, r_start)
+        .or_else(|| l.collides_width(&r).then_some(1.0))
+}
+
+// A pair as seen from its left particle. In a wrapping arena the right one is
+// moved next to it across whichever edges are between them, and each start is
+// moved next to where its particle is now, so the pair can be handled as if
+// there were no edges. Between walls the pair is left as it is.
+fn beside(
+    arena: &Arena,
+    l: &Particle,
+    r: &Particle,
+    l_start: Vec2f,
+    r_start: Vec2f,
+) -> (Particle, Vec2f, Vec2f) {
+    if !arena.wraps() {
+        return (*r, l_start, r_start);
+    }
+    let mut near = *r;
+    near.position = l.position.plus(arena.offset(l.position, r.position));
+    let l_start = l.position.minus(arena.offset(l_start, l.position));
+    let r_start = near.position.minus(arena.offset(r_start, r.position));
+    (near, l_start, r_start)
+}
+
+// Collides two particles that touched `t` of the way through the tick. Ones
+// that passed through each other are taken back to where they touched, bounce
+// off each other there and spend the rest of the tick on their new course.
+fn collide_at(
+    arena: &Arena,
+    l: &mut Particle,
+    r: &mut Particle,
+    l_start: Vec2f,
+    r_start: Vec2f,
+    t: Real,
+    restitution: Real,
+) {
+    let (mut near, l_start, r_start) = beside(arena, l, r, l_start, r_start);
+    if t >= 1.0 {
+        l.collide(&mut near, restitution);
+    } else {
+        l.position = l_start.lerp(l.position, t);
+        near.position = r_start.lerp(near.position, t);
+        l.collide(&mut near, restitution);
+        l.position = l.position.plus(l.velocity.product(1.0 - t));
+        near.position = near.position.plus(near.velocity.product(1.0 - t));
+    }
+    if arena.wraps() {
+        l.position = arena.wrap(l.position);
+        near.position = arena.wrap(near.position);
+    }
+    *r = near;
+}
+
+// Resolves every contact, in the order given. A particle in a cluster takes
+// part in all of its contacts, and since resolving one contact can push apart
+// a later pair, each is checked again right before it is applied.
+//
+// Only the physics happens here. The contacts that were actually resolved are
+// returned in the order given, however the work was split up, for the caller
+// to settle who won each of them.
+pub fn resolve_contacts(
+    particles: &mut Pool<Particle>,
+    starts: &Starts,
+    arena: &Arena,
+    contacts: &[(Handle, Handle)],
+    restitution: Real,
+) -> Vec<(Handle, Handle)> {
+    profile_scope!("resolve");
+
+    let islands = (arena.width / ISLAND_WIDTH).ceil() as usize;
+    if contacts.len() < ISLAND_THRESHOLD || islands < 2 {
+        resolve_serial(particles, starts, arena, contacts, restitution)
+    } else {
+        resolve_islands(particles, starts, arena, contacts, islands, restitution)
+    }
+}
+
+fn resolve_serial(
+    particles: &mut Pool<Particle>,
+    starts: &Starts,
+    arena: &Arena,
+    contacts: &[(Handle, Handle)],
+    restitution: Real,
+) -> Vec<(Handle, Handle)> {
+    let mut resolved = Vec::new();
+    for &(lhs, rhs) in contacts {
+        let Some((l, r)) = particles.get_pair_mut(lhs, rhs) else {
+            continue;
+        };
+        if resolve(starts, arena, (lhs, l), (rhs, r), restitution) {
+            resolved.push((lhs, rhs));
+        }
+    }
+    resolved
+}
+
+// Resolves one contact if the pair still touches, and tells whether it did
+fn resolve(
+    starts: &Starts,
+    arena: &Arena,
+    (lhs, l): (Handle, &mut Particle),
+    (rhs, r): (Handle, &mut Particle),
+    restitution: Real,
+) -> bool {
+    let (l_start, r_start) = (start(starts, lhs, l), start(starts, rhs, r));
+    let Some(t) = contact_time(arena, l, r, l_start, r_start) else {
+        return false;
+    };
+    collide_at(arena, l, r, l_start, r_start, t, restitution);
+    true
+}
+
+// Splits the contacts into groups that share no particles, so each group
+// comes out the same whenever it is resolved. Groups entirely inside one
+// vertical strip of the arena make up that strip's island, and the strips are
+// resolved on as many threads as there are to spare. Groups that span strips
+// are merged afterwards in a serial pass. The resolved contacts are then put
+// back in the order given.
+fn resolve_islands(
+    particles: &mut Pool<Particle>,
+    starts: &Starts,
+    arena: &Arena,
+    contacts: &[(Handle, Handle)],
+    islands: usize,
+    restitution: Real,
+) -> Vec<(Handle, Handle)> {
+    let strip_of = |particle: &Particle| {
+        let x = particle.position.x.clamp(0.0, arena.width);
+        ((x / ISLAND_WIDTH) as usize).min(islands - 1)
+    };
+
+    // The strip each group is in, by the particle leading it, or None if it
+    // spans more than one
+    let mut groups = Groups::default();
+    for &(lhs, rhs) in contacts {
+        groups.join(lhs, rhs);
+    }
+    let mut group_strips: HashMap<Handle, Option<usize>> = HashMap::new();
+    for &(lhs, rhs) in contacts {
+        let root = groups.root(lhs);
+        for handle in [lhs, rhs] {
+            let strip = strip_of(&particles[handle]);
+            let group = group_strips.entry(root).or_insert(Some(strip));
+            if *group != Some(strip) {
+                *group = None;
+            }
+        }
+    }
+
+    let mut island_contacts = vec![Vec::new(); islands];
+    let mut boundary = Vec::new();
+    for (i, &(lhs, _)) in contacts.iter().enumerate() {
+        match group_strips[&groups.root(lhs)] {
+            Some(strip) => island_contacts[strip].push(i),
+            None => boundary.push(i),
+        }
+    }
+
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = islands.div_ceil(workers);
+    let shared: &Pool<Particle> = particles;
+    let results: Vec<_> = thread::scope(|scope| {
+        let jobs: Vec<_> = island_contacts
+            .chunks(chunk_size)
+            .map(|strips| {
+                scope.spawn(move || {
+                    let indices: Vec<usize> = strips.iter().flatten().copied().collect();
+                    resolve_island(shared, starts, arena, contacts, &indices, restitution)
+                })
+            })
+            .collect();
+        jobs.into_iter().map(|job| job.join().unwrap()).collect()
+    });
+
+    let mut resolved = Vec::new();
+    for (updated, island_resolved) in results {
+        for (handle, particle) in updated {
+            particles[handle] = particle;
+        }
+        resolved.extend(island_resolved);
+    }
+    for i in boundary {
+        let (lhs, rhs) = contacts[i];
+        let Some((l, r)) = particles.get_pair_mut(lhs, rhs) else {
+            continue;
+        };
+        if resolve(starts, arena, (lhs, l), (rhs, r), restitution) {
+            resolved.push(i);
+        }
+    }
+    resolved.sort_unstable();
+    resolved.into_iter().map(|i| contacts[i]).collect()
+}
+
+// Updated particles of one island, and which of the contacts it resolved
+type IslandResult = (Vec<(Handle, Particle)>, Vec<usize>);
+
+// Resolves the contacts at `indices` on copies of their particles and hands
+// back the results, along with the contacts it resolved
+fn resolve_island(
+    particles: &Pool<Particle>,
+    starts: &Starts,
+    arena: &Arena,
+    contacts: &[(Handle, Handle)],
+    indices: &[usize],
+    restitution: Real,
+) -> IslandResult {
+    let mut local: HashMap<Handle, Particle> = HashMap::new();
+    for &i in indices {
+        let (lhs, rhs) = contacts[i];
+        local.entry(lhs).or_insert(particles[lhs]);
+        local.entry(rhs).or_insert(particles[rhs]);
+    }
+
+    let mut resolved = Vec::new();
+    for &i in indices {
+        let (lhs, rhs) = contacts[i];
+        let (mut l, mut r) = (local[&lhs], local[&rhs]);
+        if resolve(starts, arena, (lhs, &mut l), (rhs, &mut r), restitution) {
+            local.insert(lhs, l);
+            local.insert(rhs, r);
+            resolved.push(i);
+        }
+    }
+
+    (local.into_iter().collect(), resolved)
+}
+
+// Particles joined up by the contacts between them, each group led by one of
+// its particles
+#[derive(Default)]
+struct Groups {
+    parents: HashMap<Handle, Handle>,
+}
+
+impl Groups {
+    fn root(&mut self, handle: Handle) -> Handle {
+        let parent = *self.parents.entry(handle).or_insert(handle);
+        if parent == handle {
+            return handle;
+        }
+        let root = self.root(parent);
+        self.parents.insert(handle, root);
+        root
+    }
+
+    fn join(&mut self, lhs: Handle, rhs: Handle) {
+        let (lhs, rhs) = (self.root(lhs), self.root(rhs));
+        if lhs != rhs {
+            self.parents.insert(lhs, rhs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bracket_lib::prelude::RandomNumberGenerator;
+
+    use super::*;
+    use crate::simulation::Params;
+
+    // Particles scattered over the arena from `seed`
+    fn swarm(seed: u64, count: usize) -> Pool<Particle> {
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        let arena = Arena::full_screen();
+        let mut particles = Pool::with_capacity(count);
+        for _ in 0..count {
+            particles.insert(Particle::random(&mut rng, &arena, &Params::default()));
+        }
+        particles
+    }
+
+    // Moves every particle on by a tick, and tells where each one started
+    fn advance(particles: &mut Pool<Particle>, arena: &Arena) -> Starts {
+        let starts = particles
+            .iter()
+            .map(|(handle, particle)| (handle, particle.position))
+            .collect();
+        for particle in particles.values_mut() {
+            particle.update_position();
+            particle.check_wall_collision(arena);
+        }
+        starts
+    }
+
+    #[test]
+    fn parallel_narrow_phase_matches_serial() {
+        let arena = Arena::full_screen();
+        let particles = swarm(215, 800);
+        let mut pair_cache = PairCache::new();
+        pair_cache.update(&particles, &arena);
+        let pairs = pair_cache.pairs();
+        assert!(pairs.len() >= PARALLEL_NARROW_PHASE_THRESHOLD);
+
+        let starts = Starts::new();
+        let serial = contacts(&particles, &starts, &arena, pairs);
+        assert!(!serial.is_empty());
+        assert_eq!(find_contacts(&particles, &starts, &arena, pairs), serial);
+        // However many threads the machine running the test has
+        for workers in [2, 3, 8] {
+            assert_eq!(
+                split_contacts(&particles, &starts, &arena, pairs, workers),
+                serial
+            );
+        }
+    }
+
+    // Everything about every particle, to the last bit
+    fn state(particles: &Pool<Particle>) -> Vec<String> {
+        particles
+            .iter()
+            .map(|(handle, particle)| format!("{:?} {:?}", handle, particle))
+            .collect()
+    }
+
+    #[test]
+    fn islands_match_serial() {
+        let arena = Arena::full_screen();
+        // Sparse enough to make clusters of all sizes rather than one big one
+        let mut particles = swarm(218, 300);
+        let mut pair_cache = PairCache::new();
+        for tick in 0..20 {
+            pair_cache.update(&particles, &arena);
+            let starts = advance(&mut particles, &arena);
+            let contacts = find_contacts(&particles, &starts, &arena, pair_cache.pairs());
+            let mut islands = particles.clone();
+            let serial = resolve_serial(&mut particles, &starts, &arena, &contacts, 1.0);
+            let split = resolve_islands(&mut islands, &starts, &arena, &contacts, 2, 1.0);
+            assert_eq!(serial, split, "tick {}", tick);
+            assert_eq!(state(&particles), state(&islands), "tick {}", tick);
+        }
+    }
+
+    // Not a check but a measurement: how long a tick of physics takes on the
+    // same seeded swarms in whichever precision this was built with, to
+    // compare `cargo test --release -- --ignored --nocapture` with and without
+    // `--features f32`
+    #[test]
+    #[ignore]
+    fn time_ticks() {
+        let arena = Arena::full_screen();
+        const TICKS: u32 = 200;
+        let precision = if cfg!(feature = "f32") { "f32" } else { "f64" };
+        for count in [25, 250, 500] {
+            let mut particles = swarm(221, count);
+            let mut pair_cache = PairCache::new();
+            let start = Instant::now();
+            for _ in 0..TICKS {
+                pair_cache.update(&particles, &arena);
+                let starts = advance(&mut particles, &arena);
+                let contacts = find_contacts(&particles, &starts, &arena, pair_cache.pairs());
+                resolve_contacts(&mut particles, &starts, &arena, &contacts, 1.0);
+            }
+            println!(
+                "{} particles in {}: {:?} a tick",
+                count,
+                precision,
+                start.elapsed() / TICKS
+            );
+        }
+    }
+
+    #[test]
+    fn pruning_keeps_every_contact() {
+        let arena = Arena::full_screen();
+        // Crowded, so collisions keep pushing particles out past the walls,
+        // from where they can be thrown back further than they move
+        let mut particles = swarm(223, 800);
+        let mut outside = 0;
+        for tick in 0..30 {
+            let handles = particles.handles();
+            let mut pairs = Vec::new();
+            for (i, &lhs) in handles.iter().enumerate() {
+                for &rhs in &handles[i + 1..] {
+                    pairs.push((lhs, rhs));
+                }
+            }
+            let pruned = prune_out_of_reach(&particles, &arena, &pairs);
+            outside += particles
+                .values()
+                .filter(|p| p.reach(&arena) > p.velocity.norm())
+                .count();
+
+            let starts = advance(&mut particles, &arena);
+            let contacts = find_contacts(&particles, &starts, &arena, &pairs);
+            assert_eq!(
+                find_contacts(&particles, &starts, &arena, &pruned),
+                contacts,
+                "tick {}",
+                tick
+            );
+            resolve_contacts(&mut particles, &starts, &arena, &contacts, 1.0);
+        }
+        assert!(outside > 0, "no particle was ever left outside the walls");
+    }
+
+    // Not a check but a measurement of what pruning saves on a sparse swarm
+    // of fast particles, where most candidate pairs are nowhere near each
+    // other: `cargo test --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn time_pruning() {
+        let arena = Arena::full_screen();
+        const TICKS: u32 = 200;
+        let mut particles = swarm(223, 100);
+        for particle in particles.values_mut() {
+            particle.velocity = particle.velocity.product(4.0);
+        }
+        let mut pair_cache = PairCache::new();
+        let (mut candidates, mut kept, mut contacts) = (0, 0, 0);
+        let (mut unpruned, mut pruned) = (Duration::ZERO, Duration::ZERO);
+        for _ in 0..TICKS {
+            pair_cache.update(&particles, &arena);
+            let pairs = pair_cache.pairs().to_vec();
+            let start = Instant::now();
+            let within = prune_out_of_reach(&particles, &arena, &pairs);
+            let pruning = start.elapsed();
+
+            let starts = advance(&mut particles, &arena);
+            let start = Instant::now();
+            let found = find_contacts(&particles, &starts, &arena, &pairs);
+            unpruned += start.elapsed();
+            let start = Instant::now();
+            find_contacts(&particles, &starts, &arena, &within);
+            pruned += pruning + start.elapsed();
+
+            candidates += pairs.len();
+            kept += within.len();
+            contacts += found.len();
+            resolve_contacts(&mut particles, &starts, &arena, &found, 1.0);
+        }
+        println!(
+            "{} candidate pairs a tick, {} within reach, {} contacts",
+            candidates / TICKS as usize,
+            kept / TICKS as usize,
+            contacts / TICKS as usize
+        );
+        println!(
+            "narrow phase {:?} a tick, pruned first {:?}",
+            unpruned / TICKS,
+            pruned / TICKS
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use bracket_lib::prelude::*;
+use rock_paper_scissors::simulation::{self, Arena, Params, Simulation, NUM_PARTICLES};
+use std::sync::Arc;
+
+use rock_paper_scissors::{Hand, Real, Rules};
+
+use crate::render::{self, Glyphs};
+use crate::{HUD_LAYER, SCREEN_HEIGHT, SCREEN_WIDTH, UI_LAYER};
+
+// Each side gets half the screen, with a divider column between them
+const VIEWPORT_WIDTH: i32 = (SCREEN_WIDTH - 1) / 2;
+const DIVIDER_COLOR: (u8, u8, u8) = (24, 44, 44);
+const NAMES: [&str; 2] = ["A", "B"];
+
+// How far one key press moves each of B's rules
+const SPEED_STEP: Real = 0.25;
+const MIN_SPEED: Real = 0.25;
+const MAX_SPEED: Real = 4.0;
+const CONVERSION_STEP: Real = 0.05;
+const IMMUNITY_STEP: u32 = 5;
+
+// Two matches run side by side, so a change in the rules or in the seed can
+// be judged against an untouched baseline. Side A always plays the default
+// rules for the hands picked in the settings; side B's can be changed at any
+// time.
+pub struct Comparison {
+    sides: [Simulation; 2],
+    seeds: [u64; 2],
+    // Who won each side, and at which tick
+    results: [Option<(Hand, u64)>; 2],
+}
+
+impl Comparison {
+    pub fn new(rules: Arc<Rules>) -> Self {
+        let seed = RandomNumberGenerator::new().next_u64();
+        let params = Params {
+            rules,
+            ..Params::default()
+        };
+        Comparison {
+            sides: [side(seed, params.clone()), side(seed, params)],
+            seeds: [seed; 2],
+            results: [None; 2],
+        }
+    }
+
+    // Starts both sides over, keeping B's rules
+    fn restart(&mut self, same_seed: bool) {
+        let mut rng = RandomNumberGenerator::new();
+        let seed = rng.next_u64();
+        self.seeds = if same_seed {
+            [seed; 2]
+        } else {
+            [seed, rng.next_u64()]
+        };
+        let baseline = Params {
+            rules: self.sides[1].params.rules.clone(),
+            ..Params::default()
+        };
+        self.sides = [
+            side(self.seeds[0], baseline),
+            side(self.seeds[1], self.sides[1].params.clone()),
+        ];
+        self.results = [None; 2];
+    }
+
+    // Advances every side that is still undecided
+    pub fn step(&mut self) {
+        for (sim, result) in self.sides.iter_mut().zip(&mut self.results) {
+            if result.is_some() {
+                continue;
+            }
+            if let Some(winner) = simulation::winner(&sim.step()) {
+                *result = Some((winner, sim.ticks));
+            }
+        }
+    }
+
+    pub fn handle_key(&mut self, key: VirtualKeyCode) {
+        let b = &mut self.sides[1];
+        match key {
+            VirtualKeyCode::S => self.restart(true),
+            VirtualKeyCode::D => self.restart(false),
+            VirtualKeyCode::Left => b.set_speed((b.params.speed - SPEED_STEP).max(MIN_SPEED)),
+            VirtualKeyCode::Right => b.set_speed((b.params.speed + SPEED_STEP).min(MAX_SPEED)),
+            VirtualKeyCode::Up => {
+                let chance = b.params.conversion_chance + CONVERSION_STEP;
+                b.params.conversion_chance = chance.min(1.0);
+            }
+            VirtualKeyCode::Down => {
+                let chance = b.params.conversion_chance - CONVERSION_STEP;
+                b.params.conversion_chance = chance.max(0.0);
+            }
+            VirtualKeyCode::LBracket => {
+                b.params.immunity_ticks = b.params.immunity_ticks.saturating_sub(IMMUNITY_STEP);
+            }
+            VirtualKeyCode::RBracket => b.params.immunity_ticks += IMMUNITY_STEP,
+            _ => {}
+        }
+    }
+
+    // Draws both sides, each with its own HUD
+    pub fn draw(&self, ctx: &mut BTerm, glyphs: &Glyphs) {
+        ctx.set_active_console(UI_LAYER);
+        for y in 0..SCREEN_HEIGHT {
+            ctx.set(
+                VIEWPORT_WIDTH,
+                y,
+                WHITE,
+                RGB::from(DIVIDER_COLOR),
+                to_cp437(' '),
+            );
+        }
+        // Each side's HUD goes over it in the HUD's own cells
+        ctx.set_active_console(HUD_LAYER);
+        let (width, _) = ctx.get_char_size();
+
+        for (i, sim) in self.sides.iter().enumerate() {
+            let left = i as i32 * (VIEWPORT_WIDTH + 1);
+            render::render_particles(sim.particles.values(), Point::new(left, 0), glyphs);
+            let x = left * width as i32 / SCREEN_WIDTH;
+
+            ctx.print(x, 0, format!("{}: seed {}", NAMES[i], self.seeds[i]));
+            ctx.print(x, 1, format!("Tick {}", sim.ticks));
+            let rules = &sim.params.rules;
+            let counts = sim.counts();
+            for hand in rules.hands() {
+                let row = 2 + hand.index() as i32;
+                ctx.print(
+                    x,
+                    row,
+                    format!("{}: {}", rules.name(hand), counts[hand.index()]),
+                );
+            }
+            // Below the hands, however many there are
+            let y = 2 + rules.len() as i32;
+            ctx.print(
+                x,
+                y,
+                format!(
+                    "Speed {:.2}x, conv. {:.0}%",
+                    sim.params.speed,
+                    sim.params.conversion_chance * 100.0
+                ),
+            );
+            ctx.print(
+                x,
+                y + 1,
+                format!("Immunity {} ticks", sim.params.immunity_ticks),
+            );
+            if let Some((winner, tick)) = self.results[i] {
+                let winner = rules.name(winner);
+                ctx.print(x, y + 2, format!("{} won at tick {}", winner, tick));
+            }
+        }
+
+        ctx.print(
+            0,
+            SCREEN_HEIGHT - 3,
+            "B: (Left/Right) speed  (Up/Down) conversion",
+        );
+        ctx.print(0, SCREEN_HEIGHT - 2, "   ([/]) immunity");
+        ctx.print(
+            0,
+            SCREEN_HEIGHT - 1,
+            "(S) Same seed  (D) Different seeds  (Q) Menu",
+        );
+    }
+}
+
+fn side(seed: u64, params: Params) -> Simulation {
+    let arena = Arena {
+        width: VIEWPORT_WIDTH as Real,
+        height: SCREEN_HEIGHT as Real,
+        ..Arena::full_screen()
+    };
+    let mut sim = Simulation::new(arena, params, RandomNumberGenerator::seeded(seed));
+    sim.spawn_random(NUM_PARTICLES);
+    sim
+}
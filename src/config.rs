@@ -0,0 +1,1085 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bracket_lib::prelude::*;
+use rock_paper_scissors::collision::BroadPhaseKind;
+use rock_paper_scissors::forces;
+use rock_paper_scissors::obstacle;
+use rock_paper_scissors::simulation::{Boundary, Placement};
+use rock_paper_scissors::{Hand, Real, Rules, Species};
+
+use crate::paths;
+use crate::settings::Settings;
+
+// Read at startup from the working directory if it is there, and from the
+// config directory otherwise
+pub const CONFIG_FILE: &str = "rps.toml";
+
+// Fonts bundled in resources/, by the side of their tiles in pixels. They
+// only differ in resolution.
+pub const FONTS: [(u32, &str); 3] = [(8, "font-8.png"), (16, "font-16.png"), (64, "font.png")];
+// Longer HUD lines stop fitting on screen past this scale
+const MAX_UI_SCALE: u32 = 2;
+const MIN_HUD_PIXELS: f32 = 16.0;
+// Most positions a particle's trail can go back
+const MAX_TRAIL_LENGTH: i64 = 50;
+
+// Settings read from the config file. It is a small subset of TOML:
+//
+//   # comment
+//   [section]
+//   key = 12        (or 1.5, true, "text")
+//
+// Anything missing keeps its default.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    // What matches are made of, before the command line has its say
+    pub settings: Settings,
+    pub glyphs: GlyphConfig,
+    pub display: Display,
+    pub export: Export,
+    // Hand colours to use instead of the theme's, by hand name in lower case
+    pub colors: HashMap<String, RGB>,
+    // The rule set of the [rules] section, if there is one
+    pub rules: Option<Rules>,
+}
+
+// Which font glyphs hands are drawn with, by hand name in lower case. Hands
+// left out are drawn with their own tile.
+#[derive(Clone, Debug)]
+pub struct GlyphConfig {
+    // Centre of a hand's 3x3 art. The rest of the art is laid out around it
+    // in the font: one code to the left and right, one row of 16 codes up and
+    // down. The bundled fonts have art for the classic hands only.
+    pub art: HashMap<String, FontCharType>,
+    // Drawn alone in place of the art, for fonts or hands that have none
+    pub tiles: HashMap<String, FontCharType>,
+    pub use_art: bool,
+}
+
+impl Default for GlyphConfig {
+    fn default() -> Self {
+        let art = [("rock", 199), ("paper", 193), ("scissors", 196)];
+        GlyphConfig {
+            art: art
+                .into_iter()
+                .map(|(name, code)| (name.to_string(), code))
+                .collect(),
+            tiles: HashMap::new(),
+            use_art: true,
+        }
+    }
+}
+
+// How the window is laid out and what is drawn in it. The window can't be
+// resized once it is open, so the font and HUD scale only take effect on the
+// next start.
+#[derive(Copy, Clone, Debug)]
+pub struct Display {
+    // Side of a tile in pixels, one of the bundled fonts
+    pub font_size: u32,
+    // How many tiles across each HUD character takes, or None to follow the
+    // screen's DPI
+    pub ui_scale: Option<u32>,
+    // Ticks of past positions drawn behind each particle, none at all for 0,
+    // and how much of its colour each one loses on the one after it
+    pub trail_length: usize,
+    pub trail_fade: f32,
+    // Whether the corners of the arena are decorated
+    pub decorations: bool,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Display {
+            font_size: 64,
+            ui_scale: None,
+            trail_length: 0,
+            trail_fade: 0.2,
+            decorations: false,
+        }
+    }
+}
+
+impl Display {
+    pub fn font_file(&self) -> &'static str {
+        FONTS
+            .iter()
+            .find(|(size, _)| *size == self.font_size)
+            .map_or(FONTS[FONTS.len() - 1].1, |(_, file)| file)
+    }
+
+    // The HUD scale to use right now. Automatic scaling makes HUD characters
+    // at least MIN_HUD_PIXELS tall on the physical screen.
+    pub fn ui_scale(&self) -> u32 {
+        if let Some(scale) = self.ui_scale {
+            return scale;
+        }
+        let dpi = INPUT.lock().get_scale_factor() as f32;
+        let wanted = (MIN_HUD_PIXELS * dpi / self.font_size as f32).ceil() as u32;
+        wanted.clamp(1, MAX_UI_SCALE)
+    }
+
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match (key, value) {
+            ("ui_scale", Value::String(scale)) if scale == "auto" => {
+                self.ui_scale = None;
+                Ok(())
+            }
+            ("ui_scale", Value::Integer(scale)) if (1..=MAX_UI_SCALE as i64).contains(scale) => {
+                self.ui_scale = Some(*scale as u32);
+                Ok(())
+            }
+            ("ui_scale", _) => Err(format!(
+                "ui_scale must be \"auto\" or 1 to {}, not {}",
+                MAX_UI_SCALE, value
+            )),
+            ("font", Value::Integer(size)) if FONTS.iter().any(|(s, _)| *s as i64 == *size) => {
+                self.font_size = *size as u32;
+                Ok(())
+            }
+            ("font", _) => {
+                let sizes: Vec<String> = FONTS.iter().map(|(size, _)| size.to_string()).collect();
+                Err(format!(
+                    "font must be one of {}, not {}",
+                    sizes.join(", "),
+                    value
+                ))
+            }
+            ("trail_length", _) => {
+                self.trail_length = integer(key, value, 0..=MAX_TRAIL_LENGTH)? as usize;
+                Ok(())
+            }
+            ("trail_fade", _) => {
+                self.trail_fade = number(key, value, 0.0..=1.0)? as f32;
+                Ok(())
+            }
+            ("decorations", Value::Bool(decorations)) => {
+                self.decorations = *decorations;
+                Ok(())
+            }
+            ("decorations", _) => Err(format!("decorations must be true or false, not {}", value)),
+            _ => Err(format!("unknown key `{}` in [display]", key)),
+        }
+    }
+}
+
+// Files written as matches are played
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Export {
+    // A CSV of the populations at every tick of every match, with a summary
+    // at the end
+    pub csv: bool,
+}
+
+impl Export {
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match (key, value) {
+            ("csv", Value::Bool(csv)) => self.csv = *csv,
+            ("csv", _) => return Err(format!("csv must be true or false, not {}", value)),
+            _ => return Err(format!("unknown key `{}` in [export]", key)),
+        }
+        Ok(())
+    }
+}
+
+// A problem with one line of the config file
+#[derive(Debug)]
+pub struct ConfigError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+impl Config {
+    // The config file to read: the one in the working directory, or else the
+    // one in the config directory
+    pub fn path() -> PathBuf {
+        let local = PathBuf::from(CONFIG_FILE);
+        if local.exists() {
+            local
+        } else {
+            paths::config_dir().join(CONFIG_FILE)
+        }
+    }
+
+    // Reads the config file. Lines with problems are skipped and reported,
+    // and a missing file is no problem at all.
+    pub fn load(path: &Path) -> io::Result<(Config, Vec<ConfigError>)> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Config::parse(&contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                Ok((Config::default(), Vec::new()))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn parse(contents: &str) -> (Config, Vec<ConfigError>) {
+        let mut config = Config::default();
+        let mut errors = Vec::new();
+        let mut section = String::new();
+        let mut seen = HashSet::new();
+        // Hands are only known once the whole file is read, so the lines
+        // that define or name them are kept for the end
+        let mut rules = RulesSection::default();
+        let mut hand_keys = Vec::new();
+
+        for (i, line) in contents.lines().enumerate() {
+            let line_number = i + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let result = if let Some(name) = line.strip_prefix('[') {
+                match name.strip_suffix(']') {
+                    Some(name) => {
+                        section = name.trim().to_string();
+                        if section == "rules" {
+                            rules.line = line_number;
+                        }
+                        config.check_section(&section)
+                    }
+                    None => Err("section header is missing its closing ]".to_string()),
+                }
+            } else {
+                match line.split_once('=') {
+                    Some((key, _)) if !seen.insert((section.clone(), key.trim().to_string())) => {
+                        Err(format!("`{}` is set more than once", key.trim()))
+                    }
+                    Some((key, value)) if section == "rules" => {
+                        parse_value(value.trim()).map(|value| {
+                            rules
+                                .keys
+                                .push((line_number, key.trim().to_string(), value))
+                        })
+                    }
+                    Some((key, value)) => {
+                        let key = key.trim();
+                        if section == "glyphs" || section == "colors" {
+                            hand_keys.push((line_number, key.to_string()));
+                        }
+                        parse_value(value.trim())
+                            .and_then(|value| config.set(&section, key, &value))
+                    }
+                    None => Err("expected `key = value`".to_string()),
+                }
+            };
+
+            if let Err(message) = result {
+                errors.push(ConfigError {
+                    line: line_number,
+                    message,
+                });
+            }
+        }
+
+        if !rules.keys.is_empty() {
+            match rules.build() {
+                Ok(rules) => {
+                    config.settings.params.rules = Arc::new(rules.clone());
+                    config.rules = Some(rules);
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+        errors.extend(config.check_hand_keys(&hand_keys));
+        errors.sort_by_key(|err| err.line);
+
+        (config, errors)
+    }
+
+    // Glyphs and colours can be set for any hand the game knows: those of
+    // the built in rules and of the [rules] section
+    fn check_hand_keys(&self, keys: &[(usize, String)]) -> Vec<ConfigError> {
+        let mut rule_sets = Rules::presets();
+        rule_sets.extend(self.rules.clone());
+        let known = |name: &str| rule_sets.iter().any(|rules| rules.find(name).is_some());
+        keys.iter()
+            .filter(|(_, key)| key != "layout")
+            .filter(|(_, key)| !known(key) && !known(key.trim_end_matches("_tile")))
+            .map(|(line, key)| ConfigError {
+                line: *line,
+                message: format!("no hand is called `{}`", key.trim_end_matches("_tile")),
+            })
+            .collect()
+    }
+
+    fn check_section(&self, section: &str) -> Result<(), String> {
+        match section {
+            "simulation" | "glyphs" | "display" | "colors" | "rules" | "export" => Ok(()),
+            _ => Err(format!("unknown section [{}]", section)),
+        }
+    }
+
+    fn set(&mut self, section: &str, key: &str, value: &Value) -> Result<(), String> {
+        match section {
+            "simulation" => self.settings.set(key, value),
+            "glyphs" => self.glyphs.set(key, value),
+            "display" => self.display.set(key, value),
+            "export" => self.export.set(key, value),
+            "colors" => self.set_color(key, value),
+            "" => Err(format!("`{}` must be in a section", key)),
+            // Already reported at the section header
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Settings {
+    // Sets `key` from text on the command line, read like a config value.
+    // Strings don't need their quotes there.
+    pub fn set_from_str(&mut self, key: &str, text: &str) -> Result<(), String> {
+        let value = parse_value(text).unwrap_or_else(|_| Value::String(text.to_string()));
+        self.set(key, &value)
+    }
+
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        let params = &mut self.params;
+        match key {
+            "particles" => {
+                self.particles = integer(key, value, 1..=100_000)? as usize;
+                self.populations.clear();
+            }
+            "populations" => {
+                self.populations = populations(value)?;
+                self.particles = self.populations.iter().sum();
+            }
+            "placement" => {
+                self.placement = match value {
+                    Value::String(placement) if placement == "random" => Placement::Random,
+                    Value::String(placement) if placement == "corners" => Placement::Corners,
+                    Value::String(placement) if placement == "rings" => Placement::Rings,
+                    _ => {
+                        return Err(format!(
+                            "placement must be \"random\", \"corners\" or \"rings\", not {}",
+                            value
+                        ))
+                    }
+                }
+            }
+            "width" => self.arena.width = number(key, value, 10.0..=10_000.0)? as Real,
+            "height" => self.arena.height = number(key, value, 10.0..=10_000.0)? as Real,
+            "boundary" => {
+                self.arena.boundary = match value {
+                    Value::String(boundary) if boundary == "walls" => Boundary::Walls,
+                    Value::String(boundary) if boundary == "wrap" => Boundary::Wrap,
+                    _ => {
+                        return Err(format!(
+                            "boundary must be \"walls\" or \"wrap\", not {}",
+                            value
+                        ))
+                    }
+                }
+            }
+            "max_velocity" => params.max_velocity = number(key, value, 0.01..=10.0)? as Real,
+            "speed" => params.speed = number(key, value, 0.1..=10.0)? as Real,
+            "radius" => params.radius = number(key, value, 0.1..=10.0)? as Real,
+            "size_spread" => params.size_spread = number(key, value, 0.0..=0.9)? as Real,
+            "restitution" => params.restitution = number(key, value, 0.0..=1.0)? as Real,
+            "shrink" => params.shrink = number(key, value, 0.0..=1.0)? as Real,
+            "steering" => params.steering = number(key, value, 0.0..=1.0)? as Real,
+            "perception" => params.perception = number(key, value, 1.0..=100.0)? as Real,
+            "alignment" => params.alignment = number(key, value, 0.0..=1.0)? as Real,
+            "cohesion" => params.cohesion = number(key, value, 0.0..=1.0)? as Real,
+            "separation" => params.separation = number(key, value, 0.0..=1.0)? as Real,
+            "mutation" => params.mutation = number(key, value, 0.0..=0.1)? as Real,
+            "conversion_chance" => {
+                params.conversion_chance = number(key, value, 0.0..=1.0)? as Real
+            }
+            "immunity_ticks" => params.immunity_ticks = integer(key, value, 0..=10_000)? as u32,
+            "hit_points" => params.hit_points = integer(key, value, 1..=100)? as u32,
+            "pickup_chance" => params.pickup_chance = number(key, value, 0.0..=1.0)? as Real,
+            "broad_phase" => {
+                params.broad_phase = match value {
+                    Value::String(kind) if kind == "spatial_hash" => BroadPhaseKind::SpatialHash,
+                    Value::String(kind) if kind == "pair_cache" => BroadPhaseKind::PairCache,
+                    _ => {
+                        return Err(format!(
+                            "broad_phase must be \"spatial_hash\" or \"pair_cache\", not {}",
+                            value
+                        ))
+                    }
+                }
+            }
+            "rules" => {
+                params.rules = match value {
+                    Value::String(rules) if rules == "classic" => Arc::new(Rules::classic()),
+                    Value::String(rules) if rules == "lizard_spock" => {
+                        Arc::new(Rules::lizard_spock())
+                    }
+                    _ => {
+                        return Err(format!(
+                            "rules must be \"classic\" or \"lizard_spock\", not {}",
+                            value
+                        ))
+                    }
+                }
+            }
+            "map" => {
+                let Value::String(path) = value else {
+                    return Err(format!("map must be the path of a map file, not {}", value));
+                };
+                let contents = fs::read_to_string(path)
+                    .map_err(|err| format!("can't read map {}: {}", path, err))?;
+                let map = obstacle::parse_map(&contents)
+                    .map_err(|err| format!("map {}: {}", path, err))?;
+                params.obstacles = Arc::new(map.obstacles);
+                params.zones = Arc::new(map.zones);
+                params.portals = Arc::new(map.portals);
+            }
+            "fields" => {
+                let Value::String(text) = value else {
+                    return Err(format!("fields must be a string, not {}", value));
+                };
+                let fields =
+                    forces::parse_fields(text).map_err(|err| format!("fields: {}", err))?;
+                params.fields = Arc::new(fields);
+            }
+            "frame_ms" => self.frame_duration = number(key, value, 1.0..=1000.0)? as f32,
+            _ => return Err(format!("unknown key `{}` in [simulation]", key)),
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    // Whether the key names a hand is checked once the file is read
+    fn set_color(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        self.colors.insert(key.to_lowercase(), color(value)?);
+        Ok(())
+    }
+}
+
+// The [rules] section, kept as read until it can be checked as a whole:
+//
+//   [rules]
+//   name = "Rock Paper Scissors Lizard Spock"
+//   rock = "R #a0a0a0 beats scissors lizard"
+//
+// Every key but `name` is a hand, in the order they come: the tile it is
+// drawn with, its colour and the hands it beats.
+#[derive(Default)]
+struct RulesSection {
+    // Where the section starts
+    line: usize,
+    keys: Vec<(usize, String, Value)>,
+}
+
+impl RulesSection {
+    fn build(&self) -> Result<Rules, ConfigError> {
+        let mut name = "Custom".to_string();
+        let mut species = Vec::new();
+        let mut beaten = Vec::new();
+        for (line, key, value) in &self.keys {
+            let error = |message: String| ConfigError {
+                line: *line,
+                message,
+            };
+            if key == "name" {
+                name = match value {
+                    Value::String(text) => text.clone(),
+                    _ => return Err(error(format!("name must be a string, not {}", value))),
+                };
+                continue;
+            }
+            let (hand, beats) = species_entry(key, value).map_err(error)?;
+            species.push(hand);
+            beaten.push((*line, beats));
+        }
+
+        let mut beats = Vec::new();
+        for (winner, (line, losers)) in beaten.iter().enumerate() {
+            for loser in losers {
+                let Some(loser) = species
+                    .iter()
+                    .position(|species| species.name.eq_ignore_ascii_case(loser))
+                else {
+                    return Err(ConfigError {
+                        line: *line,
+                        message: format!("no hand is called `{}`", loser),
+                    });
+                };
+                beats.push((winner, loser));
+            }
+        }
+        let beats: Vec<(Hand, Hand)> = beats
+            .into_iter()
+            .filter_map(|(w, l)| Some((Hand::from_index(w)?, Hand::from_index(l)?)))
+            .collect();
+        Rules::new(&name, species, &beats).map_err(|message| ConfigError {
+            line: self.line,
+            message,
+        })
+    }
+}
+
+// One hand of the [rules] section, and the names of the hands it beats
+fn species_entry(key: &str, value: &Value) -> Result<(Species, Vec<String>), String> {
+    let usage = || {
+        format!(
+            "{} must look like \"R #a0a0a0 beats scissors\", not {}",
+            key, value
+        )
+    };
+    // Names are saved with the run history as one word
+    if key.contains(char::is_whitespace) {
+        return Err(format!("hand names can't have spaces, as in `{}`", key));
+    }
+    let Value::String(text) = value else {
+        return Err(usage());
+    };
+    let mut words = text.split_whitespace();
+    let mut tile = words.next().ok_or_else(usage)?.chars();
+    let (Some(glyph), None) = (tile.next(), tile.next()) else {
+        return Err(usage());
+    };
+    let color = words.next().and_then(hex_color).ok_or_else(usage)?;
+    let beats = match words.next() {
+        Some("beats") => words.map(str::to_string).collect(),
+        Some(_) => return Err(usage()),
+        None => Vec::new(),
+    };
+
+    // Shown with a capital, like the classic hands
+    let mut name: String = key.chars().take(1).flat_map(char::to_uppercase).collect();
+    name.extend(key.chars().skip(1));
+    Ok((Species { name, glyph, color }, beats))
+}
+
+impl GlyphConfig {
+    // Whether the key names a hand is checked once the file is read
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        if key == "layout" {
+            self.use_art = match value {
+                Value::String(layout) if layout == "art" => true,
+                Value::String(layout) if layout == "tile" => false,
+                _ => return Err(format!("layout must be \"art\" or \"tile\", not {}", value)),
+            };
+            return Ok(());
+        }
+
+        match key.strip_suffix("_tile") {
+            Some(name) => {
+                self.tiles
+                    .insert(name.to_lowercase(), glyph(value, 0..=255)?);
+            }
+            None => {
+                // The art reaches one glyph and one font row around its centre
+                self.art.insert(key.to_lowercase(), glyph(value, 17..=238)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+// A glyph given by its code or as a one character string
+fn glyph(value: &Value, range: RangeInclusive<i64>) -> Result<FontCharType, String> {
+    let code = match value {
+        Value::Integer(code) => *code,
+        Value::String(text) if text.chars().count() == 1 => {
+            to_cp437(text.chars().next().unwrap()) as i64
+        }
+        _ => {
+            return Err(format!(
+                "expected a glyph code or a single character, not {}",
+                value
+            ))
+        }
+    };
+    if !range.contains(&code) {
+        return Err(format!(
+            "glyph code {} is out of range ({} to {})",
+            code,
+            range.start(),
+            range.end()
+        ));
+    }
+    Ok(code as FontCharType)
+}
+
+fn integer(key: &str, value: &Value, range: RangeInclusive<i64>) -> Result<i64, String> {
+    match value {
+        Value::Integer(number) if range.contains(number) => Ok(*number),
+        _ => Err(format!(
+            "{} must be a whole number from {} to {}, not {}",
+            key,
+            range.start(),
+            range.end(),
+            value
+        )),
+    }
+}
+
+// Starting counts of the hands, in the order of the rules, as a string of
+// whole numbers apart by spaces or commas: "20 20 5"
+fn populations(value: &Value) -> Result<Vec<usize>, String> {
+    let usage = || {
+        format!(
+            "populations must be whole numbers for each hand, like \"20 20 5\", not {}",
+            value
+        )
+    };
+    let Value::String(text) = value else {
+        return Err(usage());
+    };
+    let counts: Vec<usize> = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.parse().ok())
+        .collect::<Option<_>>()
+        .ok_or_else(usage)?;
+    if counts.len() < 2 {
+        return Err(usage());
+    }
+    if !(1..=100_000).contains(&counts.iter().sum::<usize>()) {
+        return Err("populations must add up to 1 to 100000 particles".to_string());
+    }
+    Ok(counts)
+}
+
+// A number, with or without a fractional part
+fn number(key: &str, value: &Value, range: RangeInclusive<f64>) -> Result<f64, String> {
+    let number = match value {
+        Value::Integer(number) => *number as f64,
+        Value::Float(number) => *number,
+        _ => f64::NAN,
+    };
+    if !range.contains(&number) {
+        return Err(format!(
+            "{} must be a number from {} to {}, not {}",
+            key,
+            range.start(),
+            range.end(),
+            value
+        ));
+    }
+    Ok(number)
+}
+
+// A colour given as "#rrggbb"
+fn color(value: &Value) -> Result<RGB, String> {
+    let rgb = match value {
+        Value::String(text) => hex_color(text),
+        _ => None,
+    };
+    match rgb {
+        Some((r, g, b)) => Ok(RGB::from_u8(r, g, b)),
+        None => Err(format!("expected a colour like \"#a0a0a0\", not {}", value)),
+    }
+}
+
+fn hex_color(text: &str) -> Option<(u8, u8, u8)> {
+    let hex = text.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    Some((channel(0), channel(2), channel(4)))
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    if let Some(text) = text.strip_prefix('"') {
+        return match text.strip_suffix('"') {
+            Some(text) => Ok(Value::String(text.to_string())),
+            None => Err("string is missing its closing quote".to_string()),
+        };
+    }
+    match text {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+    if let Ok(value) = text.parse() {
+        return Ok(Value::Integer(value));
+    }
+    if let Ok(value) = text.parse() {
+        return Ok(Value::Float(value));
+    }
+    Err(format!("can't read the value `{}`", text))
+}
+
+// Drops a trailing comment, leaving any # inside a string alone
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    // The lines and messages of the errors in `contents`
+    fn problems(contents: &str) -> Vec<(usize, String)> {
+        let (_, errors) = Config::parse(contents);
+        errors
+            .into_iter()
+            .map(|err| (err.line, err.message))
+            .collect()
+    }
+
+    fn temp_map(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("rps-{}-{}.map", process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn good_file_has_no_errors() {
+        let contents = "\
+            # a comment\n\
+            [simulation]\n\
+            particles = 50   # trailing comment\n\
+            placement = \"corners\"\n\
+            boundary = \"wrap\"\n\
+            speed = 1.5\n\
+            [glyphs]\n\
+            layout = \"tile\"\n\
+            rock_tile = \"#\"\n\
+            [display]\n\
+            ui_scale = \"auto\"\n\
+            font = 16\n\
+            trail_length = 10\n\
+            decorations = true\n\
+            [export]\n\
+            csv = true\n\
+            [colors]\n\
+            paper = \"#102030\"\n";
+        let (config, errors) = Config::parse(contents);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        assert_eq!(config.settings.particles, 50);
+        assert_eq!(config.settings.placement, Placement::Corners);
+        assert_eq!(config.settings.arena.boundary, Boundary::Wrap);
+        assert_eq!(config.settings.params.speed, 1.5);
+        assert!(!config.glyphs.use_art);
+        assert_eq!(config.glyphs.tiles["rock"], to_cp437('#'));
+        assert_eq!(config.display.ui_scale, None);
+        assert_eq!(config.display.font_size, 16);
+        assert_eq!(config.display.trail_length, 10);
+        assert!(config.display.decorations);
+        assert!(config.export.csv);
+        assert_eq!(config.colors["paper"], RGB::from_u8(0x10, 0x20, 0x30));
+    }
+
+    #[test]
+    fn errors_name_their_line() {
+        let cases: &[(&str, usize, &str)] = &[
+            (
+                "[simulation]\nspeed = 1\nspeed = 2",
+                3,
+                "`speed` is set more than once",
+            ),
+            (
+                "[display]\nfont = 16\n\nfont = 8",
+                4,
+                "`font` is set more than once",
+            ),
+            (
+                "[simulation]\ngravity = 1",
+                2,
+                "unknown key `gravity` in [simulation]",
+            ),
+            (
+                "[glyphs]\nlayout = \"art\"\n[display]\nzoom = 2",
+                4,
+                "unknown key `zoom` in [display]",
+            ),
+            ("[export]\npng = true", 2, "unknown key `png` in [export]"),
+            ("[sound]", 1, "unknown section [sound]"),
+            ("[simulation", 1, "section header is missing its closing ]"),
+            ("particles = 5", 1, "`particles` must be in a section"),
+            ("[simulation]\nparticles", 2, "expected `key = value`"),
+            (
+                "[simulation]\nplacement = \"random",
+                2,
+                "string is missing its closing quote",
+            ),
+            (
+                "[simulation]\nspeed = fast",
+                2,
+                "can't read the value `fast`",
+            ),
+            ("[colors]\nmud = \"#000000\"", 2, "no hand is called `mud`"),
+            ("[glyphs]\nmud_tile = 1", 2, "no hand is called `mud`"),
+        ];
+        for &(contents, line, message) in cases {
+            assert_eq!(
+                problems(contents),
+                vec![(line, message.to_string())],
+                "{:?}",
+                contents
+            );
+        }
+    }
+
+    #[test]
+    fn same_key_in_another_section_is_no_duplicate() {
+        assert!(problems("[glyphs]\nrock = 20\n[colors]\nrock = \"#000000\"").is_empty());
+        assert!(problems("[glyphs]\nrock = 20\n[glyphs]\nrock = 21")
+            .iter()
+            .any(|(line, _)| *line == 4));
+    }
+
+    #[test]
+    fn bad_values_are_errors() {
+        let cases: &[(&str, &str, &str)] = &[
+            ("simulation", "placement", "\"spiral\""),
+            ("simulation", "boundary", "\"bouncy\""),
+            ("simulation", "broad_phase", "\"grid\""),
+            ("simulation", "rules", "\"chess\""),
+            ("simulation", "fields", "7"),
+            ("simulation", "populations", "\"20\""),
+            ("simulation", "populations", "\"20 x 5\""),
+            ("simulation", "populations", "\"0 0\""),
+            ("simulation", "populations", "\"60000 60000\""),
+            ("glyphs", "layout", "\"pixels\""),
+            ("glyphs", "rock", "\"ab\""),
+            ("glyphs", "rock", "5"),
+            ("glyphs", "rock_tile", "256"),
+            ("display", "ui_scale", "3"),
+            ("display", "ui_scale", "\"big\""),
+            ("display", "font", "12"),
+            ("display", "trail_length", "51"),
+            ("display", "trail_fade", "1.5"),
+            ("display", "decorations", "1"),
+            ("export", "csv", "\"yes\""),
+            ("colors", "rock", "\"#12345\""),
+            ("colors", "rock", "\"red\""),
+            ("colors", "rock", "255"),
+        ];
+        for &(section, key, value) in cases {
+            let contents = format!("[{}]\n{} = {}", section, key, value);
+            let errors = problems(&contents);
+            assert_eq!(errors.len(), 1, "{:?}: {:?}", contents, errors);
+            assert_eq!(errors[0].0, 2, "{:?}", contents);
+        }
+    }
+
+    #[test]
+    fn settings_are_range_checked() {
+        // A key, a value at each end of its range and one past either end
+        let cases: &[(&str, &str, &str, &str, &str)] = &[
+            ("particles", "1", "100000", "0", "100001"),
+            ("width", "10", "10000", "9.9", "10001"),
+            ("height", "10", "10000", "9", "10000.5"),
+            ("max_velocity", "0.01", "10", "0", "11"),
+            ("speed", "0.1", "10", "0.05", "10.5"),
+            ("radius", "0.1", "10", "0", "11"),
+            ("size_spread", "0", "0.9", "-0.1", "1"),
+            ("restitution", "0", "1", "-1", "1.1"),
+            ("perception", "1", "100", "0.5", "101"),
+            ("mutation", "0", "0.1", "-0.1", "0.2"),
+            ("immunity_ticks", "0", "10000", "-1", "10001"),
+            ("hit_points", "1", "100", "0", "101"),
+            ("frame_ms", "1", "1000", "0.5", "1001"),
+        ];
+        for &(key, low, high, under, over) in cases {
+            let mut settings = Settings::default();
+            for value in [low, high] {
+                assert_eq!(
+                    settings.set_from_str(key, value),
+                    Ok(()),
+                    "{} = {}",
+                    key,
+                    value
+                );
+            }
+            for value in [under, over] {
+                assert!(
+                    settings.set_from_str(key, value).is_err(),
+                    "{} = {}",
+                    key,
+                    value
+                );
+            }
+        }
+        // Whole numbers only where a count is wanted
+        let mut settings = Settings::default();
+        assert!(settings.set_from_str("particles", "2.5").is_err());
+        assert!(settings.set_from_str("hit_points", "true").is_err());
+        assert!(settings.set_from_str("speed", "fast").is_err());
+    }
+
+    #[test]
+    fn particles_and_populations_replace_each_other() {
+        let mut settings = Settings::default();
+        settings.set_from_str("populations", "90, 90 20").unwrap();
+        assert_eq!(settings.populations, vec![90, 90, 20]);
+        assert_eq!(settings.particles, 200);
+
+        settings.set_from_str("particles", "30").unwrap();
+        assert!(settings.populations.is_empty());
+        assert_eq!(settings.particles, 30);
+    }
+
+    #[test]
+    fn map_is_read_into_the_params() {
+        let path = temp_map("good", &fs::read_to_string("maps/portals.map").unwrap());
+        let (config, errors) =
+            Config::parse(&format!("[simulation]\nmap = \"{}\"", path.display()));
+        fs::remove_file(&path).unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let params = &config.settings.params;
+        assert_eq!(params.obstacles.len(), 1);
+        assert_eq!(params.portals.len(), 2);
+        assert!(params.zones.is_empty());
+
+        let path = temp_map("bad", "rect 1 2\n");
+        let bad = problems(&format!("[simulation]\nmap = \"{}\"", path.display()));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(bad.len(), 1);
+        assert_eq!(bad[0].0, 2);
+        assert!(bad[0].1.contains("line 1 makes no sense"), "{}", bad[0].1);
+
+        let missing = env::temp_dir().join("rps-no-such.map");
+        let bad = problems(&format!("[simulation]\nmap = \"{}\"", missing.display()));
+        assert_eq!(bad.len(), 1);
+        assert!(bad[0].1.starts_with("can't read map"), "{}", bad[0].1);
+
+        assert_eq!(
+            problems("[simulation]\nmap = 3"),
+            vec![(2, "map must be the path of a map file, not 3".to_string())]
+        );
+    }
+
+    #[test]
+    fn rules_section_builds_custom_rules() {
+        let contents = "\
+            [rules]\n\
+            name = \"Fire Water Grass\"\n\
+            fire = \"F #e04020 beats grass\"\n\
+            water = \"W #2040e0 beats fire\"\n\
+            grass = \"G #20c040 beats water\"\n\
+            [colors]\n\
+            grass = \"#00ff00\"\n\
+            [glyphs]\n\
+            water_tile = \"~\"\n";
+        let (config, errors) = Config::parse(contents);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let rules = config.rules.unwrap();
+        assert_eq!(rules.name, "Fire Water Grass");
+        assert_eq!(rules.len(), 3);
+        let [fire, water, grass] = ["fire", "water", "grass"].map(|name| rules.find(name).unwrap());
+        assert_eq!(rules.name(fire), "Fire");
+        assert_eq!(rules.species(water).glyph, 'W');
+        assert_eq!(rules.species(grass).color, (0x20, 0xc0, 0x40));
+        assert!(rules.beats(fire, grass) && rules.beats(water, fire) && rules.beats(grass, water));
+        assert!(!rules.beats(grass, fire));
+        assert_eq!(*config.settings.params.rules, rules);
+    }
+
+    #[test]
+    fn bad_rules_are_reported_at_their_line() {
+        let cases: &[(&str, usize, &str)] = &[
+            (
+                "[rules]\nname = 3\nrock = \"R #a0a0a0\"",
+                2,
+                "name must be a string, not 3",
+            ),
+            (
+                "[rules]\nrock = \"R #a0a0a0 beats stone\"\nstone = \"S #a0a0a0 beats mud\"",
+                3,
+                "no hand is called `mud`",
+            ),
+            (
+                "[rules]\nrock = \"R #a0a0a0\"\npaper = \"PP #a0a0a0\"",
+                3,
+                "paper must look like",
+            ),
+            (
+                "[rules]\nrock = \"R #a0a0a0\"\npaper = \"P red\"",
+                3,
+                "paper must look like",
+            ),
+            (
+                "[rules]\nrock = \"R #a0a0a0\"\npaper = \"P #a0a0a0 eats rock\"",
+                3,
+                "paper must look like",
+            ),
+            (
+                "[rules]\nrock = \"R #a0a0a0\"\npaper = 5",
+                3,
+                "paper must look like",
+            ),
+            (
+                "[rules]\nbig rock = \"R #a0a0a0\"",
+                2,
+                "hand names can't have spaces",
+            ),
+            // What Rules::new refuses is reported at the section header
+            (
+                "# rules\n[rules]\nrock = \"R #a0a0a0\"",
+                2,
+                "rules need 2 to",
+            ),
+            (
+                "[rules]\nrock = \"R #a0a0a0\"\nRock = \"S #a0a0a0\"",
+                1,
+                "there are two hands called Rock",
+            ),
+            (
+                "[rules]\nrock = \"R #a0a0a0 beats rock\"\npaper = \"P #a0a0a0\"",
+                1,
+                "Rock can't beat itself",
+            ),
+            (
+                "[rules]\nrock = \"R #a0a0a0 beats paper\"\npaper = \"P #a0a0a0 beats rock\"",
+                1,
+                "Paper and Rock can't both beat each other",
+            ),
+        ];
+        for &(contents, line, message) in cases {
+            let (config, errors) = Config::parse(contents);
+            assert_eq!(errors.len(), 1, "{:?}: {:?}", contents, errors);
+            assert_eq!(errors[0].line, line, "{:?}", contents);
+            assert!(
+                errors[0].message.starts_with(message),
+                "{:?}: {}",
+                contents,
+                errors[0].message
+            );
+            assert!(config.rules.is_none());
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use rock_paper_scissors::simulation::Simulation;
+use rock_paper_scissors::{Hand, Rules};
+
+// Something that happened during a match, as kept for the results screen
+enum Event {
+    // The populations changed, to these
+    Populations { tick: u64, counts: Vec<usize> },
+    // A particle of `winner` won another one over
+    Conversion { tick: u64, winner: Hand },
+}
+
+impl Event {
+    fn tick(&self) -> u64 {
+        match self {
+            Event::Populations { tick, .. } | Event::Conversion { tick, .. } => *tick,
+        }
+    }
+}
+
+// Everything that happened to the populations of the match being played, in
+// order. Ticks that change nothing add nothing, so it stays small however long
+// the match runs.
+#[derive(Default)]
+pub struct EventLog {
+    events: Vec<Event>,
+}
+
+impl EventLog {
+    // Starts over from the populations `sim` has now
+    pub fn start(&mut self, sim: &Simulation) {
+        self.events.clear();
+        self.events.push(Event::Populations {
+            tick: sim.ticks,
+            counts: sim.counts(),
+        });
+    }
+
+    // Catches up on the tick the simulation just ran, which left `counts`
+    pub fn record(&mut self, sim: &Simulation, counts: &[usize]) {
+        for conversion in &sim.conversions {
+            let Some(winner) = sim.particles.get(conversion.winner) else {
+                continue;
+            };
+            self.events.push(Event::Conversion {
+                tick: sim.ticks,
+                winner: winner.hand,
+            });
+        }
+        if self.counts() != Some(counts) {
+            self.events.push(Event::Populations {
+                tick: sim.ticks,
+                counts: counts.to_vec(),
+            });
+        }
+    }
+
+    // Forgets everything after the tick `sim` went back to, and carries on
+    // from its populations there
+    pub fn rewind(&mut self, sim: &Simulation) {
+        self.events.retain(|event| event.tick() <= sim.ticks);
+        let counts = sim.counts();
+        if self.counts() != Some(&counts) {
+            self.events.push(Event::Populations {
+                tick: sim.ticks,
+                counts,
+            });
+        }
+    }
+
+    // The populations last logged
+    fn counts(&self) -> Option<&[usize]> {
+        self.events.iter().rev().find_map(|event| match event {
+            Event::Populations { counts, .. } => Some(counts.as_slice()),
+            _ => None,
+        })
+    }
+
+    // Particles each hand won over
+    pub fn conversions(&self, rules: &Rules) -> Vec<u32> {
+        let mut conversions = vec![0; rules.len()];
+        for event in &self.events {
+            if let Event::Conversion { winner, .. } = event {
+                if let Some(n) = conversions.get_mut(winner.index()) {
+                    *n += 1;
+                }
+            }
+        }
+        conversions
+    }
+
+    // Most particles each hand had at once
+    pub fn peaks(&self, rules: &Rules) -> Vec<usize> {
+        let mut peaks = vec![0; rules.len()];
+        for event in &self.events {
+            if let Event::Populations { counts, .. } = event {
+                for (peak, &count) in peaks.iter_mut().zip(counts) {
+                    *peak = (*peak).max(count);
+                }
+            }
+        }
+        peaks
+    }
+
+    // The most particles any hand won back after falling below what it
+    // started with, counted from its lowest point before then
+    pub fn comeback(&self) -> Option<Comeback> {
+        let start = self.events.iter().find_map(|event| match event {
+            Event::Populations { counts, .. } => Some(counts),
+            _ => None,
+        })?;
+        // Each hand's lowest point so far, and when it was
+        let mut lows: Vec<(usize, u64)> = start.iter().map(|&count| (count, 0)).collect();
+        let mut best: Option<Comeback> = None;
+        for event in &self.events {
+            let Event::Populations { tick, counts } = event else {
+                continue;
+            };
+            for (i, (low, &count)) in lows.iter_mut().zip(counts).enumerate() {
+                let (from, since) = *low;
+                if count < from {
+                    *low = (count, *tick);
+                    continue;
+                }
+                let gained = count - from;
+                let dipped = from < start[i];
+                if dipped
+                    && gained > 0
+                    && best
+                        .as_ref()
+                        .is_none_or(|best| gained > best.to - best.from)
+                {
+                    best = Some(Comeback {
+                        hand: Hand::from_index(i)?,
+                        from,
+                        to: count,
+                        since,
+                        until: *tick,
+                    });
+                }
+            }
+        }
+        best
+    }
+}
+
+// A hand growing back from `from` particles at tick `since` to `to` at tick
+// `until`
+pub struct Comeback {
+    pub hand: Hand,
+    pub from: usize,
+    pub to: usize,
+    pub since: u64,
+    pub until: u64,
+}
@@ -0,0 +1,145 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+// How many writes may be in flight before the game has to wait (or drop).
+const EXPORT_QUEUE_LEN: usize = 1024;
+
+enum Message {
+    Write { path: PathBuf, bytes: Vec<u8> },
+    Close { path: PathBuf },
+    Sync(mpsc::Sender<()>),
+}
+
+// Writes export files on a background thread so disk I/O never lands in a
+// frame. Files are created on their first write and stay open until closed.
+// Where there are no threads, as in the browser, nothing is written at all.
+pub struct Exporter {
+    sender: Option<SyncSender<Message>>,
+    worker: Option<JoinHandle<()>>,
+    dropped: usize,
+}
+
+impl Exporter {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::sync_channel(EXPORT_QUEUE_LEN);
+        let worker = thread::Builder::new()
+            .name("exporter".to_string())
+            .spawn(move || run(receiver));
+        if let Err(err) = &worker {
+            eprintln!("Could not start the export thread: {}", err);
+        }
+
+        Exporter {
+            sender: worker.is_ok().then_some(sender),
+            worker: worker.ok(),
+            dropped: 0,
+        }
+    }
+
+    // Queues data that must not be lost, waiting for room if the writer is
+    // behind.
+    pub fn write(&mut self, path: &Path, bytes: Vec<u8>) {
+        self.send(Message::Write {
+            path: path.to_path_buf(),
+            bytes,
+        });
+    }
+
+    // Queues data that can be given up on: if the writer is behind, it is
+    // dropped rather than stalling the simulation.
+    pub fn write_optional(&mut self, path: &Path, bytes: Vec<u8>) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let message = Message::Write {
+            path: path.to_path_buf(),
+            bytes,
+        };
+        if let Err(TrySendError::Full(_)) = sender.try_send(message) {
+            self.dropped += 1;
+        }
+    }
+
+    pub fn close(&mut self, path: &Path) {
+        self.send(Message::Close {
+            path: path.to_path_buf(),
+        });
+    }
+
+    // Blocks until everything queued so far is on disk.
+    pub fn sync(&mut self) {
+        let (done, wait) = mpsc::channel();
+        self.send(Message::Sync(done));
+        let _ = wait.recv();
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    fn send(&mut self, message: Message) {
+        if let Some(sender) = &self.sender {
+            // The worker only goes away if it panicked; nothing left to do then.
+            let _ = sender.send(message);
+        }
+    }
+}
+
+impl Drop for Exporter {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run(receiver: Receiver<Message>) {
+    let mut files: HashMap<PathBuf, BufWriter<File>> = HashMap::new();
+
+    for message in receiver {
+        match message {
+            Message::Write { path, bytes } => {
+                let file = match files.entry(path) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => match File::create(entry.key()) {
+                        Ok(file) => entry.insert(BufWriter::new(file)),
+                        Err(err) => {
+                            eprintln!("Could not create {}: {}", entry.key().display(), err);
+                            continue;
+                        }
+                    },
+                };
+                if let Err(err) = file.write_all(&bytes) {
+                    eprintln!("Export write failed: {}", err);
+                }
+            }
+            Message::Close { path } => {
+                if let Some(mut file) = files.remove(&path) {
+                    if let Err(err) = file.flush() {
+                        eprintln!("Could not finish {}: {}", path.display(), err);
+                    }
+                }
+            }
+            Message::Sync(done) => {
+                for (path, file) in files.iter_mut() {
+                    if let Err(err) = file.flush() {
+                        eprintln!("Could not flush {}: {}", path.display(), err);
+                    }
+                }
+                let _ = done.send(());
+            }
+        }
+    }
+
+    for (path, mut file) in files {
+        if let Err(err) = file.flush() {
+            eprintln!("Could not finish {}: {}", path.display(), err);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::VecDeque;
+
+use bracket_lib::prelude::*;
+use rock_paper_scissors::simulation::Simulation;
+use rock_paper_scissors::Hand;
+
+use crate::render::Glyphs;
+
+// Conversions kept in the feed, newest first, and how long each stays
+const MAX_EVENTS: usize = 5;
+const EVENT_TICKS: u64 = 150;
+// Rows down from the top right corner of the HUD where the feed starts
+const FEED_Y: i32 = 2;
+
+// One particle winning another over, by hand and particle number
+struct Event {
+    tick: u64,
+    winner: (Hand, u32),
+    loser: (Hand, u32),
+}
+
+// The last few conversions of the match, so what is happening in a crowd
+// can be read off as well as seen. Older ones drop off the end, and any that
+// sit there long enough fade away.
+#[derive(Default)]
+pub struct Feed {
+    events: VecDeque<Event>,
+}
+
+impl Feed {
+    // Catches up on the tick the simulation just ran
+    pub fn update(&mut self, sim: &Simulation) {
+        for conversion in &sim.conversions {
+            let Some(winner) = sim.particles.get(conversion.winner) else {
+                continue;
+            };
+            if self.events.len() == MAX_EVENTS {
+                self.events.pop_back();
+            }
+            self.events.push_front(Event {
+                tick: sim.ticks,
+                winner: (winner.hand, conversion.winner.index()),
+                loser: (conversion.lost, conversion.loser.index()),
+            });
+        }
+        self.events
+            .retain(|event| sim.ticks.saturating_sub(event.tick) < EVENT_TICKS);
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    // Draws the feed right-aligned in the top right corner of the active
+    // console, in the winners' colours and dimmer the older it gets
+    pub fn draw(&self, ctx: &mut BTerm, sim: &Simulation, glyphs: &Glyphs) {
+        let (width, _) = ctx.get_char_size();
+        let rules = &sim.params.rules;
+        for (row, event) in self.events.iter().enumerate() {
+            let (winner, winner_number) = event.winner;
+            let (loser, loser_number) = event.loser;
+            let line = format!(
+                "{} #{} converted {} #{}",
+                rules.name(winner),
+                winner_number,
+                rules.name(loser),
+                loser_number
+            );
+            let age = sim.ticks.saturating_sub(event.tick) as f32 / EVENT_TICKS as f32;
+            let color = glyphs.color(winner).lerp(RGB::named(BLACK), age * 0.7);
+            let x = (width as i32 - line.len() as i32).max(0);
+            ctx.print_color(x, FEED_Y + row as i32, color, RGB::named(BLACK), line);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+
+use bracket_lib::prelude::*;
+use rock_paper_scissors::simulation::{Conversion, Simulation};
+use rock_paper_scissors::{Particle, Vec2f};
+
+use crate::render;
+
+// Ticks kept around for the finale, a few seconds of play
+const FINALE_TICKS: usize = 60;
+// How many times slower than live the finale plays
+pub const SLOW_MOTION: f32 = 3.0;
+const HIGHLIGHT_COLOR: (u8, u8, u8) = (250, 230, 90);
+// Half the side of the square drawn around the decisive conversion
+const HIGHLIGHT_RADIUS: i32 = 2;
+
+struct Moment {
+    particles: Vec<Particle>,
+    conversions: Vec<Conversion>,
+}
+
+// A rolling buffer of the last ticks of a match, replayed in slow motion once
+// it ends. The last conversion of the match is the decisive one.
+pub struct Finale {
+    moments: VecDeque<Moment>,
+    // Index of the moment on screen while playing back
+    shown: usize,
+}
+
+impl Finale {
+    pub fn new() -> Self {
+        Finale {
+            moments: VecDeque::with_capacity(FINALE_TICKS),
+            shown: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.moments.clear();
+        self.shown = 0;
+    }
+
+    pub fn record(&mut self, sim: &Simulation) {
+        if self.moments.len() == FINALE_TICKS {
+            self.moments.pop_front();
+        }
+        self.moments.push_back(Moment {
+            particles: sim.particles.values().copied().collect(),
+            conversions: sim.conversions.clone(),
+        });
+    }
+
+    // Moves on by `ticks` and tells whether there is anything left to show
+    pub fn advance(&mut self, ticks: usize) -> bool {
+        self.shown += ticks;
+        self.shown < self.moments.len()
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.moments[self.shown.min(self.moments.len() - 1)].particles
+    }
+
+    // Where the match was decided, and when
+    fn decisive(&self) -> Option<(usize, Vec2f)> {
+        self.moments
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, moment)| moment.conversions.last().map(|c| (i, c.position)))
+    }
+
+    pub fn decisive_position(&self) -> Option<Vec2f> {
+        self.decisive().map(|(_, at)| at)
+    }
+
+    // Frames the decisive conversion on the active console once it happened,
+    // with positions relative to `origin` on screen
+    pub fn draw_highlight(&self, ctx: &mut BTerm, origin: Point) {
+        let Some((when, at)) = self.decisive() else {
+            return;
+        };
+        if self.shown < when {
+            return;
+        }
+        let center = origin + Point::new(at.x as i32, at.y as i32);
+        render::draw_frame(ctx, center, HIGHLIGHT_RADIUS, RGB::from(HIGHLIGHT_COLOR));
+    }
+}
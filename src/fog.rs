@@ -0,0 +1,74 @@
+use bracket_lib::prelude::*;
+use rock_paper_scissors::simulation::Simulation;
+use rock_paper_scissors::{Hand, Particle};
+
+use crate::camera::Camera;
+use crate::theme::Theme;
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// How far, in tiles, a particle can see around itself
+const VISION_RADIUS: i32 = 8;
+
+// Fog of war: only the tiles near particles of one hand can be seen. The rest
+// of the arena is dimmed and whatever moves in it is hidden.
+pub struct Fog {
+    pub hand: Hand,
+    width: i32,
+    height: i32,
+    visible: Vec<bool>,
+}
+
+impl Fog {
+    pub fn new(hand: Hand, sim: &Simulation) -> Self {
+        let width = sim.arena.width.ceil() as i32;
+        let height = sim.arena.height.ceil() as i32;
+        let mut fog = Fog {
+            hand,
+            width,
+            height,
+            visible: vec![false; (width * height) as usize],
+        };
+        fog.update(sim);
+        fog
+    }
+
+    // Recomputes what can be seen from where the friendly particles are now
+    pub fn update(&mut self, sim: &Simulation) {
+        self.visible.fill(false);
+        for particle in sim.particles.values().filter(|p| p.hand == self.hand) {
+            let (cx, cy) = (particle.position.x as i32, particle.position.y as i32);
+            for y in (cy - VISION_RADIUS).max(0)..=(cy + VISION_RADIUS).min(self.height - 1) {
+                for x in (cx - VISION_RADIUS).max(0)..=(cx + VISION_RADIUS).min(self.width - 1) {
+                    if (x - cx).pow(2) + (y - cy).pow(2) <= VISION_RADIUS.pow(2) {
+                        self.visible[(y * self.width + x) as usize] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_visible(&self, x: i32, y: i32) -> bool {
+        (0..self.width).contains(&x)
+            && (0..self.height).contains(&y)
+            && self.visible[(y * self.width + x) as usize]
+    }
+
+    pub fn reveals(&self, particle: &Particle) -> bool {
+        let (x, y) = (particle.position.x, particle.position.y);
+        particle.hand == self.hand || (x >= 0.0 && y >= 0.0 && self.is_visible(x as i32, y as i32))
+    }
+
+    // Paints the floor on screen, dimming the tiles nobody friendly can see
+    pub fn draw_background(&self, ctx: &mut BTerm, camera: &Camera, theme: &Theme) {
+        for y in 0..SCREEN_HEIGHT {
+            for x in 0..SCREEN_WIDTH {
+                let color = if self.is_visible(camera.x + x, camera.y + y) {
+                    theme.arena
+                } else {
+                    theme.fog
+                };
+                ctx.set_bg(x, y, color);
+            }
+        }
+    }
+}
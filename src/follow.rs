@@ -0,0 +1,96 @@
+use bracket_lib::prelude::*;
+use rock_paper_scissors::pool::Handle;
+use rock_paper_scissors::simulation::Simulation;
+use rock_paper_scissors::Vec2f;
+
+use crate::render;
+
+const FRAME_COLOR: (u8, u8, u8) = (250, 250, 250);
+const FRAME_RADIUS: i32 = 2;
+
+// The particle the camera stays on, picked with a click or cycled to, and
+// what it has done since. It lets go once the particle is removed.
+pub struct Follow {
+    handle: Handle,
+    // Particles it won over, and how often it changed hands itself
+    converted: u32,
+    times_converted: u32,
+    // Tick it was picked at
+    since: u64,
+}
+
+impl Follow {
+    pub fn new(handle: Handle, sim: &Simulation) -> Self {
+        Follow {
+            handle,
+            converted: 0,
+            times_converted: 0,
+            since: sim.ticks,
+        }
+    }
+
+    // The particle after the one followed, by handle, or the first one after
+    // the last
+    pub fn next(follow: Option<&Follow>, sim: &Simulation) -> Option<Follow> {
+        let handles = sim.particles.handles();
+        let after = follow.and_then(|f| handles.iter().position(|&h| h == f.handle));
+        let next = match after {
+            Some(i) => handles.get(i + 1).or(handles.first()),
+            None => handles.first(),
+        };
+        next.map(|&handle| Follow::new(handle, sim))
+    }
+
+    pub fn position(&self, sim: &Simulation) -> Option<Vec2f> {
+        sim.particles.get(self.handle).map(|p| p.position)
+    }
+
+    // Catches up on the tick the simulation just ran. False once the
+    // particle is gone.
+    pub fn update(&mut self, sim: &Simulation) -> bool {
+        for conversion in &sim.conversions {
+            if conversion.winner == self.handle {
+                self.converted += 1;
+            } else if conversion.loser == self.handle {
+                self.times_converted += 1;
+            }
+        }
+        sim.particles.get(self.handle).is_some()
+    }
+
+    // What the HUD says about it: where it is and how it is doing, then what
+    // it has done since it was picked
+    pub fn stat_lines(&self, sim: &Simulation) -> Option<[String; 2]> {
+        let particle = sim.particles.get(self.handle)?;
+        let mut state = format!(
+            "Following {} at {:.0}, {:.0}, speed {:.2}",
+            sim.params.rules.name(particle.hand),
+            particle.position.x,
+            particle.position.y,
+            particle.velocity.norm()
+        );
+        if particle.damage > 0 {
+            state += &format!(", {}/{} hits", particle.damage, sim.params.hit_points);
+        }
+        if let Some(effect) = particle.effect {
+            state += &format!(", {} {}", effect.power.name(), effect.ticks);
+        }
+        let record = format!(
+            "{} won over, converted {} times in {} ticks",
+            self.converted,
+            self.times_converted,
+            sim.ticks.saturating_sub(self.since)
+        );
+        Some([state, record])
+    }
+
+    // Frames the particle on the active console, with positions relative to
+    // `origin` on screen
+    pub fn draw(&self, ctx: &mut BTerm, sim: &Simulation, origin: Point) {
+        let Some(position) = self.position(sim) else {
+            return;
+        };
+        let center = origin + Point::new(position.x as i32, position.y as i32);
+        render::draw_frame(ctx, center, FRAME_RADIUS, RGB::from(FRAME_COLOR));
+    }
+}
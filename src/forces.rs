@@ -0,0 +1,228 @@
+use crate::physics::{Particle, Real, Vec2f};
+use crate::pool::Pool;
+use crate::simulation::{Arena, Params};
+
+// How far from a well its pull is down to half of what it is up close
+const WELL_FALLOFF: Real = 10.0;
+// Forces speed particles up to this many times the fastest new ones start
+// out at most, or their own speed if they are already going faster
+const TOP_SPEED: Real = 2.0;
+
+// Part of the arena a field is kept to, from its top left corner
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Region {
+    pub x: Real,
+    pub y: Real,
+    pub width: Real,
+    pub height: Real,
+}
+
+impl Region {
+    pub fn contains(&self, position: &Vec2f) -> bool {
+        (self.x..self.x + self.width).contains(&position.x)
+            && (self.y..self.y + self.height).contains(&position.y)
+    }
+}
+
+// A force over the whole arena, or a region of it, that pushes on every
+// particle in it each tick
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Field {
+    // Blows the same way all the time
+    Wind {
+        force: Vec2f,
+        region: Option<Region>,
+    },
+    // Blows one way along `force` and then back the other, once every
+    // `period` ticks
+    Gusts {
+        force: Vec2f,
+        period: Real,
+        region: Option<Region>,
+    },
+    // Swirls around `center`, clockwise on screen for a positive strength,
+    // dying down to nothing `radius` tiles out
+    Vortex {
+        center: Vec2f,
+        strength: Real,
+        radius: Real,
+    },
+}
+
+impl Field {
+    // The force it puts on anything at `position` after `ticks` ticks
+    pub fn force_at(&self, position: &Vec2f, arena: &Arena, ticks: u64) -> Vec2f {
+        let inside = |region: &Option<Region>| region.is_none_or(|r| r.contains(position));
+        match *self {
+            Field::Wind { force, region } if inside(&region) => force,
+            Field::Gusts {
+                force,
+                period,
+                region,
+            } if inside(&region) => {
+                let phase = std::f64::consts::TAU as Real * ticks as Real / period;
+                force.product(phase.sin())
+            }
+            Field::Vortex {
+                center,
+                strength,
+                radius,
+            } => {
+                let offset = arena.offset(center, *position);
+                let distance = offset.norm();
+                if distance == 0.0 || distance >= radius {
+                    return Vec2f::default();
+                }
+                let around = Vec2f {
+                    x: -offset.y / distance,
+                    y: offset.x / distance,
+                };
+                around.product(strength * (1.0 - distance / radius))
+            }
+            _ => Vec2f::default(),
+        }
+    }
+}
+
+// A point particles are drawn to, or pushed away from if its strength is
+// negative, like the one the mouse holds down
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Well {
+    pub position: Vec2f,
+    pub strength: Real,
+}
+
+impl Well {
+    // The force it puts on `particle`, weaker the further away that is
+    fn force_on(&self, particle: &Particle, arena: &Arena) -> Vec2f {
+        let offset = arena.offset(particle.position, self.position);
+        let distance = offset.norm();
+        if distance == 0.0 {
+            return Vec2f::default();
+        }
+        offset.product(self.strength / (distance * (1.0 + distance / WELL_FALLOFF)))
+    }
+}
+
+// Adds up every force on each particle, from the fields of `params` after
+// `ticks` ticks and from `wells`, and speeds it up by the total, the less the
+// heavier it is
+pub fn apply(
+    particles: &mut Pool<Particle>,
+    arena: &Arena,
+    params: &Params,
+    wells: &[Well],
+    ticks: u64,
+) {
+    profile_scope!("forces");
+    let top_speed = TOP_SPEED * params.max_velocity * params.speed;
+    for particle in particles.values_mut() {
+        let mut force = Vec2f::default();
+        for field in params.fields.iter() {
+            force = force.plus(field.force_at(&particle.position, arena, ticks));
+        }
+        for well in wells {
+            force = force.plus(well.force_on(particle, arena));
+        }
+
+        let limit = particle.velocity.norm().max(top_speed);
+        particle.velocity = particle.velocity.plus(force.product(1.0 / particle.mass));
+        let speed = particle.velocity.norm();
+        if speed > limit {
+            particle.velocity = particle.velocity.product(limit / speed);
+        }
+    }
+}
+
+// Reads fields given one after the other, apart by `;`, as a config value
+pub fn parse_fields(text: &str) -> Result<Vec<Field>, String> {
+    text.split(';')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(|field| parse_field(field).ok_or_else(|| format!("makes no sense: {}", field)))
+        .collect()
+}
+
+// One field, as
+//
+//   wind <x> <y> [in <x> <y> <width> <height>]
+//   gusts <x> <y> <period> [in <x> <y> <width> <height>]
+//   vortex <x> <y> <strength> <radius>
+//
+// with the force of wind and gusts along each axis, and the region they are
+// kept to if there is one. None if it isn't a field.
+pub fn parse_field(line: &str) -> Option<Field> {
+    let (line, region) = match line.split_once(" in ") {
+        Some((line, region)) => (line, Some(parse_region(region)?)),
+        None => (line, None),
+    };
+    let mut words = line.split_whitespace();
+    let kind = words.next()?;
+    let numbers: Vec<Real> = words.map(|word| word.parse().ok()).collect::<Option<_>>()?;
+    let field = match (kind, numbers.as_slice()) {
+        ("wind", &[x, y]) => Field::Wind {
+            force: Vec2f { x, y },
+            region,
+        },
+        ("gusts", &[x, y, period]) if period > 0.0 => Field::Gusts {
+            force: Vec2f { x, y },
+            period,
+            region,
+        },
+        ("vortex", &[x, y, strength, radius]) if radius > 0.0 && region.is_none() => {
+            Field::Vortex {
+                center: Vec2f { x, y },
+                strength,
+                radius,
+            }
+        }
+        _ => return None,
+    };
+    Some(field)
+}
+
+// A region as `<x> <y> <width> <height>`
+pub fn parse_region(text: &str) -> Option<Region> {
+    let numbers: Vec<Real> = text
+        .split_whitespace()
+        .map(|word| word.parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers.as_slice() {
+        &[x, y, width, height] if width > 0.0 && height > 0.0 => Some(Region {
+            x,
+            y,
+            width,
+            height,
+        }),
+        _ => None,
+    }
+}
+
+// The line `parse_field` reads back as `field`
+pub fn field_line(field: &Field) -> String {
+    let region_line = |region: &Option<Region>| match region {
+        Some(r) => format!(" in {} {} {} {}", r.x, r.y, r.width, r.height),
+        None => String::new(),
+    };
+    match field {
+        Field::Wind { force, region } => {
+            format!("wind {} {}{}", force.x, force.y, region_line(region))
+        }
+        Field::Gusts {
+            force,
+            period,
+            region,
+        } => format!(
+            "gusts {} {} {}{}",
+            force.x,
+            force.y,
+            period,
+            region_line(region)
+        ),
+        Field::Vortex {
+            center,
+            strength,
+            radius,
+        } => format!("vortex {} {} {} {}", center.x, center.y, strength, radius),
+    }
+}
@@ -0,0 +1,76 @@
+use bracket_lib::prelude::*;
+use rock_paper_scissors::simulation::Simulation;
+use rock_paper_scissors::Hand;
+
+use crate::render::Glyphs;
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// The strip runs along the bottom of the screen, under a row for the legend
+const STRIP_HEIGHT: i32 = 10;
+const STRIP_Y: i32 = SCREEN_HEIGHT - STRIP_HEIGHT;
+const LEGEND_Y: i32 = STRIP_Y - 1;
+const EMPTY_COLOR: (u8, u8, u8) = (24, 44, 44);
+
+// Ticks of history each column stands for, so the strip spans all 240 the
+// simulation keeps and scrolls left by a column every few ticks
+const TICKS_PER_COLUMN: usize = 4;
+
+// Draws each hand's population over the recent ticks as stacked areas on the
+// active console, newest on the right. The height of the strip is the most
+// particles there were at any point shown, so spawns and removals show up as
+// the whole stack growing or shrinking.
+pub fn draw_population_graph(ctx: &mut BTerm, sim: &Simulation, glyphs: &Glyphs) {
+    let empty = RGB::from(EMPTY_COLOR);
+    for y in LEGEND_Y..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            ctx.set(x, y, WHITE, empty, to_cp437(' '));
+        }
+    }
+
+    let history = &sim.history;
+    let most = history
+        .iter()
+        .map(|counts| counts.iter().sum::<usize>())
+        .max()
+        .unwrap_or(0);
+    if most > 0 {
+        for column in 0..SCREEN_WIDTH {
+            // Columns count back from the newest tick on the right edge
+            let back = (SCREEN_WIDTH - 1 - column) as usize * TICKS_PER_COLUMN;
+            let Some(i) = (history.len() - 1).checked_sub(back) else {
+                continue;
+            };
+            let counts = &history[i];
+            let mut y = SCREEN_HEIGHT;
+            let mut covered = 0;
+            for (i, &count) in counts.iter().enumerate() {
+                let Some(hand) = Hand::from_index(i) else {
+                    break;
+                };
+                covered += count;
+                let top = SCREEN_HEIGHT - (covered * STRIP_HEIGHT as usize).div_ceil(most) as i32;
+                for row in top..y {
+                    ctx.set(column, row, WHITE, glyphs.color(hand), to_cp437(' '));
+                }
+                y = y.min(top);
+            }
+        }
+    }
+
+    let rules = &sim.params.rules;
+    let mut x = 0;
+    for hand in rules.hands() {
+        let name = rules.name(hand);
+        // Names past the edge of the screen are left out
+        if x + name.len() as i32 > SCREEN_WIDTH {
+            break;
+        }
+        ctx.print_color(x, LEGEND_Y, glyphs.color(hand), empty, name);
+        x += name.len() as i32 + 1;
+    }
+    let label = format!("last {} ticks", SCREEN_WIDTH as usize * TICKS_PER_COLUMN);
+    let label_x = SCREEN_WIDTH - label.len() as i32;
+    if label_x > x {
+        ctx.print_color(label_x, LEGEND_Y, WHITE, empty, label);
+    }
+}
@@ -0,0 +1,91 @@
+use bracket_lib::prelude::*;
+use rock_paper_scissors::simulation::{self, Arena, Params, Simulation};
+use std::sync::Arc;
+
+use rock_paper_scissors::{Real, Rules};
+
+use crate::render::{self, Glyphs};
+use crate::{HUD_LAYER, SCREEN_WIDTH, UI_LAYER};
+
+// Matches per row and column, with a divider between neighbours
+const CELLS: i32 = 3;
+const CELL_SIZE: i32 = (SCREEN_WIDTH - (CELLS - 1)) / CELLS;
+const CELL_PARTICLES: usize = 8;
+const DIVIDER_COLOR: (u8, u8, u8) = (24, 44, 44);
+
+// A wall of small matches, each from its own seed. A match that ends is
+// tallied and replaced by a fresh one, so the totals keep growing.
+pub struct Grid {
+    cells: Vec<Simulation>,
+    seeds: RandomNumberGenerator,
+    rules: Arc<Rules>,
+    wins: Vec<usize>,
+}
+
+impl Grid {
+    pub fn new(rules: Arc<Rules>) -> Self {
+        let mut seeds = RandomNumberGenerator::new();
+        let cells = (0..CELLS * CELLS)
+            .map(|_| cell(seeds.next_u64(), &rules))
+            .collect();
+        Grid {
+            cells,
+            seeds,
+            wins: vec![0; rules.len()],
+            rules,
+        }
+    }
+
+    pub fn step(&mut self) {
+        for sim in self.cells.iter_mut() {
+            if let Some(winner) = simulation::winner(&sim.step()) {
+                self.wins[winner.index()] += 1;
+                *sim = cell(self.seeds.next_u64(), &self.rules);
+            }
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut BTerm, glyphs: &Glyphs) {
+        ctx.set_active_console(UI_LAYER);
+        let divider = RGB::from(DIVIDER_COLOR);
+        for i in 1..CELLS {
+            let at = i * (CELL_SIZE + 1) - 1;
+            for j in 0..CELLS * (CELL_SIZE + 1) - 1 {
+                ctx.set(at, j, WHITE, divider, to_cp437(' '));
+                ctx.set(j, at, WHITE, divider, to_cp437(' '));
+            }
+        }
+
+        for (i, sim) in self.cells.iter().enumerate() {
+            let (column, row) = (i as i32 % CELLS, i as i32 / CELLS);
+            let origin = Point::new(column * (CELL_SIZE + 1), row * (CELL_SIZE + 1));
+            render::render_particles(sim.particles.values(), origin, glyphs);
+        }
+
+        ctx.set_active_console(HUD_LAYER);
+        let (width, height) = ctx.get_char_size();
+        let (width, height) = (width as i32, height as i32);
+        let total: usize = self.wins.iter().sum();
+        let mut tally = format!("Wins out of {}:", total);
+        for hand in self.rules.hands() {
+            tally += &format!(" {} {}", self.rules.name(hand), self.wins[hand.index()]);
+        }
+        ctx.print(0, height - 1, tally);
+        ctx.print(width - 8, height - 1, "(Q) Menu");
+    }
+}
+
+fn cell(seed: u64, rules: &Arc<Rules>) -> Simulation {
+    let arena = Arena {
+        width: CELL_SIZE as Real,
+        height: CELL_SIZE as Real,
+        ..Arena::full_screen()
+    };
+    let params = Params {
+        rules: rules.clone(),
+        ..Params::default()
+    };
+    let mut sim = Simulation::new(arena, params, RandomNumberGenerator::seeded(seed));
+    sim.spawn_random(CELL_PARTICLES);
+    sim
+}
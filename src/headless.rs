@@ -0,0 +1,207 @@
+use std::thread;
+
+use bracket_lib::prelude::RandomNumberGenerator;
+use rock_paper_scissors::simulation::Simulation;
+use rock_paper_scissors::Hand;
+
+use crate::settings::Settings;
+
+// Ticks in a row without anyone changing hands after which a match is called
+// off, for rules or settings under which nobody can win it
+pub const STALL_TICKS: u64 = 10_000;
+
+// How one match of a batch went
+struct Outcome {
+    // None if it ran out of ticks or stalled first
+    winner: Option<Hand>,
+    ticks: u64,
+    // The hand that started with the most particles, unless it shared the lead
+    favourite: Option<Hand>,
+}
+
+// A new match of the settings, from `seed`
+fn deal(settings: &Settings, seed: u64) -> Simulation {
+    let mut sim = Simulation::new(
+        settings.arena,
+        settings.params.clone(),
+        RandomNumberGenerator::seeded(seed),
+    );
+    settings.deal(&mut sim);
+    sim
+}
+
+// Plays a match without a window, for `ticks` ticks, until one hand is left
+// or until it stalls, and returns where the populations ended up
+fn play(sim: &mut Simulation, ticks: Option<u64>) -> Vec<usize> {
+    let mut counts = sim.counts();
+    let mut quiet = 0;
+    while ticks.is_none_or(|ticks| sim.ticks < ticks) && quiet < STALL_TICKS {
+        counts = sim.step();
+        if sim.winner(&counts).is_some() {
+            break;
+        }
+        quiet = if sim.conversions.is_empty() {
+            quiet + 1
+        } else {
+            0
+        };
+    }
+    counts
+}
+
+// Runs one match and prints where the populations ended up
+pub fn run(settings: &Settings, ticks: Option<u64>, seed: u64) {
+    let mut sim = deal(settings, seed);
+    let counts = play(&mut sim, ticks);
+    let mut populations = format!("Tick {}:", sim.ticks);
+    let rules = &sim.params.rules;
+    for hand in rules.hands() {
+        populations += &format!(" {} {}", rules.name(hand), counts[hand.index()]);
+    }
+    println!("{}", populations);
+    if let Some(winner) = sim.winner(&counts) {
+        println!("{} wins", rules.name(winner));
+    } else if ticks.is_none_or(|ticks| sim.ticks < ticks) {
+        println!("No winner after {} ticks without a conversion", STALL_TICKS);
+    }
+    println!("Seed {}", seed);
+}
+
+pub fn available_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// Runs `matches` matches from seeds `seed`, `seed + 1` and so on, split over
+// `jobs` threads, and prints how often each hand won and how long matches
+// took. The results don't depend on how many jobs there were.
+pub fn simulate(settings: &Settings, matches: usize, jobs: usize, ticks: Option<u64>, seed: u64) {
+    let seeds: Vec<u64> = (0..matches as u64).map(|i| seed.wrapping_add(i)).collect();
+    let chunk_size = matches.div_ceil(jobs.max(1)).max(1);
+    let outcomes: Vec<Outcome> = thread::scope(|scope| {
+        let workers: Vec<_> = seeds
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|&seed| outcome(settings, ticks, seed))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    });
+
+    let rules = &settings.params.rules;
+    println!(
+        "{} matches of {} from seed {}, {} particles each",
+        matches, rules.name, seed, settings.particles
+    );
+    for hand in rules.hands() {
+        let wins = outcomes.iter().filter(|o| o.winner == Some(hand)).count();
+        println!(
+            "{} wins: {} ({:.1}%)",
+            rules.name(hand),
+            wins,
+            percent(wins, matches)
+        );
+    }
+    let undecided = outcomes.iter().filter(|o| o.winner.is_none()).count();
+    if undecided > 0 {
+        println!("Undecided when time ran out or stalled: {}", undecided);
+    }
+
+    // Over the matches that were won, as the others were cut short
+    let lengths: Vec<f64> = outcomes
+        .iter()
+        .filter(|o| o.winner.is_some())
+        .map(|o| o.ticks as f64)
+        .collect();
+    if !lengths.is_empty() {
+        let mean = lengths.iter().sum::<f64>() / lengths.len() as f64;
+        let variance =
+            lengths.iter().map(|l| (l - mean).powi(2)).sum::<f64>() / lengths.len() as f64;
+        println!(
+            "Match length: {:.1} ticks on average, variance {:.1} (std dev {:.1})",
+            mean,
+            variance,
+            variance.sqrt()
+        );
+    }
+
+    // Whether a head start matters: how often the hand dealt the most
+    // particles went on to win
+    let led: Vec<&Outcome> = outcomes.iter().filter(|o| o.favourite.is_some()).collect();
+    let held = led.iter().filter(|o| o.winner == o.favourite).count();
+    println!(
+        "Won by the hand dealt the most particles: {:.1}% of {} matches with one",
+        percent(held, led.len()),
+        led.len()
+    );
+}
+
+fn outcome(settings: &Settings, ticks: Option<u64>, seed: u64) -> Outcome {
+    let mut sim = deal(settings, seed);
+    let dealt = sim.counts();
+    let most = dealt.iter().copied().max().unwrap_or(0);
+    let favourite = match dealt.iter().filter(|&&n| n == most).count() {
+        1 => dealt
+            .iter()
+            .position(|&n| n == most)
+            .and_then(Hand::from_index),
+        _ => None,
+    };
+
+    let counts = play(&mut sim, ticks);
+    Outcome {
+        winner: sim.winner(&counts),
+        ticks: sim.ticks,
+        favourite,
+    }
+}
+
+fn percent(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        return 0.0;
+    }
+    100.0 * part as f64 / whole as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_that_cant_convert_stalls() {
+        let mut settings = Settings::default();
+        settings.params.conversion_chance = 0.0;
+        let mut sim = deal(&settings, 252);
+        let dealt = sim.counts();
+
+        let counts = play(&mut sim, None);
+        // Nobody converts from the first tick, so it is all quiet ticks
+        assert_eq!(sim.ticks, STALL_TICKS);
+        assert_eq!(counts, dealt);
+        assert_eq!(sim.winner(&counts), None);
+    }
+
+    #[test]
+    fn tick_limit_comes_before_the_stall() {
+        let mut settings = Settings::default();
+        settings.params.conversion_chance = 0.0;
+        let mut sim = deal(&settings, 252);
+        play(&mut sim, Some(100));
+        assert_eq!(sim.ticks, 100);
+    }
+
+    #[test]
+    fn classic_match_is_won_before_it_can_stall() {
+        let mut sim = deal(&Settings::default(), 42);
+        let counts = play(&mut sim, None);
+        assert_eq!(sim.ticks, 233);
+        assert_eq!(sim.winner(&counts), Hand::from_index(0));
+    }
+}
@@ -0,0 +1,77 @@
+use rock_paper_scissors::simulation::Simulation;
+use rock_paper_scissors::Hand;
+
+// Points for taking the whole arena over, on top of one for each percent of
+// it the infection held at its peak
+const TAKEOVER_POINTS: u32 = 100;
+
+// An infection match: one hand starts out as a single particle, patient zero,
+// against everyone else split between the other hands. It is won once that
+// hand has taken the arena over, and lost as soon as it dies out.
+#[derive(Default)]
+pub struct Infection {
+    pub hand: Hand,
+    // Particles in the match, which conversions don't change
+    total: usize,
+    // The most particles the hand has had at once, and when it first did
+    pub peak: usize,
+    pub peak_tick: u64,
+}
+
+impl Infection {
+    // Sets `sim` up as a new infection. Patient zero plays the hand of the
+    // first particle, so it follows from the seed, and the rest of the
+    // particles are shared out between the other hands.
+    pub fn start(&mut self, sim: &mut Simulation) {
+        let Some((_, first)) = sim.particles.iter().next() else {
+            return;
+        };
+        let hand = first.hand;
+        let total = sim.particles.len();
+        let others: Vec<Hand> = sim.params.rules.hands().filter(|&h| h != hand).collect();
+        sim.set_population(hand, 1);
+        let (share, rest) = ((total - 1) / others.len(), (total - 1) % others.len());
+        for (i, &other) in others.iter().enumerate() {
+            sim.set_population(other, share + usize::from(i < rest));
+        }
+        *self = Infection {
+            hand,
+            total,
+            peak: 1,
+            peak_tick: sim.ticks,
+        };
+    }
+
+    // Catches up on the populations after a tick
+    pub fn update(&mut self, counts: &[usize], ticks: u64) {
+        let count = counts[self.hand.index()];
+        if count > self.peak {
+            self.peak = count;
+            self.peak_tick = ticks;
+        }
+    }
+
+    pub fn contained(&self, counts: &[usize]) -> bool {
+        counts[self.hand.index()] == 0
+    }
+
+    pub fn took_over(&self, counts: &[usize]) -> bool {
+        counts[self.hand.index()] == counts.iter().sum::<usize>()
+    }
+
+    // How much of the match `count` particles are, in percent
+    pub fn percent(&self, count: usize) -> usize {
+        count * 100 / self.total.max(1)
+    }
+
+    // What the match scores: one point per percent of the arena the
+    // infection held at its peak, and a bonus if it took all of it
+    pub fn points(&self, took_over: bool) -> u32 {
+        let spread = self.percent(self.peak) as u32;
+        if took_over {
+            spread + TAKEOVER_POINTS
+        } else {
+            spread
+        }
+    }
+}
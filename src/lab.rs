@@ -0,0 +1,227 @@
+use bracket_lib::prelude::*;
+use rock_paper_scissors::simulation::Simulation;
+use rock_paper_scissors::{Hand, Real};
+
+use crate::render::Glyphs;
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH, UI_LAYER};
+
+// The panel covers the right edge of the arena
+const PANEL_WIDTH: i32 = 24;
+const PANEL_X: i32 = SCREEN_WIDTH - PANEL_WIDTH;
+const PANEL_COLOR: (u8, u8, u8) = (24, 44, 44);
+
+// Bars and the graph are drawn with blank cells and background colours, as the
+// font's block glyphs are taken by the hand art
+const BAR_X: i32 = PANEL_X + 1;
+const BAR_WIDTH: i32 = PANEL_WIDTH - 2;
+const BAR_COLOR: (u8, u8, u8) = (210, 200, 120);
+const BAR_EMPTY_COLOR: (u8, u8, u8) = (60, 76, 76);
+
+const SLIDERS_Y: i32 = 3;
+// Rows taken by each slider: label, bar and a gap. Past the classic three
+// hands, the gap goes to make room.
+const SLIDER_ROWS: i32 = 3;
+const CROWDED_SLIDER_ROWS: i32 = 2;
+const GRAPH_HEIGHT: i32 = 14;
+
+const MAX_POPULATION: usize = 200;
+
+#[derive(Copy, Clone)]
+enum Slider {
+    Population(Hand),
+    Speed,
+    ConversionChance,
+    Immunity,
+}
+
+// One population slider per hand of the match, then the rules
+fn sliders(sim: &Simulation) -> Vec<Slider> {
+    let mut sliders: Vec<Slider> = sim.params.rules.hands().map(Slider::Population).collect();
+    sliders.extend([Slider::Speed, Slider::ConversionChance, Slider::Immunity]);
+    sliders
+}
+
+fn slider_rows(sliders: &[Slider]) -> i32 {
+    if sliders.len() > 6 {
+        CROWDED_SLIDER_ROWS
+    } else {
+        SLIDER_ROWS
+    }
+}
+
+fn graph_y(sliders: &[Slider]) -> i32 {
+    SLIDERS_Y + slider_rows(sliders) * sliders.len() as i32 + 2
+}
+
+impl Slider {
+    // Lowest value, highest value and how far one key press moves it
+    fn range(self) -> (Real, Real, Real) {
+        match self {
+            Slider::Population(_) => (0.0, MAX_POPULATION as Real, 5.0),
+            Slider::Speed => (0.25, 4.0, 0.25),
+            Slider::ConversionChance => (0.0, 1.0, 0.05),
+            Slider::Immunity => (0.0, 100.0, 5.0),
+        }
+    }
+
+    fn value(self, sim: &Simulation) -> Real {
+        match self {
+            Slider::Population(hand) => sim.counts()[hand.index()] as Real,
+            Slider::Speed => sim.params.speed,
+            Slider::ConversionChance => sim.params.conversion_chance,
+            Slider::Immunity => sim.params.immunity_ticks as Real,
+        }
+    }
+
+    fn set(self, sim: &mut Simulation, value: Real) {
+        let (min, max, step) = self.range();
+        let value = ((value / step).round() * step).clamp(min, max);
+        match self {
+            Slider::Population(hand) => sim.set_population(hand, value as usize),
+            Slider::Speed => sim.set_speed(value),
+            Slider::ConversionChance => sim.params.conversion_chance = value,
+            Slider::Immunity => sim.params.immunity_ticks = value as u32,
+        }
+    }
+
+    fn label(self, sim: &Simulation) -> String {
+        let value = self.value(sim);
+        match self {
+            Slider::Population(hand) => format!("{}: {}", sim.params.rules.name(hand), value),
+            Slider::Speed => format!("Speed: {:.2}x", value),
+            Slider::ConversionChance => format!("Conversion: {:.0}%", value * 100.0),
+            Slider::Immunity => format!("Immunity: {} ticks", value),
+        }
+    }
+}
+
+// Classroom mode: a side panel of sliders that change the running simulation,
+// next to a graph of how the populations respond.
+#[derive(Default)]
+pub struct Lab {
+    selected: usize,
+}
+
+impl Lab {
+    pub fn update(&mut self, ctx: &mut BTerm, sim: &mut Simulation, glyphs: &Glyphs) {
+        ctx.set_active_console(UI_LAYER);
+        let sliders = sliders(sim);
+        let rows = slider_rows(&sliders);
+        self.handle_input(ctx, sim, &sliders);
+
+        let background = RGB::from(PANEL_COLOR);
+        for y in 0..SCREEN_HEIGHT {
+            for x in PANEL_X..SCREEN_WIDTH {
+                ctx.set(x, y, WHITE, background, to_cp437(' '));
+            }
+        }
+        ctx.print_color(BAR_X, 1, YELLOW, background, "LAB");
+
+        for (i, slider) in sliders.iter().enumerate() {
+            let y = SLIDERS_Y + rows * i as i32;
+            let fg = if i == self.selected { YELLOW } else { WHITE };
+            ctx.print_color(BAR_X, y, fg, background, slider.label(sim));
+
+            let (min, max, _) = slider.range();
+            let fraction = ((slider.value(sim) - min) / (max - min)).clamp(0.0, 1.0);
+            let filled = (fraction * BAR_WIDTH as Real).round() as i32;
+            for x in 0..BAR_WIDTH {
+                let color = if x < filled {
+                    BAR_COLOR
+                } else {
+                    BAR_EMPTY_COLOR
+                };
+                ctx.set(BAR_X + x, y + 1, WHITE, color, to_cp437(' '));
+            }
+        }
+
+        let graph_y = graph_y(&sliders);
+        draw_graph(ctx, sim, glyphs, graph_y);
+
+        let help_y = graph_y + GRAPH_HEIGHT + 4;
+        ctx.print_color(BAR_X, help_y, WHITE, background, "Up/Down: pick a slider");
+        ctx.print_color(
+            BAR_X,
+            help_y + 1,
+            WHITE,
+            background,
+            "Left/Right: adjust it",
+        );
+        ctx.print_color(
+            BAR_X,
+            help_y + 2,
+            WHITE,
+            background,
+            "Click a bar to set it",
+        );
+    }
+
+    fn handle_input(&mut self, ctx: &BTerm, sim: &mut Simulation, sliders: &[Slider]) {
+        // The hands may have changed since the last frame
+        self.selected = self.selected.min(sliders.len() - 1);
+        let rows = slider_rows(sliders);
+        if ctx.left_click {
+            let (x, y) = ctx.mouse_pos();
+            let row = y - SLIDERS_Y - 1;
+            let on_bar = (BAR_X..BAR_X + BAR_WIDTH).contains(&x) && row % rows == 0;
+            if let (true, Some(&slider)) = (on_bar, sliders.get((row / rows) as usize)) {
+                self.selected = (row / rows) as usize;
+                let (min, max, _) = slider.range();
+                let fraction = (x - BAR_X) as Real / (BAR_WIDTH - 1) as Real;
+                slider.set(sim, min + fraction * (max - min));
+            }
+        }
+
+        let slider = sliders[self.selected];
+        let (_, _, step) = slider.range();
+        match ctx.key {
+            Some(VirtualKeyCode::Up) => {
+                self.selected = (self.selected + sliders.len() - 1) % sliders.len()
+            }
+            Some(VirtualKeyCode::Down) => self.selected = (self.selected + 1) % sliders.len(),
+            Some(VirtualKeyCode::Left) => slider.set(sim, slider.value(sim) - step),
+            Some(VirtualKeyCode::Right) => slider.set(sim, slider.value(sim) + step),
+            _ => {}
+        }
+    }
+}
+
+// Share of each hand over the recent ticks, oldest on the left
+fn draw_graph(ctx: &mut BTerm, sim: &Simulation, glyphs: &Glyphs, graph_y: i32) {
+    let background = RGB::from(PANEL_COLOR);
+    ctx.print_color(BAR_X, graph_y - 1, WHITE, background, "Populations");
+    for y in graph_y..graph_y + GRAPH_HEIGHT {
+        for x in BAR_X..BAR_X + BAR_WIDTH {
+            ctx.set(x, y, WHITE, BAR_EMPTY_COLOR, to_cp437(' '));
+        }
+    }
+
+    let history = &sim.history;
+    if !history.is_empty() {
+        for column in 0..BAR_WIDTH {
+            let counts = &history[column as usize * history.len() / BAR_WIDTH as usize];
+            let total: usize = counts.iter().sum();
+            if total == 0 {
+                continue;
+            }
+            for hand in sim.params.rules.hands() {
+                let height = counts[hand.index()] * (GRAPH_HEIGHT - 1) as usize / total;
+                let y = graph_y + GRAPH_HEIGHT - 1 - height as i32;
+                ctx.set(BAR_X + column, y, WHITE, glyphs.color(hand), to_cp437(' '));
+            }
+        }
+    }
+
+    let mut x = BAR_X;
+    let mut y = graph_y + GRAPH_HEIGHT + 1;
+    for hand in sim.params.rules.hands() {
+        let name = sim.params.rules.name(hand);
+        // Wraps to the next row once the panel is full
+        if x + name.len() as i32 > BAR_X + BAR_WIDTH {
+            x = BAR_X;
+            y += 1;
+        }
+        ctx.print_color(x, y, glyphs.color(hand), background, name);
+        x += name.len() as i32 + 1;
+    }
+}
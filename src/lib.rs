@@ -0,0 +1,21 @@
+// The simulation on its own, without a window: particles, the rules they
+// play by and the matches they make up. The game draws it with bracket-lib.
+
+#[macro_use]
+pub mod profiling;
+
+pub mod collision;
+pub mod forces;
+pub mod obstacle;
+pub mod physics;
+pub mod pickup;
+pub mod pool;
+pub mod portal;
+pub mod rules;
+pub mod save;
+pub mod simulation;
+pub mod steering;
+pub mod zone;
+
+pub use physics::{Particle, Real, Vec2f, PARTICLE_RADIUS};
+pub use rules::{Hand, Rules, Species};
@@ -0,0 +1,83 @@
+use bracket_lib::prelude::*;
+use rock_paper_scissors::Particle;
+
+use crate::render::{self, Glyphs};
+use crate::{MAGNIFIER_LAYER, SCREEN_HEIGHT, SCREEN_WIDTH, UI_LAYER};
+
+// Tiles of the arena the lens shows along each side, and how much bigger
+// they are drawn
+const SOURCE_SIZE: i32 = 8;
+const ZOOM: i32 = 3;
+const PANEL_SIZE: i32 = SOURCE_SIZE * ZOOM;
+
+// The panel sits in the bottom-left corner, inside a one tile frame
+const PANEL_X: i32 = 1;
+const PANEL_Y: i32 = SCREEN_HEIGHT - PANEL_SIZE - 1;
+const FRAME_COLOR: (u8, u8, u8) = (24, 44, 44);
+
+// Draws a zoomed-in copy of the screen around `focus` in a corner panel. The
+// lens has a console of its own, scaled up around the corner of the region it
+// shows and then shifted onto the panel, so the main view is left untouched.
+// Particle positions are relative to `origin` on screen, and the floor is
+// painted `background`.
+pub fn draw_magnifier<'a>(
+    ctx: &mut BTerm,
+    particles: impl Iterator<Item = &'a Particle>,
+    origin: Point,
+    focus: Point,
+    background: RGB,
+    glyphs: &Glyphs,
+) {
+    let left = (focus.x - SOURCE_SIZE / 2).clamp(0, SCREEN_WIDTH - SOURCE_SIZE);
+    let top = (focus.y - SOURCE_SIZE / 2).clamp(0, SCREEN_HEIGHT - SOURCE_SIZE);
+    let inside = |x: i32, y: i32| {
+        (left..left + SOURCE_SIZE).contains(&x) && (top..top + SOURCE_SIZE).contains(&y)
+    };
+
+    ctx.set_active_console(UI_LAYER);
+    let frame = RGB::from(FRAME_COLOR);
+    for i in -1..=PANEL_SIZE {
+        ctx.set(PANEL_X + i, PANEL_Y - 1, WHITE, frame, to_cp437(' '));
+        ctx.set(
+            PANEL_X + i,
+            PANEL_Y + PANEL_SIZE,
+            WHITE,
+            frame,
+            to_cp437(' '),
+        );
+        ctx.set(PANEL_X - 1, PANEL_Y + i, WHITE, frame, to_cp437(' '));
+        ctx.set(
+            PANEL_X + PANEL_SIZE,
+            PANEL_Y + i,
+            WHITE,
+            frame,
+            to_cp437(' '),
+        );
+    }
+
+    ctx.set_active_console(MAGNIFIER_LAYER);
+    // Offsets are in unscaled tiles, with y growing upwards
+    ctx.set_scale(ZOOM as f32, left, top);
+    ctx.set_offset((PANEL_X - left) as f32, (top - PANEL_Y) as f32);
+
+    for y in top..top + SOURCE_SIZE {
+        for x in left..left + SOURCE_SIZE {
+            ctx.set(x, y, WHITE, background, to_cp437(' '));
+        }
+    }
+
+    let mut commands = Vec::new();
+    for particle in particles {
+        render::particle_commands(particle, glyphs, &mut commands);
+    }
+    for command in commands {
+        // Snapped to tiles, as the magnifier scales its whole console up
+        let (x, y) = (
+            origin.x + command.x.round() as i32,
+            origin.y + command.y.round() as i32,
+        );
+        if inside(x, y) {
+            ctx.set(x, y, command.fg, background, command.glyph);
+        }
+    }
+}
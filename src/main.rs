@@ -1,312 +1,1986 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
 use bracket_lib::prelude::*;
+use rock_paper_scissors::forces::Well;
+use rock_paper_scissors::pool::Handle;
+use rock_paper_scissors::profile_scope;
+use rock_paper_scissors::save;
+use rock_paper_scissors::simulation::{Arena, Simulation};
+use rock_paper_scissors::{Hand, Particle, Real, Rules, Vec2f};
+
+mod art;
+mod avatar;
+mod bet;
+mod branch;
+mod camera;
+mod champion;
+mod cli;
+mod compare;
+mod config;
+mod events;
+mod export;
+mod feed;
+mod finale;
+mod fog;
+mod follow;
+mod graph;
+mod grid;
+mod headless;
+mod infection;
+mod lab;
+mod magnifier;
+mod paths;
+mod prediction;
+mod profile;
+mod render;
+mod replay;
+mod rewind;
+mod settings;
+mod snapshot;
+mod sparks;
+mod stats;
+#[cfg(feature = "terminal")]
+mod terminal;
+mod theme;
+mod timeline;
+mod trails;
+
+use art::Art;
+use avatar::Avatar;
+use bet::Bet;
+use branch::Branches;
+use camera::{Camera, Hotspots, Surge};
+use champion::Champion;
+use cli::Options;
+use compare::Comparison;
+use config::{Config, Display, GlyphConfig};
+use events::EventLog;
+use export::Exporter;
+use feed::Feed;
+use finale::Finale;
+use fog::Fog;
+use follow::Follow;
+use grid::Grid;
+use infection::Infection;
+use lab::Lab;
+use profile::{Entry, Profile};
+use render::Glyphs;
+use replay::{ReplayReader, ReplayWriter};
+use rewind::Rewind;
+use settings::Settings;
+use sparks::Sparks;
+use stats::{History, Run, Summary, Tally};
+use theme::{Snow, Theme};
+use timeline::Timeline;
+use trails::Trails;
 
 enum GameMode {
+    Profiles,
     Menu,
+    // Picking a winner before the match starts
+    Betting,
     Playing,
+    // The last moments of a match, in slow motion
+    Finale { winner: Hand },
     End { winner: Hand },
+    Replay,
+    Compare,
+    Grid,
+    Statistics,
+    History,
+    Settings,
 }
 
 const SCREEN_WIDTH: i32 = 60;
 const SCREEN_HEIGHT: i32 = 60;
-const FRAME_DURATION: f32 = 60.0;
+// Most ticks run in a single frame to catch up after a stall
+const MAX_CATCH_UP_TICKS: usize = 5;
+// How far +/- can slow down or speed up play, halving or doubling each time
+const MIN_TIME_SCALE: f32 = 0.25;
+const MAX_TIME_SCALE: f32 = 8.0;
+// Wall-clock time per frame spent stepping the simulation in turbo mode, or
+// ticks per frame in the browser, where std has no clock to measure it with
+const TURBO_FRAME_BUDGET: Duration = Duration::from_millis(25);
+const WEB_TURBO_TICKS: usize = 20;
+// The browser runs the game in a page, so it gets the font that fits one
+#[cfg(target_arch = "wasm32")]
+const WEB_FONT_SIZE: u32 = 16;
+// Held down to take the match back in time
+const REWIND_KEY: VirtualKeyCode = VirtualKeyCode::Z;
+
+// The background only changes with the terrain, so it gets a console of its
+// own and is left alone between redraws. Everything else goes on top of it.
+const BACKGROUND_LAYER: usize = 0;
+const ARENA_LAYER: usize = 1;
+// Particles, drawn where they are between tiles instead of snapped to them so
+// they glide across the arena
+const PARTICLE_LAYER: usize = 2;
+// Panels that need a solid backdrop of their own, on top of the arena
+const UI_LAYER: usize = 3;
+// Zoomed in views, scaled up as a whole
+const MAGNIFIER_LAYER: usize = 4;
+// Text over the arena, with cells `ui_scale` tiles wide so it stays readable
+// whatever the font
+const HUD_LAYER: usize = 5;
+const ARENA_COLOR: (u8, u8, u8) = (54, 126, 127);
 
-const NUM_PARTICLES: usize = 25;
-const MIN_VELOCITY: f64 = -1.0;
-const MAX_VELOCITY: f64 = 1.0;
-const PARTICLE_RADIUS: f64 = 1.5;
+// Every match is recorded here, in the player's directory, overwriting the
+// previous one
+const REPLAY_FILE: &str = "last-match.replay";
+// Where F5 saves the match being played and F9 loads it from
+const QUICKSAVE_FILE: &str = "quicksave.txt";
+
+// Side of the arena in big world matches, which scroll under the screen
+const BIG_WORLD_SIZE: Real = 400.0;
+// Tiles the walls of a battle royale close in by each tick, unless the
+// settings have them closing in already
+const BATTLE_ROYALE_SHRINK: Real = 0.02;
+// Milliseconds the left mouse button has to be held down before it pulls
+// particles in instead of clicking
+const HOLD_TIME: f32 = 250.0;
+// How hard the mouse pulls particles in or pushes them away, at match speed 1,
+// and the colours of the frame around it while it does
+const WELL_STRENGTH: Real = 0.08;
+const PULL_COLOR: (u8, u8, u8) = (90, 160, 250);
+const PUSH_COLOR: (u8, u8, u8) = (250, 110, 90);
+
+struct State {
+    sim: Simulation,
+    lab: Option<Lab>,
+    comparison: Option<Comparison>,
+    grid: Option<Grid>,
+    profile: Option<Profile>,
+    profile_names: Vec<String>,
+    // Name typed so far while creating a profile
+    new_profile_name: Option<String>,
+    frame_time: f32,
+    mode: GameMode,
+    elapsed_time: f32,
+    background_dirty: bool,
+    exporter: Exporter,
+    recording: Option<PathBuf>,
+    // Where this match's populations go when every match gets a CSV, and how
+    // many matches this session has started, to tell their files apart
+    match_csv: Option<PathBuf>,
+    csv_export: bool,
+    matches_started: u32,
+    turbo: bool,
+    turbo_ticks_per_frame: usize,
+    // Game time that passes per unit of wall-clock time
+    time_scale: f32,
+    magnifier: bool,
+    // Whether the strip graphing the populations is shown
+    graph: bool,
+    prediction: bool,
+    fog: Option<Fog>,
+    camera: Camera,
+    hotspots: Hotspots,
+    surge: Surge,
+    sparks: Sparks,
+    feed: Feed,
+    replay_writer: Option<ReplayWriter>,
+    playback: Option<ReplayPlayback>,
+    theme: Theme,
+    snow: Snow,
+    art: Art,
+    // Hands wiped out so far this match, and how many of those the
+    // prediction overlay saw coming
+    eliminations: u32,
+    foreseen: u32,
+    // What happened to the populations this match, for the results screen
+    log: EventLog,
+    summary: Option<Summary>,
+    history: Option<History>,
+    finale: Finale,
+    // Set while rounds run back to back
+    endless: Option<Tally>,
+    paused: bool,
+    // The particle the player named, and the name being typed for one
+    champion: Option<Champion>,
+    naming: Option<(Handle, String)>,
+    // The particle the camera stays on, while there is one
+    follow: Option<Follow>,
+    trails: Trails,
+    // Hand dropped into the arena by clicking while playing
+    spawn_hand: Hand,
+    // Set while the player steers a particle of their own
+    avatar: Option<Avatar>,
+    // Milliseconds the left mouse button has been held down for
+    held_for: f32,
+    // Set while one hand starts out as a single particle against the rest
+    infection: Option<Infection>,
+    // The winner the player picked for this match, and the points their
+    // bets have won so far
+    bet: Option<Bet>,
+    score: u32,
+    // What-if branches, once the match has been forked
+    branches: Option<Branches>,
+    rewind: Rewind,
+    // Whether the last frame went back in time
+    rewinding: bool,
+    // How the hands of the match are drawn, as picked from the config file,
+    // the theme and the rules
+    glyphs: Glyphs,
+    glyph_config: GlyphConfig,
+    display: Display,
+    // Hand colours picked in the config file, over the theme's
+    colors: HashMap<String, RGB>,
+    // What the settings menu offers: the built in rules and those of the
+    // config file
+    rule_sets: Vec<Arc<Rules>>,
+    // HUD scale the console is currently sized for
+    ui_scale: u32,
+    // Seed of the match being played, unless it was saved or loaded midway
+    // and the seed no longer plays it out the same, and the one every match
+    // gets if it was given on the command line
+    seed: Option<u64>,
+    fixed_seed: Option<u64>,
+    settings: Settings,
+    // Row picked on the settings menu
+    settings_row: usize,
+}
 
-#[derive(Copy, Clone, Debug)]
-struct Vec2f {
-    x: f64,
-    y: f64,
+struct ReplayPlayback {
+    reader: ReplayReader,
+    timeline: Timeline,
+    frame: u64,
+    frame_time: f32,
 }
 
-impl Vec2f {
-    fn scalar_product(&self, other: &Vec2f) -> f64 {
-        (self.x * other.x) + (self.y * other.y)
+impl State {
+    fn new(config: Config, fixed_seed: Option<u64>) -> Self {
+        let settings = config.settings.clone();
+        let mut rule_sets: Vec<Arc<Rules>> = Rules::presets().into_iter().map(Arc::new).collect();
+        rule_sets.extend(config.rules.map(Arc::new));
+        let seed = fixed_seed.unwrap_or_else(random_seed);
+        let mut state = State {
+            sim: Simulation::new(
+                settings.arena,
+                settings.params.clone(),
+                RandomNumberGenerator::seeded(seed),
+            ),
+            lab: None,
+            comparison: None,
+            grid: None,
+            profile: None,
+            profile_names: Profile::list(),
+            new_profile_name: None,
+            frame_time: 0.0,
+            // The browser has nowhere to keep profiles
+            mode: if cfg!(target_arch = "wasm32") {
+                GameMode::Menu
+            } else {
+                GameMode::Profiles
+            },
+            elapsed_time: 0.0,
+            background_dirty: true,
+            exporter: Exporter::new(),
+            recording: None,
+            match_csv: None,
+            csv_export: config.export.csv,
+            matches_started: 0,
+            turbo: false,
+            turbo_ticks_per_frame: 0,
+            time_scale: 1.0,
+            magnifier: false,
+            graph: false,
+            prediction: false,
+            fog: None,
+            camera: Camera::default(),
+            hotspots: Hotspots::new(&settings.arena),
+            surge: Surge::new(),
+            sparks: Sparks::new(),
+            feed: Feed::default(),
+            replay_writer: None,
+            playback: None,
+            theme: Theme::seasonal(),
+            snow: Snow::new(),
+            art: Art::load(),
+            eliminations: 0,
+            foreseen: 0,
+            log: EventLog::default(),
+            summary: None,
+            history: None,
+            finale: Finale::new(),
+            endless: None,
+            paused: false,
+            champion: None,
+            naming: None,
+            follow: None,
+            trails: Trails::new(config.display.trail_length, config.display.trail_fade),
+            spawn_hand: Hand::default(),
+            avatar: None,
+            held_for: 0.0,
+            infection: None,
+            bet: None,
+            score: 0,
+            branches: None,
+            rewind: Rewind::new(),
+            rewinding: false,
+            glyphs: Glyphs::default(),
+            glyph_config: config.glyphs,
+            display: config.display,
+            colors: config.colors,
+            rule_sets,
+            ui_scale: 1,
+            seed: Some(seed),
+            fixed_seed,
+            settings_row: 0,
+            settings,
+        };
+        state.settings.deal(&mut state.sim);
+        state.apply_theme();
+        state
     }
 
-    fn product(&self, other: f64) -> Vec2f {
-        Vec2f {
-            x: self.x * other,
-            y: self.y * other,
+    fn draw_arena(&mut self, ctx: &mut BTerm) {
+        if self.background_dirty {
+            ctx.set_active_console(BACKGROUND_LAYER);
+            ctx.cls_bg(self.surge.floor(self.theme.arena));
+            self.background_dirty = false;
+        }
+        for layer in [
+            HUD_LAYER,
+            MAGNIFIER_LAYER,
+            UI_LAYER,
+            PARTICLE_LAYER,
+            ARENA_LAYER,
+        ] {
+            ctx.set_active_console(layer);
+            ctx.cls();
+        }
+        if self.display.decorations {
+            self.art.draw_corners(ctx, self.theme.arena);
+        }
+        if self.theme.snow {
+            self.snow.draw(ctx, ctx.frame_time_ms);
         }
     }
 
-    fn minus(&self, other: Vec2f) -> Vec2f {
-        Vec2f {
-            x: self.x - other.x,
-            y: self.y - other.y,
+    fn play(&mut self, ctx: &mut BTerm) {
+        if self.surge.shaking() {
+            // The floor fades back over the frames it shakes for
+            self.background_dirty = true;
         }
-    }
+        self.surge.shake(&mut self.camera, ctx.frame_time_ms);
+        self.sparks.update(ctx.frame_time_ms);
+        self.draw_arena(ctx);
 
-    fn plus(&self, other: Vec2f) -> Vec2f {
-        Vec2f {
-            x: self.x + other.x,
-            y: self.y + other.y,
+        self.rewinding = INPUT.lock().is_key_pressed(REWIND_KEY);
+        if let Some(avatar) = &mut self.avatar {
+            avatar.steer(&INPUT.lock());
+            if let Some(position) = avatar.position(&self.sim) {
+                self.camera.look_at(&position, &self.sim.arena);
+            }
+        }
+        match self.follow.as_ref().map(|f| f.position(&self.sim)) {
+            Some(Some(position)) => self.camera.look_at(&position, &self.sim.arena),
+            // Rewound or switched to a branch from before it was there
+            Some(None) => self.follow = None,
+            None => {}
+        }
+        let clicked = self.read_mouse(ctx);
+        if self.rewinding {
+            self.frame_time = 0.0;
+            self.step_back();
+        } else if self.paused {
+            self.frame_time = 0.0;
+        } else if self.turbo {
+            // Run as many ticks as fit in the frame and only draw the last one
+            let started = (!cfg!(target_arch = "wasm32")).then(Instant::now);
+            let mut ticks = 0;
+            while started.map_or(ticks < WEB_TURBO_TICKS, |started| {
+                started.elapsed() < TURBO_FRAME_BUDGET
+            }) {
+                self.step();
+                ticks += 1;
+                if !matches!(self.mode, GameMode::Playing) {
+                    break;
+                }
+            }
+            self.frame_time = 0.0;
+            self.elapsed_time += ticks as f32 * self.settings.frame_duration;
+            self.turbo_ticks_per_frame = ticks;
+        } else {
+            // Catch up on ticks owed since the last frame, but only so far:
+            // after a long stall the rest is dropped instead of snowballing.
+            let game_time = ctx.frame_time_ms * self.time_scale;
+            self.frame_time += game_time;
+            let mut ticks = 0;
+            while self.frame_time >= self.settings.frame_duration && ticks < MAX_CATCH_UP_TICKS {
+                self.frame_time -= self.settings.frame_duration;
+                self.step();
+                ticks += 1;
+                if !matches!(self.mode, GameMode::Playing) {
+                    break;
+                }
+            }
+            self.frame_time %= self.settings.frame_duration;
+            self.elapsed_time += game_time;
+        }
+
+        if let Some(fog) = &self.fog {
+            ctx.set_active_console(BACKGROUND_LAYER);
+            fog.draw_background(ctx, &self.camera, &self.theme);
+            ctx.set_active_console(ARENA_LAYER);
+        }
+        let shrunk = self.sim.arena.walls().0.x > 0.0;
+        // The fog of war paints its own background
+        let fields = !self.sim.params.fields.is_empty() && self.fog.is_none();
+        let zones = !self.sim.params.zones.is_empty() && self.fog.is_none();
+        if !self.sim.params.obstacles.is_empty() || shrunk || fields || zones {
+            ctx.set_active_console(BACKGROUND_LAYER);
+            let origin = self.camera.origin();
+            if fields {
+                render::draw_fields(ctx, &self.sim, origin, &self.theme);
+            }
+            if zones {
+                render::draw_zones(ctx, &self.sim, &self.glyphs, origin, &self.theme);
+            }
+            render::draw_walls(ctx, &self.sim.arena, origin, &self.theme);
+            let obstacles = &self.sim.params.obstacles;
+            render::draw_obstacles(ctx, obstacles, self.sim.ticks, origin, &self.theme);
+            // Painted over again next frame, as the camera may have moved
+            self.background_dirty = true;
+            ctx.set_active_console(ARENA_LAYER);
+        }
+        let (fog, camera) = (&self.fog, &self.camera);
+        let visible = |particle: &&Particle| {
+            camera.sees(&particle.position) && fog.as_ref().is_none_or(|fog| fog.reveals(particle))
+        };
+
+        if self.prediction {
+            let particles: Vec<&Particle> = self.sim.particles.values().filter(visible).collect();
+            let collisions = prediction::upcoming_collisions(&particles, &self.sim.params.rules);
+            prediction::draw_collision_lines(ctx, &collisions, camera.origin());
+        }
+
+        // Ticks run at a fixed rate, so frames in between show the particles
+        // part of the way through the last one
+        let alpha = if self.paused || self.rewinding || self.turbo {
+            1.0
+        } else {
+            self.frame_time / self.settings.frame_duration
+        };
+        // Trails give away where hidden particles are as much as they do
+        let revealed = |particle: &Particle| fog.as_ref().is_none_or(|fog| fog.reveals(particle));
+        let (glyphs, theme) = (&self.glyphs, &self.theme);
+        self.trails
+            .draw(ctx, &self.sim, revealed, glyphs, camera, theme);
+        render::draw_portals(ctx, &self.sim.params.portals, camera);
+        let pickups = self.sim.pickups.iter();
+        render::draw_pickups(
+            ctx,
+            pickups.filter(|pickup| camera.sees(&pickup.position)),
+            camera.origin(),
+        );
+        let particles = self.sim.interpolated(alpha as Real);
+        render::draw_rings(
+            ctx,
+            particles.iter().filter(visible),
+            camera.origin(),
+            &self.glyphs,
+            &self.theme,
+        );
+        self.sparks.draw(ctx, camera, self.theme.arena);
+        render::render_particles(
+            particles.iter().filter(visible),
+            camera.origin(),
+            &self.glyphs,
+        );
+
+        let mut hud = DrawBatch::new();
+        hud.target(HUD_LAYER);
+        hud.print(
+            Point::new(0, 0),
+            format!(
+                "Elapsed time: {:.1}s, tick {}, speed {}x",
+                self.elapsed_time / 1000.0,
+                self.sim.ticks,
+                self.time_scale
+            ),
+        );
+
+        let counts = self.sim.counts();
+        let total: usize = counts.iter().sum();
+        hud.print(Point::new(0, 1), format!("Scores ({} particles)", total));
+
+        let endangered = if self.prediction {
+            prediction::endangered(&self.sim.history)
+        } else {
+            None
+        };
+        let rules = &self.sim.params.rules;
+        // Everything under the counts moves down for hands past the classic
+        // three, and up for fewer
+        let shift = rules.len() as i32 - Rules::classic().len() as i32;
+        for hand in rules.hands() {
+            let (row, count) = (2 + hand.index(), counts[hand.index()]);
+            if endangered == Some(hand) {
+                hud.print_color(
+                    Point::new(0, row),
+                    format!("{}: {} (endangered)", rules.name(hand), count),
+                    ColorPair::new(RGB::named(YELLOW), RGB::named(BLACK)),
+                );
+            } else {
+                hud.print_color(
+                    Point::new(0, row),
+                    format!("{}: {}", rules.name(hand), count),
+                    ColorPair::new(self.glyphs.color(hand), RGB::named(BLACK)),
+                );
+            }
+        }
+
+        if self.recording.is_some() {
+            let mut status = "Recording populations (R to stop)".to_string();
+            if self.exporter.dropped() > 0 {
+                status += &format!(", {} rows dropped", self.exporter.dropped());
+            }
+            hud.print(Point::new(0, 5 + shift), status);
+        }
+
+        if self.turbo {
+            hud.print(
+                Point::new(0, 6 + shift),
+                format!(
+                    "TURBO: {} ticks/frame (T to stop)",
+                    self.turbo_ticks_per_frame
+                ),
+            );
+        }
+
+        if let Some(fog) = &self.fog {
+            hud.print(
+                Point::new(0, 7 + shift),
+                format!("Fog: {} view (F to change)", rules.name(fog.hand)),
+            );
+        }
+
+        if self.sim.arena.width > SCREEN_WIDTH as Real
+            || self.sim.arena.height > SCREEN_HEIGHT as Real
+        {
+            hud.print(
+                Point::new(0, 8 + shift),
+                format!("View at {}, {} (WASD to scroll)", camera.x, camera.y),
+            );
+        }
+
+        if self.prediction {
+            hud.print(Point::new(0, 9 + shift), "Prediction overlay (O to hide)");
+        }
+
+        if self.rewinding {
+            hud.print(
+                Point::new(0, 12 + shift),
+                format!("REWINDING to tick {}", self.sim.ticks),
+            );
+        } else if let Some((_, name)) = &self.naming {
+            hud.print(Point::new(0, 12 + shift), format!("Name: {}_", name));
+            hud.print(Point::new(0, 13 + shift), "(Enter) Done  (Escape) Cancel");
+        } else if self.paused {
+            hud.print(Point::new(0, 12 + shift), "PAUSED (Space to resume)");
+            hud.print(
+                Point::new(0, 13 + shift),
+                "(.) Step  (K) Fork  (Click) Convert",
+            );
+            hud.print(
+                Point::new(0, 14 + shift),
+                "(Z) Hold to rewind  (N) Name  (F5/F9) Save/Load",
+            );
+        } else if let Some(avatar) = &self.avatar {
+            hud.print(
+                Point::new(0, 12 + shift),
+                format!(
+                    "You are a {} (arrows or WASD to steer)",
+                    rules.name(avatar.hand)
+                ),
+            );
+        } else if self.lab.is_none() && self.infection.is_none() {
+            hud.print(
+                Point::new(0, 12 + shift),
+                format!(
+                    "(Click) Drop a {}  (Tab) Change",
+                    rules.name(self.spawn_hand)
+                ),
+            );
+            hud.print(Point::new(0, 13 + shift), "(Hold left/right) Pull/Push");
+            if self.follow.is_none() {
+                hud.print(
+                    Point::new(0, 14 + shift),
+                    "(Click a particle or N) Follow it",
+                );
+            }
+        }
+
+        if let Some(lines) = self.follow.as_ref().and_then(|f| f.stat_lines(&self.sim)) {
+            for (row, line) in (17 + shift..).zip(lines) {
+                hud.print(Point::new(0, row), line);
+            }
+            hud.print(Point::new(0, 19 + shift), "(N) Next  (WASD) Let go");
+        }
+
+        if let Some(champion) = &self.champion {
+            hud.print(Point::new(0, 15 + shift), champion.stat_line());
+            if let Some(news) = champion.news(self.sim.ticks) {
+                hud.print(Point::new(0, 16 + shift), news);
+            }
+        }
+
+        if let Some(tally) = &self.endless {
+            hud.print(
+                Point::new(0, 10 + shift),
+                format!(
+                    "Round {}, average {} ticks",
+                    tally.rounds() + 1,
+                    tally.average_ticks()
+                ),
+            );
+            let mut wins = "Wins:".to_string();
+            for hand in rules.hands() {
+                wins += &format!(" {} {}", rules.name(hand), tally.wins[hand.index()]);
+            }
+            hud.print(Point::new(0, 11 + shift), wins);
+        }
+
+        if let Some(infection) = &self.infection {
+            let count = self.sim.counts()[infection.hand.index()];
+            hud.print(
+                Point::new(0, 10 + shift),
+                format!(
+                    "Infection: {} {} ({}%)",
+                    rules.name(infection.hand),
+                    count,
+                    infection.percent(count)
+                ),
+            );
+            hud.print(
+                Point::new(0, 11 + shift),
+                format!(
+                    "Peak {} ({}%) at tick {}",
+                    infection.peak,
+                    infection.percent(infection.peak),
+                    infection.peak_tick
+                ),
+            );
+        }
+
+        // Nothing else is batched onto the HUD, so its order doesn't matter
+        if let Err(err) = hud.submit(0) {
+            eprintln!("Could not queue the HUD: {}", err);
+        }
+        ctx.set_active_console(HUD_LAYER);
+        self.feed.draw(ctx, &self.sim, &self.glyphs);
+
+        ctx.set_active_console(UI_LAYER);
+        if self.graph {
+            graph::draw_population_graph(ctx, &self.sim, &self.glyphs);
+        }
+        self.hotspots.draw_indicators(ctx, camera);
+        if let Some(champion) = &self.champion {
+            champion.draw(ctx, &self.sim, camera.origin());
+        }
+        if let Some(avatar) = &self.avatar {
+            avatar.draw(ctx, &self.sim, camera.origin());
+        }
+        if let Some(follow) = &self.follow {
+            follow.draw(ctx, &self.sim, camera.origin());
+        }
+        for well in &self.sim.wells {
+            let center =
+                camera.origin() + Point::new(well.position.x as i32, well.position.y as i32);
+            let color = if well.strength > 0.0 {
+                PULL_COLOR
+            } else {
+                PUSH_COLOR
+            };
+            render::draw_frame(ctx, center, 1, RGB::from(color));
+        }
+        ctx.set_active_console(ARENA_LAYER);
+
+        if self.magnifier {
+            let focus = ctx.mouse_point();
+            let particles = self.sim.particles.values().filter(visible);
+            magnifier::draw_magnifier(
+                ctx,
+                particles,
+                camera.origin(),
+                focus,
+                self.theme.arena,
+                &self.glyphs,
+            );
+        }
+
+        if let Some(lab) = &mut self.lab {
+            lab.update(ctx, &mut self.sim, &self.glyphs);
+        }
+
+        if let Some(branches) = self.branches.as_ref().filter(|b| b.len() > 1) {
+            branches.draw(ctx);
+        }
+
+        if self.naming.is_some() {
+            self.type_champion_name(ctx);
+            return;
+        }
+
+        if self.paused && clicked {
+            if let Some(handle) = self.particle_under_mouse(ctx) {
+                let particle = &mut self.sim.particles[handle];
+                particle.hand = self.sim.params.rules.next(particle.hand);
+            }
+        } else if clicked {
+            // Clicking a particle follows it, and anywhere else drops one
+            match self.particle_under_mouse(ctx) {
+                Some(handle) if self.avatar.is_none() => {
+                    self.follow = Some(Follow::new(handle, &self.sim));
+                }
+                None if self.nudges() => {
+                    let position = self.mouse_position(ctx);
+                    if self.sim.arena.contains(&position) {
+                        self.sim.spawn_moving(self.spawn_hand, position);
+                    }
+                }
+                _ => {}
+            }
         }
-    }
 
-    fn distance(&self, other: &Vec2f) -> f64 {
-        self.minus(*other).norm()
+        if let Some(key) = ctx.key {
+            match key {
+                VirtualKeyCode::R => self.toggle_recording(),
+                VirtualKeyCode::T => self.turbo = !self.turbo,
+                VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+                    self.time_scale = (self.time_scale * 2.0).min(MAX_TIME_SCALE);
+                }
+                VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                    self.time_scale = (self.time_scale / 2.0).max(MIN_TIME_SCALE);
+                }
+                VirtualKeyCode::M => self.magnifier = !self.magnifier,
+                VirtualKeyCode::G => self.graph = !self.graph,
+                VirtualKeyCode::Tab => {
+                    self.spawn_hand = self.sim.params.rules.next(self.spawn_hand);
+                }
+                VirtualKeyCode::O => self.prediction = !self.prediction,
+                VirtualKeyCode::Space => self.paused = !self.paused,
+                VirtualKeyCode::Period if self.paused => {
+                    self.step();
+                    self.elapsed_time += self.settings.frame_duration;
+                }
+                VirtualKeyCode::K if self.paused => self.fork(),
+                VirtualKeyCode::N if self.paused => {
+                    if let Some(handle) = self.particle_under_mouse(ctx) {
+                        self.naming = Some((handle, String::new()));
+                    }
+                }
+                VirtualKeyCode::N if self.avatar.is_none() => {
+                    self.follow = Follow::next(self.follow.as_ref(), &self.sim);
+                }
+                VirtualKeyCode::F => self.cycle_fog(),
+                VirtualKeyCode::F5 => self.quick_save(),
+                VirtualKeyCode::F9 => self.quick_load(),
+                // The avatar takes WASD, and the camera follows it instead
+                VirtualKeyCode::W if self.avatar.is_none() => self.pan(0, -1),
+                VirtualKeyCode::A if self.avatar.is_none() => self.pan(-1, 0),
+                VirtualKeyCode::S if self.avatar.is_none() => self.pan(0, 1),
+                VirtualKeyCode::D if self.avatar.is_none() => self.pan(1, 0),
+                VirtualKeyCode::Q => {
+                    self.stop_recording();
+                    self.close_match_csv();
+                    self.finish_replay();
+                    self.endless = None;
+                    self.mode = GameMode::Menu;
+                }
+                key => {
+                    let branch = DIGIT_KEYS[1..].iter().position(|&digit| digit == key);
+                    if let Some(i) = branch {
+                        self.switch_branch(i);
+                    }
+                }
+            }
+        }
     }
 
-    fn norm(&self) -> f64 {
-        self.scalar_product(self).sqrt()
+    // Scrolls the view by hand, letting go of the particle it followed
+    fn pan(&mut self, dx: i32, dy: i32) {
+        self.follow = None;
+        self.camera.pan(dx, dy, &self.sim.arena);
     }
-}
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-enum Hand {
-    Rock,
-    Paper,
-    Scissors,
-}
+    // Whether the player may drop particles into the match and pull them
+    // around, which would spoil the lab, their own avatar and infections
+    fn nudges(&self) -> bool {
+        self.lab.is_none() && self.avatar.is_none() && self.infection.is_none()
+    }
 
-pub trait Beats {
-    fn beats(&self) -> Self;
-}
+    // Holding the left mouse button down pulls particles towards the mouse
+    // and holding the right one pushes them away. Returns whether the left
+    // one was clicked instead, let go of soon after it went down.
+    fn read_mouse(&mut self, ctx: &mut BTerm) -> bool {
+        let (left, right) = {
+            let input = INPUT.lock();
+            (
+                input.is_mouse_button_pressed(0),
+                input.is_mouse_button_pressed(1),
+            )
+        };
+        // left_click is set both as the button goes down and as it comes up
+        let clicked = ctx.left_click && !left && self.held_for < HOLD_TIME;
+        self.held_for = if left {
+            self.held_for + ctx.frame_time_ms
+        } else {
+            0.0
+        };
 
-impl Beats for Hand {
-    fn beats(&self) -> Self {
-        // match is exhaustive, so every enum variant must be covered
-        match *self {
-            Hand::Rock => Hand::Scissors,
-            Hand::Paper => Hand::Rock,
-            Hand::Scissors => Hand::Paper,
+        self.sim.wells.clear();
+        let strength = if left && self.held_for >= HOLD_TIME {
+            WELL_STRENGTH
+        } else if right {
+            -WELL_STRENGTH
+        } else {
+            return clicked;
+        };
+        if self.nudges() {
+            self.sim.wells.push(Well {
+                position: self.mouse_position(ctx),
+                strength: strength * self.sim.params.speed,
+            });
         }
+        clicked
     }
-}
 
-#[derive(Copy, Clone, Debug)]
-struct Particle {
-    position: Vec2f,
-    velocity: Vec2f,
-    hand: Hand,
-}
+    fn particle_under_mouse(&self, ctx: &mut BTerm) -> Option<Handle> {
+        let position = self.mouse_position(ctx);
+        self.sim.particle_at(&position)
+    }
 
-impl Particle {
-    fn new() -> Self {
-        let mut random = RandomNumberGenerator::new();
-        Particle {
-            position: Vec2f {
-                x: random.range(0.0, SCREEN_WIDTH as f64),
-                y: random.range(0.0, SCREEN_HEIGHT as f64),
-            },
-            velocity: Vec2f {
-                x: random.range(MIN_VELOCITY, MAX_VELOCITY),
-                y: random.range(MIN_VELOCITY, MAX_VELOCITY),
-            },
-            hand: match random.range(0, 3) {
-                0 => Hand::Rock,
-                1 => Hand::Paper,
-                _ => Hand::Scissors,
-            },
+    // The middle of the arena tile under the mouse
+    fn mouse_position(&self, ctx: &mut BTerm) -> Vec2f {
+        ctx.set_active_console(ARENA_LAYER);
+        let point = ctx.mouse_point();
+        Vec2f {
+            x: (point.x + self.camera.x) as Real + 0.5,
+            y: (point.y + self.camera.y) as Real + 0.5,
         }
     }
 
-    fn render(&self, ctx: &mut BTerm) {
-        let glyph: FontCharType = match self.hand {
-            Hand::Rock => 199,
-            Hand::Paper => 193,
-            Hand::Scissors => 196,
+    // Takes the keys while a champion is being named
+    fn type_champion_name(&mut self, ctx: &BTerm) {
+        let Some((handle, name)) = &mut self.naming else {
+            return;
         };
-
-        for dx in -1..2 {
-            for dy in -1..2 {
-                ctx.set(
-                    self.position.x as i32 + dx,
-                    self.position.y as i32 - dy,
-                    WHITE,
-                    BLACK,
-                    (glyph as i32 + dx - 16 * dy) as u16,
-                );
+        match ctx.key {
+            Some(VirtualKeyCode::Return) if !name.is_empty() => {
+                let name = name.clone();
+                self.champion = Champion::new(name, *handle, &self.sim);
+                self.naming = None;
+            }
+            Some(VirtualKeyCode::Escape) => self.naming = None,
+            Some(VirtualKeyCode::Back) => {
+                name.pop();
+            }
+            Some(key) => {
+                if let Some(c) = key_to_char(key, ctx.shift) {
+                    if name.len() < champion::MAX_NAME_LEN {
+                        name.push(c);
+                    }
+                }
             }
+            None => {}
         }
     }
 
-    fn check_wall_collision(&mut self) {
-        if self.position.x < 0.0 {
-            self.position.x = -self.position.x;
-            self.velocity.x = -self.velocity.x;
-        } else if self.position.x > SCREEN_WIDTH as f64 {
-            self.position.x = 2.0 * SCREEN_WIDTH as f64 - self.position.x;
-            self.velocity.x = -self.velocity.x;
+    // Advances the match by one physics tick and records it
+    fn step(&mut self) {
+        let endangered = prediction::endangered(&self.sim.history);
+        let before = self.sim.counts();
+        if let Some(avatar) = &self.avatar {
+            avatar.push(&mut self.sim);
+        }
+        let counts = self.sim.step();
+        self.rewind.record(&self.sim);
+        for hand in self.sim.params.rules.hands() {
+            if before[hand.index()] > 0 && counts[hand.index()] == 0 {
+                self.eliminations += 1;
+                if endangered == Some(hand) {
+                    self.foreseen += 1;
+                }
+            }
+        }
+        self.hotspots.update(&self.sim.conversions);
+        let conversions = self.sim.conversions.len();
+        self.surge.update(conversions, self.sim.particles.len());
+        for conversion in &self.sim.conversions {
+            let Some(particle) = self.sim.particles.get(conversion.loser) else {
+                continue;
+            };
+            if self.fog.as_ref().is_none_or(|fog| fog.reveals(particle)) {
+                let color = self.glyphs.color(particle.hand);
+                self.sparks.burst(conversion.position, color);
+            }
+        }
+        self.feed.update(&self.sim);
+        self.log.record(&self.sim, &counts);
+        if let Some(champion) = &mut self.champion {
+            champion.update(&self.sim);
+        }
+        self.trails.record(&self.sim);
+        if self.follow.as_mut().is_some_and(|f| !f.update(&self.sim)) {
+            self.follow = None;
+        }
+        if let Some(fog) = &mut self.fog {
+            fog.update(&self.sim);
+        }
+        let lost_to = self.avatar.as_mut().and_then(|a| a.update(&self.sim));
+        if let Some(infection) = &mut self.infection {
+            infection.update(&counts, self.sim.ticks);
+        }
+        if let Some(path) = &self.recording {
+            let row = population_row(self.sim.ticks, &counts);
+            self.exporter.write_optional(path, row.into_bytes());
+        }
+        if let Some(path) = &self.match_csv {
+            let row = population_row(self.sim.ticks, &counts);
+            self.exporter.write(path, row.into_bytes());
+        }
+        if let Some(writer) = &mut self.replay_writer {
+            writer.push_frame(
+                &mut self.exporter,
+                self.sim.ticks,
+                self.sim.particles.values(),
+            );
         }
 
-        if self.position.y < 0.0 {
-            self.position.y = -self.position.y;
-            self.velocity.y = -self.velocity.y;
-        } else if self.position.y > SCREEN_HEIGHT as f64 {
-            self.position.y = 2.0 * SCREEN_HEIGHT as f64 - self.position.y;
-            self.velocity.y = -self.velocity.y;
+        // The lab keeps running so hands can be brought back
+        if self.lab.is_some() {
+            return;
+        }
+        self.finale.record(&self.sim);
+        // The match is over for the player once their avatar is won over
+        if let Some(winner) = lost_to {
+            self.mode = GameMode::End { winner };
+            self.finish_match_csv(None);
+            self.stop_recording();
+            self.finish_replay();
+            return;
+        }
+        // An infection is over once it dies out, whoever is left
+        if let Some(infection) = &self.infection {
+            if infection.contained(&counts) {
+                self.score += infection.points(false);
+                let winner = self
+                    .sim
+                    .params
+                    .rules
+                    .hands()
+                    .max_by_key(|hand| counts[hand.index()])
+                    .unwrap_or_default();
+                self.mode = GameMode::End { winner };
+                self.finish_match_csv(None);
+                self.stop_recording();
+                self.finish_replay();
+                return;
+            }
+        }
+        if let Some(winner) = self.sim.winner(&counts) {
+            if let Some(tally) = &mut self.endless {
+                tally.record(winner, self.sim.ticks);
+                self.record_win(winner);
+                self.rematch();
+                return;
+            }
+            if let Some(bet) = &self.bet {
+                self.score += bet.payout(winner, self.sim.ticks);
+            }
+            if let Some(infection) = &self.infection {
+                self.score += infection.points(infection.took_over(&counts));
+            }
+            self.mode = GameMode::Finale { winner };
+            self.frame_time = 0.0;
+            if let Some(position) = self.finale.decisive_position() {
+                self.camera.look_at(&position, &self.sim.arena);
+            }
+            self.record_win(winner);
+            self.stop_recording();
+            self.finish_replay();
         }
     }
 
-    fn collides_width(&self, other: &Particle) -> bool {
-        self.position.distance(&other.position) < 2.0 * PARTICLE_RADIUS
+    // Adds a finished match to the run history and, if it was a classic one,
+    // to the player's leaderboard. Other arena sizes take too long to compare.
+    fn record_win(&mut self, winner: Hand) {
+        self.finish_match_csv(Some(winner));
+        let score = match &self.infection {
+            Some(infection) => infection.points(winner == infection.hand),
+            None => self.bet.map_or(0, |bet| bet.payout(winner, self.sim.ticks)),
+        };
+        let winner = self.sim.params.rules.name(winner).to_string();
+        let run = Run {
+            ticks: self.sim.ticks,
+            winner: winner.clone(),
+            arena: self.sim.arena,
+            eliminations: self.eliminations,
+            foreseen: self.foreseen,
+            seed: self.seed,
+            settings: Some(self.settings.fingerprint()),
+            score,
+        };
+        let path = self.save_path(stats::RUNS_FILE);
+        if let Err(err) = run.append(&path) {
+            eprintln!("Could not write {}: {}", path.display(), err);
+        }
+
+        let Some(profile) = &mut self.profile else {
+            return;
+        };
+        if self.sim.arena != Arena::full_screen()
+            || !self.settings.is_classic()
+            || self.avatar.is_some()
+            || self.infection.is_some()
+        {
+            return;
+        }
+        let entry = Entry {
+            ticks: self.sim.ticks,
+            winner,
+        };
+        if profile.record(entry).is_some() {
+            if let Err(err) = profile.save() {
+                eprintln!("Could not save profile {}: {}", profile.name, err);
+            }
+        }
     }
 
-    fn velocity_projection(&self, other: &Particle) -> Vec2f {
-        let line = other.position.minus(self.position);
-        line.product(self.velocity.scalar_product(&line) / line.norm().powi(2))
+    // Carries on from here as a new what-if branch, keeping the match as it
+    // is now to come back to
+    fn fork(&mut self) {
+        // A replay follows a single timeline, so it ends at the first fork
+        self.finish_replay();
+        self.close_match_csv();
+        let branches = self.branches.get_or_insert_with(Branches::new);
+        if !branches.fork(&self.sim) {
+            eprintln!("No room for more than {} branches", branch::MAX_BRANCHES);
+        }
     }
 
-    fn update_position(&mut self) {
-        self.position = self.position.plus(self.velocity);
+    // Takes the match back to its last snapshot
+    fn step_back(&mut self) {
+        if !self.rewind.step_back(&mut self.sim) {
+            return;
+        }
+        // A replay follows a single timeline, so it ends at the first rewind
+        self.finish_replay();
+        self.close_match_csv();
+        self.hotspots = Hotspots::new(&self.sim.arena);
+        self.surge.clear();
+        self.sparks.clear();
+        self.feed.clear();
+        self.trails.clear();
+        self.log.rewind(&self.sim);
+        self.finale.clear();
+        if let Some(fog) = &mut self.fog {
+            fog.update(&self.sim);
+        }
     }
 
-    fn handle_match(&mut self, other: Hand) {
-        if other.beats() == self.hand {
-            self.hand = other;
+    fn switch_branch(&mut self, i: usize) {
+        let Some(branches) = &mut self.branches else {
+            return;
+        };
+        if branches.switch(i, &mut self.sim) {
+            // Anything tracked over time belongs to the branch left behind
+            self.hotspots = Hotspots::new(&self.sim.arena);
+            self.surge.clear();
+            self.sparks.clear();
+            self.feed.clear();
+            self.trails.clear();
+            self.log.rewind(&self.sim);
+            self.finale.clear();
+            self.rewind.clear();
+            if let Some(fog) = &mut self.fog {
+                fog.update(&self.sim);
+            }
         }
     }
-}
 
-struct State {
-    particles: [Particle; NUM_PARTICLES],
-    frame_time: f32,
-    mode: GameMode,
-    elapsed_time: f32,
-}
+    // Switches the fog of war to the next hand's point of view, and off
+    // after the last one
+    fn cycle_fog(&mut self) {
+        let rules = &self.sim.params.rules;
+        let next = match &self.fog {
+            None => rules.hands().next(),
+            Some(fog) => rules
+                .hands()
+                .find(|hand| hand.index() == fog.hand.index() + 1),
+        };
+        self.fog = next.map(|hand| Fog::new(hand, &self.sim));
+        self.background_dirty = true;
+    }
 
-impl State {
-    fn new() -> Self {
-        State {
-            particles: [(); NUM_PARTICLES].map(|_| Particle::new()),
-            frame_time: 0.0,
-            mode: GameMode::Menu,
-            elapsed_time: 0.0,
+    fn toggle_recording(&mut self) {
+        if self.recording.is_some() {
+            self.stop_recording();
+            return;
         }
+
+        let path = self.save_path(&format!("populations-{}.csv", unix_timestamp()));
+        let header = population_header(&self.sim.params.rules);
+        self.exporter.write(&path, header.into_bytes());
+        self.recording = Some(path);
     }
 
-    fn collide(&mut self, lhs: usize, rhs: usize) {
-        // Changes in velocity
-        let v_lr = self.particles[lhs].velocity_projection(&self.particles[rhs]);
-        let v_rl = self.particles[rhs].velocity_projection(&self.particles[lhs]);
+    fn stop_recording(&mut self) {
+        if let Some(path) = self.recording.take() {
+            self.exporter.close(&path);
+        }
+    }
 
-        self.particles[lhs].velocity = self.particles[lhs].velocity.minus(v_lr).plus(v_rl);
-        self.particles[rhs].velocity = self.particles[rhs].velocity.minus(v_rl).plus(v_lr);
+    // Starts this match's CSV, from the populations it starts with
+    fn start_match_csv(&mut self) {
+        self.matches_started += 1;
+        if !self.csv_export {
+            return;
+        }
+        let file = format!("match-{}-{}.csv", unix_timestamp(), self.matches_started);
+        let path = self.save_path(&file);
+        let mut contents = population_header(&self.sim.params.rules);
+        contents += &population_row(self.sim.ticks, &self.sim.counts());
+        self.exporter.write(&path, contents.into_bytes());
+        self.match_csv = Some(path);
+    }
 
-        // Displace particles to leave collision condition
-        let distance = self.particles[rhs]
-            .position
-            .distance(&self.particles[lhs].position);
-        let displacement = PARTICLE_RADIUS - distance / 2.0; // per particle
+    // Ends this match's CSV with a summary row:
+    //
+    //   summary,<winner>,<ticks>,<conversions>
+    //
+    // with no winner if the player's avatar was won over first
+    fn finish_match_csv(&mut self, winner: Option<Hand>) {
+        let Some(path) = &self.match_csv else {
+            return;
+        };
+        let winner = winner.map_or("", |hand| self.sim.params.rules.name(hand));
+        let conversions: u32 = self.log.conversions(&self.sim.params.rules).iter().sum();
+        let row = format!("summary,{},{},{}\n", winner, self.sim.ticks, conversions);
+        self.exporter.write(path, row.into_bytes());
+        self.close_match_csv();
+    }
 
-        let l_to_r = self.particles[rhs]
-            .position
-            .minus(self.particles[lhs].position);
-        let displacement_vec = l_to_r.product(displacement / l_to_r.norm());
+    // Stops this match's CSV where it is, for matches left unfinished or
+    // that stopped following a single timeline
+    fn close_match_csv(&mut self) {
+        if let Some(path) = self.match_csv.take() {
+            self.exporter.close(&path);
+        }
+    }
 
-        self.particles[lhs].position = self.particles[lhs].position.minus(displacement_vec);
-        self.particles[rhs].position = self.particles[rhs].position.plus(displacement_vec);
+    fn finish_replay(&mut self) {
+        if let Some(writer) = self.replay_writer.take() {
+            writer.finish(&mut self.exporter);
+        }
+    }
 
-        // Change symbol type
-        self.particles[lhs].handle_match(self.particles[rhs].hand);
-        self.particles[rhs].handle_match(self.particles[lhs].hand);
+    // Where a file saved for the current player goes
+    fn save_path(&self, file: &str) -> PathBuf {
+        match &self.profile {
+            Some(profile) => profile.path(file),
+            None => paths::data_dir().join(file),
+        }
     }
 
-    fn play(&mut self, ctx: &mut BTerm) {
-        ctx.cls_bg((54, 126, 127));
+    fn watch_last_match(&mut self) {
+        // The last frames may still be on their way to disk
+        self.exporter.sync();
+        let path = self.save_path(REPLAY_FILE);
+        let opened = ReplayReader::open(&path).and_then(|mut reader| {
+            let timeline = Timeline::new(&mut reader, self.sim.params.rules.len())?;
+            Ok((reader, timeline))
+        });
+        match opened {
+            Ok((reader, timeline)) => {
+                self.playback = Some(ReplayPlayback {
+                    reader,
+                    timeline,
+                    frame: 0,
+                    frame_time: 0.0,
+                });
+                self.mode = GameMode::Replay;
+                self.background_dirty = true;
+            }
+            Err(err) => eprintln!("Could not open {}: {}", path.display(), err),
+        }
+    }
 
-        self.frame_time += ctx.frame_time_ms;
-        if self.frame_time > FRAME_DURATION {
-            self.frame_time = 0.0;
+    fn watch_replay(&mut self, ctx: &mut BTerm) {
+        self.draw_arena(ctx);
+        let Some(playback) = &mut self.playback else {
+            self.mode = GameMode::Menu;
+            return;
+        };
 
-            for particle in &mut self.particles {
-                particle.update_position();
-                particle.check_wall_collision();
-            }
+        let last_frame = playback.reader.frame_count() - 1;
+        playback.frame_time += ctx.frame_time_ms;
+        if playback.frame_time > self.settings.frame_duration {
+            playback.frame_time = 0.0;
+            playback.frame = (playback.frame + 1).min(last_frame);
+        }
 
-            (0..NUM_PARTICLES).for_each(|lhs| {
-                for rhs in lhs + 1..NUM_PARTICLES {
-                    if self.particles[lhs].collides_width(&self.particles[rhs]) {
-                        self.collide(lhs, rhs);
-                        return;
-                    }
+        if let Some(key) = ctx.key {
+            match key {
+                VirtualKeyCode::Left => {
+                    playback.frame = playback.timeline.previous_event(playback.frame)
                 }
-            });
+                VirtualKeyCode::Right => {
+                    playback.frame = playback.timeline.next_event(playback.frame)
+                }
+                VirtualKeyCode::Q => {
+                    self.playback = None;
+                    self.mode = GameMode::Menu;
+                    return;
+                }
+                // Replays are of the last match, so its arena bounds the view
+                VirtualKeyCode::W => self.camera.pan(0, -1, &self.sim.arena),
+                VirtualKeyCode::A => self.camera.pan(-1, 0, &self.sim.arena),
+                VirtualKeyCode::S => self.camera.pan(0, 1, &self.sim.arena),
+                VirtualKeyCode::D => self.camera.pan(1, 0, &self.sim.arena),
+                _ => {}
+            }
         }
 
-        for particle in &self.particles {
-            particle.render(ctx);
+        if let Some(frame) = playback.timeline.clicked(ctx) {
+            playback.frame = frame;
+            playback.frame_time = 0.0;
         }
 
-        self.elapsed_time += ctx.frame_time_ms;
+        ctx.set_active_console(UI_LAYER);
+        playback.timeline.draw(ctx, playback.frame, &self.glyphs);
+        ctx.set_active_console(ARENA_LAYER);
+
+        let position = playback.frame;
+        match playback.reader.frame(position) {
+            Ok(frame) => {
+                let camera = &self.camera;
+                let particles = frame.particles.iter().filter(|p| camera.sees(&p.position));
+                render::render_particles(particles, camera.origin(), &self.glyphs);
+                ctx.set_active_console(HUD_LAYER);
+                ctx.print(0, 0, format!("Replay tick: {}", frame.tick));
+            }
+            Err(err) => {
+                eprintln!(
+                    "Could not read {}: {}",
+                    self.save_path(REPLAY_FILE).display(),
+                    err
+                );
+                self.playback = None;
+                self.mode = GameMode::Menu;
+                return;
+            }
+        }
         ctx.print(
             0,
-            0,
-            &format!("Elapsed time: {}", self.elapsed_time / 1000.0),
+            1,
+            format!("Frame {} of {}", position + 1, last_frame + 1),
         );
+        ctx.print(0, 2, "(Left/Right) Eliminations");
+        ctx.print(0, 3, "(Click) Seek  (Q) Menu");
+    }
 
-        ctx.print(0, 1, "Scores");
-        let hands = [Hand::Rock, Hand::Paper, Hand::Scissors];
-        let mut counts: [usize; 3] = [0, 0, 0];
+    // Ticks owed since the last frame, capped like in `play` so a long stall
+    // is dropped instead of snowballing
+    fn due_ticks(&mut self, ctx: &BTerm) -> usize {
+        self.frame_time += ctx.frame_time_ms;
+        let ticks =
+            ((self.frame_time / self.settings.frame_duration) as usize).min(MAX_CATCH_UP_TICKS);
+        self.frame_time %= self.settings.frame_duration;
+        ticks
+    }
 
-        self.particles.iter().for_each(|p| {
-            let idx = match p.hand {
-                Hand::Rock => 0,
-                Hand::Paper => 1,
-                Hand::Scissors => 2,
-            };
-            counts[idx] += 1;
-        });
+    fn compare(&mut self, ctx: &mut BTerm) {
+        self.draw_arena(ctx);
+        let ticks = self.due_ticks(ctx);
+        let Some(comparison) = &mut self.comparison else {
+            self.mode = GameMode::Menu;
+            return;
+        };
 
-        hands.iter().enumerate().for_each(|(i, hand)| {
-            ctx.print(0, 2 + i, &format!("{:?}: {}", hand, counts[i]));
+        for _ in 0..ticks {
+            comparison.step();
+        }
+        comparison.draw(ctx, &self.glyphs);
 
-            if counts[i] == NUM_PARTICLES {
-                self.mode = GameMode::End { winner: *hand };
-            }
-        });
-    
-        if let Some(key) = ctx.key {
-            match key {
-                VirtualKeyCode::Q => self.mode = GameMode::Menu,
-                _ => {}
+        match ctx.key {
+            Some(VirtualKeyCode::Q) => {
+                self.comparison = None;
+                self.mode = GameMode::Menu;
             }
+            Some(key) => comparison.handle_key(key),
+            None => {}
+        }
+    }
+
+    fn watch_grid(&mut self, ctx: &mut BTerm) {
+        self.draw_arena(ctx);
+        let ticks = self.due_ticks(ctx);
+        let Some(grid) = &mut self.grid else {
+            self.mode = GameMode::Menu;
+            return;
+        };
+
+        for _ in 0..ticks {
+            grid.step();
+        }
+        grid.draw(ctx, &self.glyphs);
+
+        if let Some(VirtualKeyCode::Q) = ctx.key {
+            self.grid = None;
+            self.mode = GameMode::Menu;
         }
     }
 
-    fn restart(&mut self) {
-        self.particles = [(); NUM_PARTICLES].map(|_| Particle::new());
+    fn restart(&mut self, arena: Arena) {
+        let seed = self.fixed_seed.unwrap_or_else(random_seed);
+        self.seed = Some(seed);
+        let rng = RandomNumberGenerator::seeded(seed);
+        let mut sim = Simulation::new(arena, self.settings.params.clone(), rng);
+        self.settings.deal(&mut sim);
+        if let Some(infection) = &mut self.infection {
+            infection.start(&mut sim);
+        }
+        self.start_match(sim);
+        if self.avatar.is_some() {
+            self.avatar = Avatar::new(&self.sim);
+        }
+        // Plain matches open on a chance to bet on the winner
+        self.bet = None;
+        self.mode = if self.lab.is_none()
+            && self.endless.is_none()
+            && self.avatar.is_none()
+            && self.infection.is_none()
+        {
+            GameMode::Betting
+        } else {
+            GameMode::Playing
+        };
+    }
+
+    // Starts another match like the one just played, in an arena as big and
+    // with walls open again, closing in as fast as they did
+    fn rematch(&mut self) {
+        let shrink = self.sim.params.shrink;
+        self.restart(Arena {
+            inset: 0.0,
+            ..self.sim.arena
+        });
+        self.sim.params.shrink = shrink;
+    }
+
+    // Plays `sim` from wherever it is, forgetting everything about the match
+    // before it
+    fn start_match(&mut self, sim: Simulation) {
+        self.stop_recording();
+        self.close_match_csv();
+        self.finish_replay();
+        self.sim = sim;
+        self.eliminations = 0;
+        self.foreseen = 0;
+        self.log.start(&self.sim);
+        if self.spawn_hand.index() >= self.sim.params.rules.len() {
+            self.spawn_hand = Hand::default();
+        }
+        self.rebuild_glyphs();
+        self.finale.clear();
+        self.paused = false;
+        self.champion = None;
+        self.naming = None;
+        self.follow = None;
+        self.branches = None;
+        self.rewind.clear();
+        self.camera = Camera::default();
+        self.hotspots = Hotspots::new(&self.sim.arena);
+        self.surge.clear();
+        self.sparks.clear();
+        self.feed.clear();
+        self.trails.clear();
+        self.fog = None;
+        let path = self.save_path(REPLAY_FILE);
+        self.replay_writer = Some(ReplayWriter::create(&mut self.exporter, &path));
+        self.start_match_csv();
         self.frame_time = 0.0;
-        self.mode = GameMode::Playing;
-        self.elapsed_time = 0.0;
+        self.elapsed_time = self.sim.ticks as f32 * self.settings.frame_duration;
+        self.background_dirty = true;
+    }
+
+    // Writes the match as it is now to the quick save, over the last one
+    fn quick_save(&mut self) {
+        let path = self.save_path(QUICKSAVE_FILE);
+        if let Err(err) = fs::write(&path, save::save(&mut self.sim)) {
+            eprintln!("Could not write {}: {}", path.display(), err);
+        }
+        // Saving starts the random numbers over, so from here on the match
+        // plays out differently from what its seed would
+        self.seed = None;
+    }
+
+    // Carries on from the quick save as a plain match. The particles come
+    // back under new handles, so nothing that follows one of them survives.
+    fn quick_load(&mut self) {
+        let path = self.save_path(QUICKSAVE_FILE);
+        let loaded = fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| save::load(&contents));
+        match loaded {
+            Ok(sim) => {
+                self.start_match(sim);
+                self.seed = None;
+                self.avatar = None;
+                self.infection = None;
+                self.bet = None;
+            }
+            Err(err) => eprintln!("Could not load {}: {}", path.display(), err),
+        }
     }
 
     fn main_menu(&mut self, ctx: &mut BTerm) {
-        ctx.cls();
-        ctx.print_centered(5, "Welcome to Rock Paper Scissors!");
-        ctx.print_centered(8, "(P) Play Game");
-        ctx.print_centered(9, "(Q) Quit Game");
+        clear_screen(ctx);
+        self.art.draw_menu(ctx);
+        let top = self.art.draw_title(ctx, 4) + 1;
+        if let Some(profile) = &self.profile {
+            ctx.print_centered(top, format!("Playing as {}", profile.name));
+        }
+        let options = [
+            "(P) Play Game",
+            "(A) Play as a Particle",
+            "(B) Big World",
+            "(R) Battle Royale",
+            "(I) Infection",
+            "(L) Lab Mode",
+            "(C) Compare A/B",
+            "(G) Grid of Matches",
+            "(N) Endless Loop",
+            "(W) Watch Last Match",
+            "(T) Statistics",
+            "(H) History",
+            "(S) Switch Profile",
+            "(O) Open Data Folder",
+            "(M) Match Settings",
+            "(Q) Quit Game",
+        ];
+        for (row, option) in (top + 2..).zip(options) {
+            ctx.print_centered(row, option);
+        }
+        let seasonal = self.profile.as_ref().is_none_or(|p| p.seasonal_themes);
+        let status = if seasonal { "On" } else { "Off" };
+        ctx.print_centered(
+            top + options.len() as i32 + 3,
+            format!("(E) Seasonal Themes: {} ({})", status, self.theme.name),
+        );
 
         if let Some(key) = ctx.key {
             match key {
-                VirtualKeyCode::P => self.restart(),
+                VirtualKeyCode::P => {
+                    self.lab = None;
+                    self.endless = None;
+                    self.avatar = None;
+                    self.infection = None;
+                    self.restart(self.settings.arena);
+                }
+                VirtualKeyCode::A => {
+                    self.lab = None;
+                    self.endless = None;
+                    self.infection = None;
+                    self.restart(self.settings.arena);
+                    self.avatar = Avatar::new(&self.sim);
+                    self.mode = GameMode::Playing;
+                }
+                VirtualKeyCode::N => {
+                    self.lab = None;
+                    self.endless = Some(Tally::new(&self.settings.params.rules));
+                    self.avatar = None;
+                    self.infection = None;
+                    self.restart(self.settings.arena);
+                }
+                VirtualKeyCode::B => {
+                    self.lab = None;
+                    self.endless = None;
+                    self.avatar = None;
+                    self.infection = None;
+                    self.restart(Arena {
+                        width: BIG_WORLD_SIZE,
+                        height: BIG_WORLD_SIZE,
+                        ..self.settings.arena
+                    });
+                }
+                VirtualKeyCode::R => {
+                    self.lab = None;
+                    self.endless = None;
+                    self.avatar = None;
+                    self.infection = None;
+                    self.restart(self.settings.arena);
+                    if self.sim.params.shrink == 0.0 {
+                        self.sim.params.shrink = BATTLE_ROYALE_SHRINK;
+                    }
+                }
+                VirtualKeyCode::I => {
+                    self.lab = None;
+                    self.endless = None;
+                    self.avatar = None;
+                    // Picks patient zero as the match starts
+                    self.infection = Some(Infection::default());
+                    self.restart(self.settings.arena);
+                }
+                VirtualKeyCode::L => {
+                    self.lab = Some(Lab::default());
+                    self.endless = None;
+                    self.avatar = None;
+                    self.infection = None;
+                    self.restart(self.settings.arena);
+                }
+                VirtualKeyCode::C => {
+                    self.comparison = Some(Comparison::new(self.settings.params.rules.clone()));
+                    self.frame_time = 0.0;
+                    self.mode = GameMode::Compare;
+                    self.background_dirty = true;
+                }
+                VirtualKeyCode::G => {
+                    self.grid = Some(Grid::new(self.settings.params.rules.clone()));
+                    self.frame_time = 0.0;
+                    self.mode = GameMode::Grid;
+                    self.background_dirty = true;
+                }
+                VirtualKeyCode::W => self.watch_last_match(),
+                VirtualKeyCode::S => {
+                    self.profile_names = Profile::list();
+                    self.mode = GameMode::Profiles;
+                }
+                VirtualKeyCode::E => self.toggle_seasonal_themes(),
+                VirtualKeyCode::M => self.mode = GameMode::Settings,
+                VirtualKeyCode::O => {
+                    let dir = paths::data_dir();
+                    if let Err(err) = paths::open_in_file_manager(&dir) {
+                        eprintln!("Could not open {}: {}", dir.display(), err);
+                    }
+                }
+                VirtualKeyCode::T => {
+                    let path = self.save_path(stats::RUNS_FILE);
+                    match Run::load(&path) {
+                        Ok(runs) => {
+                            self.summary = Some(Summary::new(&runs));
+                            self.mode = GameMode::Statistics;
+                        }
+                        Err(err) => eprintln!("Could not read {}: {}", path.display(), err),
+                    }
+                }
+                VirtualKeyCode::H => {
+                    let path = self.save_path(stats::RUNS_FILE);
+                    match Run::load(&path) {
+                        Ok(runs) => {
+                            self.history = Some(History::new(&runs));
+                            self.mode = GameMode::History;
+                        }
+                        Err(err) => eprintln!("Could not read {}: {}", path.display(), err),
+                    }
+                }
                 VirtualKeyCode::Q => ctx.quitting = true,
                 _ => {}
             }
         }
     }
 
+    fn select_profile(&mut self, ctx: &mut BTerm) {
+        clear_screen(ctx);
+        ctx.print_centered(5, "Who is playing?");
+
+        if let Some(name) = &mut self.new_profile_name {
+            ctx.print_centered(8, format!("New profile: {}_", name));
+            ctx.print_centered(10, "Letters, digits, - and _ only");
+            ctx.print_centered(12, "(Enter) Create  (Escape) Back");
+
+            match ctx.key {
+                Some(VirtualKeyCode::Return) if Profile::is_valid_name(name) => {
+                    let name = name.clone();
+                    self.new_profile_name = None;
+                    self.open_profile(&name);
+                }
+                Some(VirtualKeyCode::Escape) => self.new_profile_name = None,
+                Some(VirtualKeyCode::Back) => {
+                    name.pop();
+                }
+                Some(key) => {
+                    if let Some(c) = key_to_char(key, ctx.shift) {
+                        if name.len() < profile::MAX_NAME_LEN {
+                            name.push(c);
+                        }
+                    }
+                }
+                None => {}
+            }
+            return;
+        }
+
+        for (i, name) in self
+            .profile_names
+            .iter()
+            .take(DIGIT_KEYS.len() - 1)
+            .enumerate()
+        {
+            ctx.print_centered(8 + i, format!("({}) {}", i + 1, name));
+        }
+        let row = 9 + self.profile_names.len().min(DIGIT_KEYS.len() - 1);
+        ctx.print_centered(row, "(N) New Profile");
+        ctx.print_centered(row + 1, "(Q) Quit Game");
+
+        match ctx.key {
+            Some(VirtualKeyCode::N) => self.new_profile_name = Some(String::new()),
+            Some(VirtualKeyCode::Q) => ctx.quitting = true,
+            Some(key) => {
+                let chosen = DIGIT_KEYS[1..].iter().position(|&digit| digit == key);
+                if let Some(name) = chosen.and_then(|i| self.profile_names.get(i)) {
+                    let name = name.clone();
+                    self.open_profile(&name);
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn open_profile(&mut self, name: &str) {
+        match Profile::open(name) {
+            Ok(profile) => {
+                // Replays and recordings so far belong to the previous player
+                self.stop_recording();
+                self.finish_replay();
+                self.profile = Some(profile);
+                self.apply_theme();
+                self.mode = GameMode::Menu;
+            }
+            Err(err) => eprintln!("Could not open profile {}: {}", name, err),
+        }
+    }
+
+    // Changes what the next matches are made of. restart() picks them up.
+    fn settings_menu(&mut self, ctx: &mut BTerm) {
+        clear_screen(ctx);
+        ctx.print_centered(5, "Match Settings");
+        for row in 0..settings::MENU_ROWS {
+            let line = self.settings.menu_line(row);
+            if row == self.settings_row {
+                ctx.print_color_centered(
+                    8 + row,
+                    RGB::named(YELLOW),
+                    RGB::named(BLACK),
+                    format!("> {} <", line),
+                );
+            } else {
+                ctx.print_centered(8 + row, line);
+            }
+        }
+        if !self.settings.is_classic() {
+            ctx.print_centered(15, "Matches with changed settings");
+            ctx.print_centered(16, "stay off the leaderboard");
+        }
+        ctx.print_centered(18, "(Up/Down) Pick  (Left/Right) Change");
+        ctx.print_centered(19, "(R) Reset to Classic  (Q) Menu");
+
+        let row = self.settings_row;
+        match ctx.key {
+            Some(VirtualKeyCode::Up) => {
+                self.settings_row = (row + settings::MENU_ROWS - 1) % settings::MENU_ROWS
+            }
+            Some(VirtualKeyCode::Down) => self.settings_row = (row + 1) % settings::MENU_ROWS,
+            Some(VirtualKeyCode::Left) => self.settings.adjust(row, -1, &self.rule_sets),
+            Some(VirtualKeyCode::Right) => self.settings.adjust(row, 1, &self.rule_sets),
+            Some(VirtualKeyCode::R) => self.settings = Settings::default(),
+            Some(VirtualKeyCode::Q | VirtualKeyCode::Escape) => self.mode = GameMode::Menu,
+            _ => {}
+        }
+    }
+
+    fn statistics(&mut self, ctx: &mut BTerm) {
+        clear_screen(ctx);
+        let Some(summary) = &self.summary else {
+            self.mode = GameMode::Menu;
+            return;
+        };
+
+        let title = match &self.profile {
+            Some(profile) => format!("Statistics for {}", profile.name),
+            None => "Statistics".to_string(),
+        };
+        ctx.print_centered(5, title);
+        for (i, line) in summary.lines().into_iter().enumerate() {
+            ctx.print_centered(8 + i, line);
+        }
+        ctx.print_centered(18, "Round lengths are of classic matches");
+        ctx.print_centered(20, "(Q) Menu");
+
+        if let Some(VirtualKeyCode::Q) = ctx.key {
+            self.summary = None;
+            self.mode = GameMode::Menu;
+        }
+    }
+
+    // The latest matches and the best scores, from the run history
+    fn match_history(&mut self, ctx: &mut BTerm) {
+        clear_screen(ctx);
+        let Some(history) = &self.history else {
+            self.mode = GameMode::Menu;
+            return;
+        };
+
+        let title = match &self.profile {
+            Some(profile) => format!("History for {}", profile.name),
+            None => "History".to_string(),
+        };
+        ctx.print_centered(3, title);
+        ctx.print_centered(5, "Recent matches");
+        let recent = history.recent_lines();
+        for (i, line) in recent.iter().enumerate() {
+            ctx.print(3, 7 + i, line);
+        }
+        let best_y = 8 + recent.len();
+        ctx.print_centered(best_y, "Best scores");
+        for (i, line) in history.best_lines().into_iter().enumerate() {
+            ctx.print_centered(best_y + 2 + i, line);
+        }
+        ctx.print_centered(best_y + 9, "(Q) Menu");
+
+        if let Some(VirtualKeyCode::Q) = ctx.key {
+            self.history = None;
+            self.mode = GameMode::Menu;
+        }
+    }
+
+    fn toggle_seasonal_themes(&mut self) {
+        if let Some(profile) = &mut self.profile {
+            profile.seasonal_themes = !profile.seasonal_themes;
+            if let Err(err) = profile.save() {
+                eprintln!("Could not save profile {}: {}", profile.name, err);
+            }
+        }
+        self.apply_theme();
+    }
+
+    // Picks the theme for today, or the classic one if the player opted out
+    // of seasonal ones
+    fn apply_theme(&mut self) {
+        let seasonal = self.profile.as_ref().is_none_or(|p| p.seasonal_themes);
+        self.theme = if seasonal {
+            Theme::seasonal()
+        } else {
+            Theme::classic()
+        };
+        self.rebuild_glyphs();
+        self.background_dirty = true;
+    }
+
+    // Picks the glyphs and colours for the hands of the match being played
+    fn rebuild_glyphs(&mut self) {
+        self.glyphs = Glyphs::new(
+            &self.sim.params.rules,
+            &self.glyph_config,
+            &self.colors,
+            &self.theme,
+        );
+    }
+
+    // Shows the match as it starts and takes the player's pick of a winner
+    fn place_bet(&mut self, ctx: &mut BTerm) {
+        self.draw_arena(ctx);
+        let camera = &self.camera;
+        render::render_particles(
+            self.sim
+                .particles
+                .values()
+                .filter(|p| camera.sees(&p.position)),
+            camera.origin(),
+            &self.glyphs,
+        );
+
+        ctx.set_active_console(HUD_LAYER);
+        ctx.print(0, 0, "Who will win?");
+        let counts = self.sim.counts();
+        let rules = &self.sim.params.rules;
+        // Only as many hands as there are digit keys can be bet on
+        let hands = rules.len().min(DIGIT_KEYS.len() - 1);
+        for hand in rules.hands().take(hands) {
+            ctx.print_color(
+                0,
+                2 + hand.index(),
+                self.glyphs.color(hand),
+                RGB::named(BLACK),
+                format!(
+                    "({}) {}: {} particles, pays {:.1}x",
+                    hand.index() + 1,
+                    rules.name(hand),
+                    counts[hand.index()],
+                    bet::odds(hand, &counts)
+                ),
+            );
+        }
+        ctx.print(0, 3 + hands, "(Space) Just watch  (Q) Main Menu");
+        ctx.print(0, 4 + hands, format!("Score: {}", self.score));
+
+        match ctx.key {
+            Some(VirtualKeyCode::Space) => self.mode = GameMode::Playing,
+            Some(VirtualKeyCode::Q) => {
+                self.stop_recording();
+                self.close_match_csv();
+                self.finish_replay();
+                self.mode = GameMode::Menu;
+            }
+            Some(key) => {
+                let pick = DIGIT_KEYS[1..].iter().position(|&digit| digit == key);
+                if let Some(hand) = pick.and_then(|i| rules.hands().nth(i)) {
+                    self.bet = Some(Bet::new(hand, &counts));
+                    self.mode = GameMode::Playing;
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn watch_finale(&mut self, ctx: &mut BTerm, winner: Hand) {
+        self.draw_arena(ctx);
+
+        self.frame_time += ctx.frame_time_ms;
+        let slow_frame = self.settings.frame_duration * finale::SLOW_MOTION;
+        let ticks = (self.frame_time / slow_frame) as usize;
+        self.frame_time %= slow_frame;
+        let skipped = matches!(
+            ctx.key,
+            Some(VirtualKeyCode::Space | VirtualKeyCode::Return | VirtualKeyCode::Escape)
+        );
+        if !self.finale.advance(ticks) || skipped {
+            self.mode = GameMode::End { winner };
+            return;
+        }
+
+        let camera = &self.camera;
+        let particles = self.finale.particles().iter();
+        render::render_particles(
+            particles.filter(|p| camera.sees(&p.position)),
+            camera.origin(),
+            &self.glyphs,
+        );
+        ctx.set_active_console(UI_LAYER);
+        self.finale.draw_highlight(ctx, camera.origin());
+        ctx.set_active_console(HUD_LAYER);
+        let winner = self.sim.params.rules.name(winner);
+        ctx.print(0, 0, format!("{} takes the match!", winner));
+        ctx.print(0, 1, "(Space) Skip");
+    }
+
     fn dead(&mut self, ctx: &mut BTerm, winner: Hand) {
-        ctx.cls();
-        ctx.print_centered(5, &format!("The winner is: {:?}!", winner));
+        clear_screen(ctx);
+        let rules = &self.sim.params.rules;
+        let name = rules.name(winner);
+        let title = match (&self.avatar, &self.infection) {
+            (Some(avatar), _) => match avatar.converted_at {
+                Some(tick) => format!("You were won over by {} at tick {}!", name, tick),
+                None => format!("You won as {}!", name),
+            },
+            (None, Some(infection)) if infection.hand == winner => {
+                format!("The {} infection took over!", name)
+            }
+            (None, Some(infection)) => format!(
+                "The {} infection was contained!",
+                rules.name(infection.hand)
+            ),
+            (None, None) => format!("The winner is: {}!", name),
+        };
+        ctx.print_color_centered(5, self.glyphs.color(winner), RGB::named(BLACK), title);
         ctx.print_centered(
             6,
-            &format!(
-                "This game lasted for {} seconds.",
-                self.elapsed_time / 1000.0
+            format!(
+                "This game lasted for {} seconds ({} ticks).",
+                self.elapsed_time / 1000.0,
+                self.sim.ticks
             ),
         );
-        ctx.print_centered(8, "(P) Play Game");
-        ctx.print_centered(9, "(Q) Quit Game");
+
+        let peaks = self.log.peaks(rules);
+        let conversions = self.log.conversions(rules);
+        let mut peak = "Peak:".to_string();
+        let mut converted = "Conversions:".to_string();
+        for hand in rules.hands() {
+            peak += &format!(" {} {}", rules.name(hand), peaks[hand.index()]);
+            converted += &format!(" {} {}", rules.name(hand), conversions[hand.index()]);
+        }
+        ctx.print_centered(8, peak);
+        ctx.print_centered(9, converted);
+        let comeback = match self.log.comeback() {
+            Some(comeback) => format!(
+                "Biggest comeback: {}, {} to {} (ticks {}-{})",
+                rules.name(comeback.hand),
+                comeback.from,
+                comeback.to,
+                comeback.since,
+                comeback.until
+            ),
+            None => "No hand came back from a loss".to_string(),
+        };
+        ctx.print_centered(10, comeback);
+
+        let seed = match self.seed {
+            Some(seed) => format!("Seed: {} (--seed to replay it)", seed),
+            None => "Saved or loaded midway, so no seed replays it".to_string(),
+        };
+        ctx.print_centered(12, seed);
+        if let Some(champion) = &self.champion {
+            ctx.print_centered(13, champion.fate());
+        }
+        if let Some(bet) = &self.bet {
+            let result = match bet.payout(winner, self.sim.ticks) {
+                0 => format!(
+                    "Your bet on {} lost. Score: {}",
+                    rules.name(bet.hand),
+                    self.score
+                ),
+                points => format!(
+                    "Your bet on {} won {} points! Score: {}",
+                    rules.name(bet.hand),
+                    points,
+                    self.score
+                ),
+            };
+            ctx.print_centered(14, result);
+        }
+        if let Some(infection) = &self.infection {
+            ctx.print_centered(
+                14,
+                format!(
+                    "Peak {} ({}%) at tick {}, {} points. Score: {}",
+                    infection.peak,
+                    infection.percent(infection.peak),
+                    infection.peak_tick,
+                    infection.points(infection.hand == winner),
+                    self.score
+                ),
+            );
+        }
+        ctx.print_centered(16, "(P) Rematch");
+        ctx.print_centered(17, "(W) Watch Last Match");
+        ctx.print_centered(18, "(M) Main Menu");
+        ctx.print_centered(19, "(Q) Quit Game");
+
+        if let Some(profile) = &self.profile {
+            ctx.print_centered(21, format!("Quickest wins for {}", profile.name));
+            for (i, entry) in profile.leaderboard.iter().enumerate() {
+                ctx.print_centered(
+                    23 + i,
+                    format!("{}. {} in {} ticks", i + 1, entry.winner, entry.ticks),
+                );
+            }
+        }
 
         if let Some(key) = ctx.key {
             match key {
-                VirtualKeyCode::P => self.restart(),
+                VirtualKeyCode::P => self.rematch(),
+                VirtualKeyCode::W => self.watch_last_match(),
+                VirtualKeyCode::M => self.mode = GameMode::Menu,
                 VirtualKeyCode::Q => ctx.quitting = true,
                 _ => {}
             }
@@ -314,26 +1988,286 @@ impl State {
     }
 }
 
+// Number keys, in the order of the digits they type
+const DIGIT_KEYS: [VirtualKeyCode; 10] = [
+    VirtualKeyCode::Key0,
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+
+// The character a key types, for the few that can go in a name
+fn key_to_char(key: VirtualKeyCode, shift: bool) -> Option<char> {
+    if let Some(digit) = DIGIT_KEYS.iter().position(|&k| k == key) {
+        return char::from_digit(digit as u32, 10);
+    }
+    match (key, shift) {
+        (VirtualKeyCode::Minus, false) => Some('-'),
+        (VirtualKeyCode::Minus, true) => Some('_'),
+        _ => {
+            let letter = (b'a' + u8::try_from(letter_to_option(key)).ok()?) as char;
+            Some(if shift {
+                letter.to_ascii_uppercase()
+            } else {
+                letter
+            })
+        }
+    }
+}
+
+fn random_seed() -> u64 {
+    RandomNumberGenerator::new().next_u64()
+}
+
+// Seconds since 1970, or 0 in the browser, where std has no clock
+fn unix_timestamp() -> u64 {
+    if cfg!(target_arch = "wasm32") {
+        return 0;
+    }
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |t| t.as_secs())
+}
+
+// First line of a CSV of populations
+fn population_header(rules: &Rules) -> String {
+    let mut header = "tick".to_string();
+    for hand in rules.hands() {
+        header += &format!(",{}", rules.name(hand)).to_lowercase();
+    }
+    header.push('\n');
+    header
+}
+
+fn population_row(ticks: u64, counts: &[usize]) -> String {
+    let mut row = ticks.to_string();
+    for count in counts {
+        row += &format!(",{}", count);
+    }
+    row.push('\n');
+    row
+}
+
+fn clear_screen(ctx: &mut BTerm) {
+    ctx.set_active_console(HUD_LAYER);
+    ctx.cls();
+    ctx.set_active_console(MAGNIFIER_LAYER);
+    ctx.cls();
+    ctx.set_active_console(UI_LAYER);
+    ctx.cls();
+    ctx.set_active_console(PARTICLE_LAYER);
+    ctx.cls();
+    ctx.set_active_console(ARENA_LAYER);
+    ctx.cls();
+    ctx.set_active_console(BACKGROUND_LAYER);
+    ctx.cls();
+}
+
 impl GameState for State {
     fn tick(&mut self, ctx: &mut BTerm) {
+        profile_scope!("frame");
+
+        #[cfg(feature = "profiling")]
+        if let Some(VirtualKeyCode::F12) = ctx.key {
+            let path = self.save_path(&format!("trace-{}.json", unix_timestamp()));
+            if let Err(err) = rock_paper_scissors::profiling::dump_trace(&path) {
+                eprintln!("Could not write {}: {}", path.display(), err);
+            }
+        }
+
+        let scale = self.display.ui_scale();
+        if scale != self.ui_scale {
+            ctx.set_active_console(HUD_LAYER);
+            ctx.set_char_size(
+                (SCREEN_WIDTH as u32) / scale,
+                (SCREEN_HEIGHT as u32) / scale,
+            );
+            self.ui_scale = scale;
+        }
+
         match self.mode {
+            GameMode::Profiles => self.select_profile(ctx),
             GameMode::Menu => self.main_menu(ctx),
+            GameMode::Betting => self.place_bet(ctx),
+            GameMode::Finale { winner } => self.watch_finale(ctx, winner),
             GameMode::End { winner } => self.dead(ctx, winner),
             GameMode::Playing => self.play(ctx),
+            GameMode::Replay => self.watch_replay(ctx),
+            GameMode::Compare => self.compare(ctx),
+            GameMode::Grid => self.watch_grid(ctx),
+            GameMode::Statistics => self.statistics(ctx),
+            GameMode::History => self.match_history(ctx),
+            GameMode::Settings => self.settings_menu(ctx),
+        }
+        // Everything queued in batches over the frame goes on screen at once
+        if let Err(err) = render_draw_buffer(ctx) {
+            eprintln!("Could not draw the frame: {}", err);
+        }
+    }
+}
+
+// Plays a match in the terminal for --terminal, and exits
+#[cfg(feature = "terminal")]
+fn play_in_terminal(config: &Config, ticks: Option<u64>, seed: Option<u64>) -> ! {
+    let seed = seed.unwrap_or_else(random_seed);
+    let rules = &config.settings.params.rules;
+    let glyphs = Glyphs::new(rules, &config.glyphs, &config.colors, &Theme::classic());
+    match terminal::run(&config.settings, &glyphs, ticks, seed) {
+        Ok(()) => process::exit(0),
+        Err(err) => {
+            eprintln!("Could not draw to the terminal: {}", err);
+            process::exit(1);
         }
     }
 }
 
+#[cfg(not(feature = "terminal"))]
+fn play_in_terminal(_: &Config, _: Option<u64>, _: Option<u64>) -> ! {
+    eprintln!("--terminal needs a build with --features terminal");
+    process::exit(2);
+}
+
+// Reports every problem in a config file, for `--check-config [path]`, and
+// returns the exit code
+#[cfg(not(target_arch = "wasm32"))]
+fn check_config(path: &Path) -> i32 {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("Could not read {}: {}", path.display(), err);
+            return 2;
+        }
+    };
+    let (config, errors) = Config::parse(&contents);
+    for err in &errors {
+        eprintln!("{}:{}: {}", path.display(), err.line, err.message);
+    }
+    let invalid = config.settings.validate().err();
+    if let Some(err) = &invalid {
+        eprintln!("{}: {}", path.display(), err);
+    }
+    // Rules that make sense can still make for dull matches, which is worth
+    // knowing but no reason to fail
+    for warning in config.settings.params.rules.warnings() {
+        eprintln!("{}: warning: {}", path.display(), warning);
+    }
+    if errors.is_empty() && invalid.is_none() {
+        println!("{}: ok", path.display());
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 fn main() -> BError {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    if let Some(path) = options.check_config {
+        let path = path.or(options.config).unwrap_or_else(Config::path);
+        process::exit(check_config(&path));
+    }
+
+    if let Some(path) = options.config.as_ref().filter(|path| !path.exists()) {
+        eprintln!("Could not find {}", path.display());
+        process::exit(2);
+    }
+    let path = options.config.unwrap_or_else(Config::path);
+    let mut config = match Config::load(&path) {
+        Ok((config, errors)) => {
+            for err in errors {
+                eprintln!("{}: {}", path.display(), err);
+            }
+            config
+        }
+        Err(err) => {
+            eprintln!("Could not read {}: {}", path.display(), err);
+            Config::default()
+        }
+    };
+    config.export.csv |= options.csv;
+    for (key, value) in &options.settings {
+        if let Err(err) = config.settings.set_from_str(key, value) {
+            eprintln!("--{}: {}", key.replace('_', "-"), err);
+            process::exit(2);
+        }
+    }
+
+    if let Err(err) = config.settings.validate() {
+        eprintln!("{}", err);
+        process::exit(2);
+    }
+    // Without a last tick, matches that never end would run forever
+    let windowless = options.simulate.is_some() || options.headless || options.terminal;
+    if windowless && config.settings.params.mutation > 0.0 && options.ticks.is_none() {
+        eprintln!("Matches with mutation never end, so they need --ticks without a window");
+        process::exit(2);
+    }
+    if let Some(matches) = options.simulate {
+        let seed = options.seed.unwrap_or_else(random_seed);
+        let jobs = options.jobs.unwrap_or_else(headless::available_jobs);
+        headless::simulate(&config.settings, matches, jobs, options.ticks, seed);
+        return Ok(());
+    }
+    if options.headless {
+        let seed = options.seed.unwrap_or_else(random_seed);
+        headless::run(&config.settings, options.ticks, seed);
+        return Ok(());
+    }
+    if options.terminal {
+        play_in_terminal(&config, options.ticks, options.seed);
+    }
+
+    open_window(config, options.seed)
+}
+
+// The page has no command line or config file to read, so it plays the
+// classic game with the settings every new player starts with
+#[cfg(target_arch = "wasm32")]
+fn main() -> BError {
+    link_resource!(WEB_FONT, "resources/font-16.png");
+    let mut config = Config::default();
+    config.display.font_size = WEB_FONT_SIZE;
+    open_window(config, None)
+}
+
+// Nor can it read the font from disk, so the one it uses is built in
+#[cfg(target_arch = "wasm32")]
+embedded_resource!(WEB_FONT, "../resources/font-16.png");
+
+add_wasm_support!();
+
+fn open_window(config: Config, fixed_seed: Option<u64>) -> BError {
+    let (font, size) = (config.display.font_file(), config.display.font_size);
     let context = BTermBuilder::new()
         .with_title("Rock Paper Scissors")
         .with_fps_cap(30.0)
         .with_dimensions(SCREEN_WIDTH, SCREEN_HEIGHT)
-        .with_tile_dimensions(64, 64)
+        .with_tile_dimensions(size, size)
         .with_resource_path("resources/")
-        .with_font("font.png", 64, 64)
-        .with_simple_console(SCREEN_WIDTH, SCREEN_HEIGHT, "font.png")
-        .with_simple_console_no_bg(SCREEN_WIDTH, SCREEN_HEIGHT, "font.png")
+        .with_font(font, size, size)
+        .with_simple_console(SCREEN_WIDTH, SCREEN_HEIGHT, font)
+        .with_simple_console_no_bg(SCREEN_WIDTH, SCREEN_HEIGHT, font)
+        .with_fancy_console(SCREEN_WIDTH, SCREEN_HEIGHT, font)
+        .with_sparse_console(SCREEN_WIDTH, SCREEN_HEIGHT, font)
+        .with_sparse_console(SCREEN_WIDTH, SCREEN_HEIGHT, font)
+        .with_simple_console_no_bg(SCREEN_WIDTH, SCREEN_HEIGHT, font)
         .build()?;
-    main_loop(context, State::new())
+    main_loop(context, State::new(config, fixed_seed))
 }
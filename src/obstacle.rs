@@ -0,0 +1,340 @@
+use crate::physics::{Particle, Real, Vec2f};
+use crate::portal::{self, Portal};
+use crate::zone::{self, Zone};
+
+// The outline of an obstacle where the map puts it, in arena coordinates
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    // A box from its top left corner, level with the arena
+    Rect {
+        x: Real,
+        y: Real,
+        width: Real,
+        height: Real,
+    },
+    Circle {
+        center: Vec2f,
+        radius: Real,
+    },
+    // A bar spinning around its middle, level with the arena at first and a
+    // full turn every `period` ticks, clockwise on screen unless negative
+    Bar {
+        center: Vec2f,
+        length: Real,
+        thickness: Real,
+        period: Real,
+    },
+}
+
+// Back and forth along `reach` either way from where the map puts an
+// obstacle, once every `period` ticks
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sway {
+    pub reach: Vec2f,
+    pub period: Real,
+}
+
+// Where a moving obstacle has got to at some tick: how far it has swayed and
+// turned from where the map puts it, and how fast it is doing both
+#[derive(Copy, Clone, Debug, Default)]
+struct Pose {
+    offset: Vec2f,
+    velocity: Vec2f,
+    angle: Real,
+    spin: Real,
+}
+
+// Something particles bounce off inside the arena, which may move
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Obstacle {
+    pub shape: Shape,
+    pub sway: Option<Sway>,
+}
+
+impl Obstacle {
+    pub fn contains(&self, position: &Vec2f, ticks: u64) -> bool {
+        let pose = self.pose(ticks);
+        let position = self.unmoved(&pose, position);
+        match self.shape {
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => (x..x + width).contains(&position.x) && (y..y + height).contains(&position.y),
+            Shape::Circle { center, radius } => center.distance(&position) < radius,
+            Shape::Bar {
+                center,
+                length,
+                thickness,
+                ..
+            } => {
+                (position.x - center.x).abs() < length / 2.0
+                    && (position.y - center.y).abs() < thickness / 2.0
+            }
+        }
+    }
+
+    // Pushes `particle` back out if it overlaps the obstacle as it is after
+    // `ticks` ticks, and turns it around if it was heading further in. Like
+    // the walls, the obstacle takes none of its speed, but one that moves
+    // throws it off as fast again as it hit it, and carries it along.
+    pub fn bounce(&self, particle: &mut Particle, ticks: u64) {
+        let pose = self.pose(ticks);
+        let local = self.unmoved(&pose, &particle.position);
+        let Some((surface, normal)) = self.surface(&local) else {
+            return;
+        };
+        let (surface, normal) = (self.moved(&pose, surface), rotate(normal, pose.angle));
+        let reach = particle.radius;
+        let clearance = particle.position.minus(surface).scalar_product(&normal);
+        if clearance >= reach {
+            return;
+        }
+        particle.position = surface.plus(normal.product(reach));
+
+        // How fast the edge it hit is going where it hit it
+        let arm = surface.minus(self.pivot().plus(pose.offset));
+        let edge = pose.velocity.plus(Vec2f {
+            x: -pose.spin * arm.y,
+            y: pose.spin * arm.x,
+        });
+        let heading = particle.velocity.minus(edge).scalar_product(&normal);
+        if heading < 0.0 {
+            particle.velocity = particle.velocity.minus(normal.product(2.0 * heading));
+        }
+    }
+
+    // The nearest point on the edge of the shape to `position`, and which
+    // way is out from there, where the map puts it. None from right in the
+    // middle of a circle, where every way is as good.
+    fn surface(&self, position: &Vec2f) -> Option<(Vec2f, Vec2f)> {
+        match self.shape {
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => rect_surface(position, x, y, width, height),
+            Shape::Circle { center, radius } => {
+                let outward = position.minus(center);
+                let distance = outward.norm();
+                if distance == 0.0 {
+                    return None;
+                }
+                let normal = outward.product(1.0 / distance);
+                Some((center.plus(normal.product(radius)), normal))
+            }
+            Shape::Bar {
+                center,
+                length,
+                thickness,
+                ..
+            } => rect_surface(
+                position,
+                center.x - length / 2.0,
+                center.y - thickness / 2.0,
+                length,
+                thickness,
+            ),
+        }
+    }
+
+    fn pose(&self, ticks: u64) -> Pose {
+        let mut pose = Pose::default();
+        let tau = std::f64::consts::TAU as Real;
+        if let Some(sway) = self.sway {
+            let phase = tau * ticks as Real / sway.period;
+            pose.offset = sway.reach.product(phase.sin());
+            pose.velocity = sway.reach.product(tau / sway.period * phase.cos());
+        }
+        if let Shape::Bar { period, .. } = self.shape {
+            pose.spin = tau / period;
+            pose.angle = pose.spin * ticks as Real;
+        }
+        pose
+    }
+
+    // The point the shape turns around, where the map puts it
+    fn pivot(&self) -> Vec2f {
+        match self.shape {
+            Shape::Rect { x, y, .. } => Vec2f { x, y },
+            Shape::Circle { center, .. } | Shape::Bar { center, .. } => center,
+        }
+    }
+
+    // Where `position` is to the shape, as if it were still where the map
+    // puts it
+    fn unmoved(&self, pose: &Pose, position: &Vec2f) -> Vec2f {
+        let pivot = self.pivot();
+        rotate(position.minus(pose.offset).minus(pivot), -pose.angle).plus(pivot)
+    }
+
+    // Back from `unmoved`
+    fn moved(&self, pose: &Pose, position: Vec2f) -> Vec2f {
+        let pivot = self.pivot();
+        rotate(position.minus(pivot), pose.angle)
+            .plus(pivot)
+            .plus(pose.offset)
+    }
+}
+
+fn rotate(v: Vec2f, angle: Real) -> Vec2f {
+    if angle == 0.0 {
+        return v;
+    }
+    let (sin, cos) = angle.sin_cos();
+    Vec2f {
+        x: v.x * cos - v.y * sin,
+        y: v.x * sin + v.y * cos,
+    }
+}
+
+fn rect_surface(
+    position: &Vec2f,
+    x: Real,
+    y: Real,
+    width: Real,
+    height: Real,
+) -> Option<(Vec2f, Vec2f)> {
+    let nearest = Vec2f {
+        x: position.x.clamp(x, x + width),
+        y: position.y.clamp(y, y + height),
+    };
+    let outside = position.minus(nearest);
+    let distance = outside.norm();
+    if distance > 0.0 {
+        return Some((nearest, outside.product(1.0 / distance)));
+    }
+    // Inside: out through the nearest side
+    let sides = [
+        (position.x - x, Vec2f { x: -1.0, y: 0.0 }),
+        (x + width - position.x, Vec2f { x: 1.0, y: 0.0 }),
+        (position.y - y, Vec2f { x: 0.0, y: -1.0 }),
+        (y + height - position.y, Vec2f { x: 0.0, y: 1.0 }),
+    ];
+    let (depth, normal) = sides
+        .into_iter()
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap();
+    Some((position.plus(normal.product(depth)), normal))
+}
+
+// What a map file puts in the arena
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Map {
+    pub obstacles: Vec<Obstacle>,
+    pub zones: Vec<Zone>,
+    pub portals: Vec<Portal>,
+}
+
+// Reads a map: the obstacles, zones and portals of an arena, one per line, as
+//
+//   rect <x> <y> <width> <height>
+//   circle <x> <y> <radius>
+//   bar <x> <y> <length> <thickness> <period>
+//   zone <name> <hand> <speed> <x> <y> <width> <height>
+//   portal <x> <y> <x> <y>
+//
+// where an obstacle can end on `sway <x> <y> <period>` to move back and forth
+// that far either way along each axis, once every `period` ticks.
+// Blank lines and lines starting with `#` are skipped. Says what was wrong,
+// and on which line, if it can't.
+pub fn parse_map(contents: &str) -> Result<Map, String> {
+    let mut map = Map::default();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let read = if let Some(zone) = line.strip_prefix("zone ") {
+            zone::parse_zone(zone).map(|zone| map.zones.push(zone))
+        } else if let Some(portal) = line.strip_prefix("portal ") {
+            portal::parse_portal(portal).map(|portal| map.portals.push(portal))
+        } else {
+            parse_obstacle(line).map(|obstacle| map.obstacles.push(obstacle))
+        };
+        read.ok_or_else(|| format!("line {} makes no sense: {}", i + 1, line))?;
+    }
+    Ok(map)
+}
+
+// One line of a map, or None if it isn't an obstacle
+pub fn parse_obstacle(line: &str) -> Option<Obstacle> {
+    let (line, sway) = match line.split_once(" sway ") {
+        Some((line, sway)) => (line, Some(parse_sway(sway)?)),
+        None => (line, None),
+    };
+    let mut words = line.split_whitespace();
+    let kind = words.next()?;
+    let numbers: Vec<Real> = words.map(|word| word.parse().ok()).collect::<Option<_>>()?;
+    let shape = match (kind, numbers.as_slice()) {
+        ("rect", &[x, y, width, height]) if width > 0.0 && height > 0.0 => Shape::Rect {
+            x,
+            y,
+            width,
+            height,
+        },
+        ("circle", &[x, y, radius]) if radius > 0.0 => Shape::Circle {
+            center: Vec2f { x, y },
+            radius,
+        },
+        ("bar", &[x, y, length, thickness, period])
+            if length > 0.0 && thickness > 0.0 && period != 0.0 =>
+        {
+            Shape::Bar {
+                center: Vec2f { x, y },
+                length,
+                thickness,
+                period,
+            }
+        }
+        _ => return None,
+    };
+    Some(Obstacle { shape, sway })
+}
+
+fn parse_sway(text: &str) -> Option<Sway> {
+    let numbers: Vec<Real> = text
+        .split_whitespace()
+        .map(|word| word.parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers.as_slice() {
+        &[x, y, period] if period > 0.0 => Some(Sway {
+            reach: Vec2f { x, y },
+            period,
+        }),
+        _ => None,
+    }
+}
+
+// The line `parse_obstacle` reads back as `obstacle`
+pub fn obstacle_line(obstacle: &Obstacle) -> String {
+    let shape = match obstacle.shape {
+        Shape::Rect {
+            x,
+            y,
+            width,
+            height,
+        } => format!("rect {} {} {} {}", x, y, width, height),
+        Shape::Circle { center, radius } => {
+            format!("circle {} {} {}", center.x, center.y, radius)
+        }
+        Shape::Bar {
+            center,
+            length,
+            thickness,
+            period,
+        } => format!(
+            "bar {} {} {} {} {}",
+            center.x, center.y, length, thickness, period
+        ),
+    };
+    match obstacle.sway {
+        Some(sway) => format!(
+            "{} sway {} {} {}",
+            shape, sway.reach.x, sway.reach.y, sway.period
+        ),
+        None => shape,
+    }
+}
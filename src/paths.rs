@@ -0,0 +1,61 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Directory the game keeps its files in, under the platform's own
+const APP_DIR: &str = "rock-paper-scissors";
+
+// Where saves go: profiles, replays, recordings and traces.
+//
+//   Linux:   $XDG_DATA_HOME or ~/.local/share
+//   macOS:   ~/Library/Application Support
+//   Windows: %APPDATA%
+//
+// Falls back to the working directory if none of those can be found.
+pub fn data_dir() -> PathBuf {
+    let base = if cfg!(windows) {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        env_dir("XDG_DATA_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".local/share")))
+    };
+    base.map_or_else(|| PathBuf::from("."), |base| base.join(APP_DIR))
+}
+
+// Where the config file is looked for.
+//
+//   Linux:   $XDG_CONFIG_HOME or ~/.config
+//   macOS:   ~/Library/Application Support
+//   Windows: %APPDATA%
+pub fn config_dir() -> PathBuf {
+    if cfg!(windows) || cfg!(target_os = "macos") {
+        return data_dir();
+    }
+    let base =
+        env_dir("XDG_CONFIG_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".config")));
+    base.map_or_else(|| PathBuf::from("."), |base| base.join(APP_DIR))
+}
+
+// Relative paths in these variables are ignored, as the XDG spec asks
+fn env_dir(name: &str) -> Option<PathBuf> {
+    let dir = PathBuf::from(env::var_os(name)?);
+    dir.is_absolute().then_some(dir)
+}
+
+// Shows a directory in the platform's file manager, creating it first
+pub fn open_in_file_manager(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let opener = if cfg!(windows) {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    // The openers hand the directory over and return right away
+    Command::new(opener).arg(dir).status()?;
+    Ok(())
+}
@@ -0,0 +1,243 @@
+use bracket_lib::prelude::RandomNumberGenerator;
+
+use crate::pickup::Effect;
+use crate::rules::Hand;
+use crate::simulation::{Arena, Params};
+
+// Scalar type for all simulation math. Single precision halves the memory
+// traffic of huge swarms; double stays the default for accuracy.
+#[cfg(not(feature = "f32"))]
+pub type Real = f64;
+#[cfg(feature = "f32")]
+pub type Real = f32;
+
+// Radius of particles unless the rules say otherwise
+pub const PARTICLE_RADIUS: Real = 1.5;
+
+// Mass of a particle of `radius`: a flat disc, weighed against one of the
+// default size
+pub fn mass_of(radius: Real) -> Real {
+    (radius / PARTICLE_RADIUS).powi(2)
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vec2f {
+    pub x: Real,
+    pub y: Real,
+}
+
+impl Vec2f {
+    pub fn scalar_product(&self, other: &Vec2f) -> Real {
+        (self.x * other.x) + (self.y * other.y)
+    }
+
+    pub fn product(&self, other: Real) -> Vec2f {
+        Vec2f {
+            x: self.x * other,
+            y: self.y * other,
+        }
+    }
+
+    pub fn minus(&self, other: Vec2f) -> Vec2f {
+        Vec2f {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+
+    pub fn plus(&self, other: Vec2f) -> Vec2f {
+        Vec2f {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+
+    pub fn distance(&self, other: &Vec2f) -> Real {
+        self.minus(*other).norm()
+    }
+
+    pub fn norm(&self) -> Real {
+        self.scalar_product(self).sqrt()
+    }
+
+    // The point `t` of the way from here to `other`
+    pub fn lerp(&self, other: Vec2f, t: Real) -> Vec2f {
+        self.plus(other.minus(*self).product(t))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Particle {
+    pub position: Vec2f,
+    pub velocity: Vec2f,
+    pub hand: Hand,
+    // Ticks left before this particle can be converted again
+    pub immunity: u32,
+    // Hits taken from hands that beat it since it last changed hands
+    pub damage: u32,
+    // The power it took from a pickup, while it lasts
+    pub effect: Option<Effect>,
+    // Ticks left of being drawn lit up for having been won over, and ringed
+    // for having won one over. Only for show, so saves leave them out.
+    pub flash: u32,
+    pub ring: u32,
+    pub radius: Real,
+    pub mass: Real,
+}
+
+impl Particle {
+    // A particle anywhere in the arena, moving at most `params.max_velocity`
+    // along each axis. With a size spread, bigger particles start out slower
+    // and smaller ones faster, in proportion.
+    pub fn random(random: &mut RandomNumberGenerator, arena: &Arena, params: &Params) -> Self {
+        let max_velocity = params.max_velocity;
+        // The hand is drawn as an i32, as it always was, so a seed keeps
+        // dealing the same classic match
+        let hands = params.rules.len() as i32;
+        let mut particle = Particle {
+            position: Vec2f {
+                x: random.range(0.0, arena.width),
+                y: random.range(0.0, arena.height),
+            },
+            velocity: Vec2f {
+                x: random.range(-max_velocity, max_velocity),
+                y: random.range(-max_velocity, max_velocity),
+            },
+            hand: Hand::from_index(random.range(0, hands) as usize).unwrap(),
+            immunity: 0,
+            damage: 0,
+            flash: 0,
+            ring: 0,
+            effect: None,
+            radius: params.radius,
+            mass: mass_of(params.radius),
+        };
+        // Drawn last, and only when sizes vary, for the same reason
+        if params.size_spread > 0.0 {
+            let scale = random.range(1.0 - params.size_spread, 1.0 + params.size_spread);
+            particle.radius *= scale;
+            particle.mass = mass_of(particle.radius);
+            particle.velocity = particle.velocity.product(1.0 / scale);
+        }
+        particle
+    }
+
+    // Whether a hand that beats it can hit it right now: not while it is
+    // immune from its last conversion or shielded
+    pub fn can_be_hit(&self) -> bool {
+        self.immunity == 0 && !self.effect.is_some_and(|effect| effect.power.shields())
+    }
+
+    pub fn check_wall_collision(&mut self, arena: &Arena) {
+        if arena.wraps() {
+            self.position = arena.wrap(self.position);
+            return;
+        }
+        let (min, max) = arena.walls();
+        if self.position.x < min.x {
+            self.position.x = 2.0 * min.x - self.position.x;
+            self.velocity.x = -self.velocity.x;
+        } else if self.position.x > max.x {
+            self.position.x = 2.0 * max.x - self.position.x;
+            self.velocity.x = -self.velocity.x;
+        }
+
+        if self.position.y < min.y {
+            self.position.y = 2.0 * min.y - self.position.y;
+            self.velocity.y = -self.velocity.y;
+        } else if self.position.y > max.y {
+            self.position.y = 2.0 * max.y - self.position.y;
+            self.velocity.y = -self.velocity.y;
+        }
+    }
+
+    // Furthest this particle can end up from where it is after one tick.
+    // Bouncing off a wall only shortens the trip, unless a collision left it
+    // outside the arena, or the walls closed in on it, in which case it can be
+    // thrown back by the overshoot.
+    pub fn reach(&self, arena: &Arena) -> Real {
+        let (min, max) = arena.walls();
+        let outside_x = (min.x - self.position.x)
+            .max(self.position.x - max.x)
+            .max(0.0);
+        let outside_y = (min.y - self.position.y)
+            .max(self.position.y - max.y)
+            .max(0.0);
+        self.velocity.norm() + 2.0 * (outside_x + outside_y)
+    }
+
+    pub fn collides_width(&self, other: &Particle) -> bool {
+        self.position.distance(&other.position) < self.radius + other.radius
+    }
+
+    // How far through the tick this particle first touched `other`, from 0
+    // to 1, had both gone in a straight line from `start` and `other_start`
+    // to where they are now. None if they never touched, or already did at
+    // the start of it.
+    pub fn time_of_impact(
+        &self,
+        other: &Particle,
+        start: Vec2f,
+        other_start: Vec2f,
+    ) -> Option<Real> {
+        let gap = other_start.minus(start);
+        let closing = other
+            .position
+            .minus(other_start)
+            .minus(self.position.minus(start));
+        let contact = self.radius + other.radius;
+
+        // |gap + closing * t| = contact, a quadratic in t
+        let a = closing.scalar_product(&closing);
+        let b = 2.0 * gap.scalar_product(&closing);
+        let c = gap.scalar_product(&gap) - contact * contact;
+        if c <= 0.0 || a == 0.0 {
+            return None;
+        }
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let t = (-b - discriminant.sqrt()) / (2.0 * a);
+        (0.0..=1.0).contains(&t).then_some(t)
+    }
+
+    pub fn velocity_projection(&self, other: &Particle) -> Vec2f {
+        let line = other.position.minus(self.position);
+        line.product(self.velocity.scalar_product(&line) / line.norm().powi(2))
+    }
+
+    pub fn update_position(&mut self) {
+        self.position = self.position.plus(self.velocity);
+    }
+
+    // Momentum is kept, and only the velocities along the line between the
+    // centres change. With a restitution of 1 the collision is perfectly
+    // elastic: two particles of the same mass swap those velocities, and a
+    // light one bounces off a heavy one and barely moves it. Below 1 some of
+    // the energy is lost, down to 0, where both carry on together along it.
+    pub fn collide(&mut self, other: &mut Particle, restitution: Real) {
+        // Changes in velocity
+        let v_lr = self.velocity_projection(other);
+        let v_rl = other.velocity_projection(self);
+        let total = self.mass + other.mass;
+        let (l_share, r_share) = (other.mass / total, self.mass / total);
+
+        // v_l' = v_l + (1 + e) m_r / (m_l + m_r) (v_rl - v_lr), and the other
+        // way around for the right one
+        let exchange = v_rl.minus(v_lr).product(1.0 + restitution);
+        self.velocity = self.velocity.plus(exchange.product(l_share));
+        other.velocity = other.velocity.minus(exchange.product(r_share));
+
+        // Displace particles to leave collision condition, the lighter one
+        // further
+        let distance = other.position.distance(&self.position);
+        let overlap = self.radius + other.radius - distance;
+
+        let l_to_r = other.position.minus(self.position);
+        let direction = l_to_r.product(1.0 / l_to_r.norm());
+
+        self.position = self.position.minus(direction.product(overlap * l_share));
+        other.position = other.position.plus(direction.product(overlap * r_share));
+    }
+}
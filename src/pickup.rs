@@ -0,0 +1,104 @@
+use crate::physics::{Particle, Real, Vec2f};
+
+// How close a particle has to come to a pickup to take it, past its radius
+pub const PICKUP_RADIUS: Real = 1.0;
+// Most pickups waiting on the field at once
+pub const MAX_PICKUPS: usize = 4;
+// How much faster a speed boost has a particle go
+const SPEED_BOOST: Real = 1.6;
+// How far a convert aura reaches from the particle that takes it
+const AURA_RADIUS: Real = 8.0;
+
+// What a pickup does for the particle that takes it. A new power takes a
+// variant here, a place in POWERS and its arms in the methods below; the
+// simulation and the renderer go by those.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Power {
+    // Goes faster for a while
+    Speed,
+    // Can't be hit for a while
+    Shield,
+    // Wins over everything it beats around it there and then
+    Aura,
+}
+
+// Every power, each as likely to turn up as the others
+pub const POWERS: [Power; 3] = [Power::Speed, Power::Shield, Power::Aura];
+
+impl Power {
+    // What saves call it
+    pub fn name(self) -> &'static str {
+        match self {
+            Power::Speed => "speed",
+            Power::Shield => "shield",
+            Power::Aura => "aura",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Power> {
+        POWERS.into_iter().find(|power| power.name() == name)
+    }
+
+    pub fn glyph(self) -> char {
+        match self {
+            Power::Speed => '»',
+            Power::Shield => '♦',
+            Power::Aura => '☼',
+        }
+    }
+
+    pub fn color(self) -> (u8, u8, u8) {
+        match self {
+            Power::Speed => (90, 220, 250),
+            Power::Shield => (250, 250, 250),
+            Power::Aura => (250, 200, 60),
+        }
+    }
+
+    // Ticks it lasts once taken, or 0 if it is over there and then
+    pub fn duration(self) -> u32 {
+        match self {
+            Power::Speed => 100,
+            Power::Shield => 80,
+            Power::Aura => 0,
+        }
+    }
+
+    // Whether the particle wearing it can be hit
+    pub fn shields(self) -> bool {
+        self == Power::Shield
+    }
+
+    // How far around the particle taking it everything it beats is won over
+    pub fn aura(self) -> Option<Real> {
+        (self == Power::Aura).then_some(AURA_RADIUS)
+    }
+
+    // Takes effect on the particle that took it
+    pub fn take(self, particle: &mut Particle) {
+        if self == Power::Speed {
+            particle.velocity = particle.velocity.product(SPEED_BOOST);
+        }
+    }
+
+    // Undoes what `take` did, once it runs out
+    pub fn wear_off(self, particle: &mut Particle) {
+        if self == Power::Speed {
+            particle.velocity = particle.velocity.product(1.0 / SPEED_BOOST);
+        }
+    }
+}
+
+// A power waiting on the field for a particle to take it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pickup {
+    pub position: Vec2f,
+    pub power: Power,
+}
+
+// A power a particle took, and the ticks it has left
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Effect {
+    pub power: Power,
+    pub ticks: u32,
+}
@@ -0,0 +1,247 @@
+use std::ops::{Index, IndexMut};
+
+// Slot storage with generational handles. Removing an entry frees its slot for
+// reuse without shifting any other entry, and bumps the slot's generation so
+// stale handles stop resolving instead of silently pointing at a newcomer.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Handle {
+    index: u32,
+    generation: u32,
+}
+
+impl Handle {
+    // The slot it points at, which tells apart entries alive at the same time
+    pub fn index(self) -> u32 {
+        self.index
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Pool<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> Pool<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Pool {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    // Makes room for `additional` more entries than there are free slots
+    pub fn reserve(&mut self, additional: usize) {
+        self.slots
+            .reserve(additional.saturating_sub(self.free.len()));
+    }
+
+    pub fn insert(&mut self, value: T) -> Handle {
+        self.len += 1;
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.value = Some(value);
+                Handle {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                Handle {
+                    index: (self.slots.len() - 1) as u32,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        self.slots
+            .get(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        self.slots
+            .get_mut(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    pub fn get_pair_mut(&mut self, a: Handle, b: Handle) -> Option<(&mut T, &mut T)> {
+        if a.index == b.index {
+            return None;
+        }
+
+        let (low, high, swapped) = if a.index < b.index {
+            (a, b, false)
+        } else {
+            (b, a, true)
+        };
+        let (head, tail) = self.slots.split_at_mut(high.index as usize);
+        let low_slot = &mut head[low.index as usize];
+        let high_slot = &mut tail[0];
+        if low_slot.generation != low.generation || high_slot.generation != high.generation {
+            return None;
+        }
+
+        let low_value = low_slot.value.as_mut()?;
+        let high_value = high_slot.value.as_mut()?;
+        if swapped {
+            Some((high_value, low_value))
+        } else {
+            Some((low_value, high_value))
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if slot.value.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(index as u32);
+            }
+        }
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Handle, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.value.as_ref().map(|value| {
+                (
+                    Handle {
+                        index: index as u32,
+                        generation: slot.generation,
+                    },
+                    value,
+                )
+            })
+        })
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots.iter_mut().filter_map(|slot| slot.value.as_mut())
+    }
+
+    pub fn handles(&self) -> Vec<Handle> {
+        self.iter().map(|(handle, _)| handle).collect()
+    }
+}
+
+impl<T> Index<Handle> for Pool<T> {
+    type Output = T;
+
+    fn index(&self, handle: Handle) -> &T {
+        self.get(handle).expect("stale particle handle")
+    }
+}
+
+impl<T> IndexMut<Handle> for Pool<T> {
+    fn index_mut(&mut self, handle: Handle) -> &mut T {
+        self.get_mut(handle).expect("stale particle handle")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_handle_is_rejected_after_reuse() {
+        let mut pool = Pool::with_capacity(2);
+        let old = pool.insert("old");
+        assert_eq!(pool.remove(old), Some("old"));
+
+        let new = pool.insert("new");
+        assert_eq!(new.index(), old.index());
+        assert_ne!(new, old);
+        assert_eq!(pool.get(old), None);
+        assert_eq!(pool.get_mut(old), None);
+        assert_eq!(pool.remove(old), None);
+        assert_eq!(pool.get(new), Some(&"new"));
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn generation_wraps() {
+        let mut pool = Pool::with_capacity(1);
+        let first = pool.insert(0);
+        pool.remove(first);
+        pool.slots[0].generation = u32::MAX;
+
+        let last = pool.insert(1);
+        assert_eq!(last.generation, u32::MAX);
+        pool.remove(last);
+        let wrapped = pool.insert(2);
+        assert_eq!(wrapped.generation, 0);
+        assert_eq!(pool.get(last), None);
+        assert_eq!(pool.get(wrapped), Some(&2));
+    }
+
+    #[test]
+    fn handles_order_by_index_then_generation() {
+        let handle = |index, generation| Handle { index, generation };
+        let mut handles = vec![handle(1, 0), handle(0, 2), handle(1, 1), handle(0, 5)];
+        handles.sort();
+        assert_eq!(
+            handles,
+            [handle(0, 2), handle(0, 5), handle(1, 0), handle(1, 1)]
+        );
+    }
+
+    #[test]
+    fn iteration_skips_free_slots() {
+        let mut pool = Pool::with_capacity(4);
+        let handles: Vec<Handle> = (0..4).map(|value| pool.insert(value)).collect();
+        pool.remove(handles[1]);
+        pool.remove(handles[3]);
+
+        let live: Vec<(Handle, i32)> = pool
+            .iter()
+            .map(|(handle, &value)| (handle, value))
+            .collect();
+        assert_eq!(live, [(handles[0], 0), (handles[2], 2)]);
+        assert_eq!(pool.handles(), [handles[0], handles[2]]);
+        assert_eq!(pool.values().copied().collect::<Vec<_>>(), [0, 2]);
+        assert_eq!(pool.values_mut().count(), 2);
+    }
+}
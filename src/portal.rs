@@ -0,0 +1,66 @@
+use crate::physics::{Real, Vec2f};
+use crate::simulation::Arena;
+
+// How close a particle's centre has to come to a portal to go through it,
+// past its own radius
+pub const PORTAL_RADIUS: Real = 1.0;
+
+// Two ends of a portal: a particle that moves into one comes out of the other
+// just as far from it, going the same way just as fast
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Portal {
+    pub ends: [Vec2f; 2],
+}
+
+impl Portal {
+    // Where a particle of `radius` that moved from `start` to `end` this tick
+    // comes out, if it went into either end on the way. One that starts the
+    // tick in an end, like one that just came out of it, has to leave it
+    // before it can go back in.
+    pub fn exit(&self, start: Vec2f, end: Vec2f, radius: Real, arena: &Arena) -> Option<Vec2f> {
+        let reach = PORTAL_RADIUS + radius;
+        let moved = arena.offset(start, end);
+        for (i, &entrance) in self.ends.iter().enumerate() {
+            let to_entrance = arena.offset(start, entrance);
+            if to_entrance.norm() < reach {
+                continue;
+            }
+            // The nearest the particle came to the entrance on the way
+            let length = moved.scalar_product(&moved);
+            let t = if length == 0.0 {
+                0.0
+            } else {
+                (to_entrance.scalar_product(&moved) / length).clamp(0.0, 1.0)
+            };
+            if to_entrance.minus(moved.product(t)).norm() < reach {
+                let past = arena.offset(entrance, end);
+                return Some(self.ends[1 - i].plus(past));
+            }
+        }
+        None
+    }
+}
+
+// One portal, after the `portal` that starts its line of a map, as
+//
+//   <x> <y> <x> <y>
+//
+// for where each end is. None if it isn't a portal.
+pub fn parse_portal(line: &str) -> Option<Portal> {
+    let numbers: Vec<Real> = line
+        .split_whitespace()
+        .map(|word| word.parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers.as_slice() {
+        &[x1, y1, x2, y2] if (x1, y1) != (x2, y2) => Some(Portal {
+            ends: [Vec2f { x: x1, y: y1 }, Vec2f { x: x2, y: y2 }],
+        }),
+        _ => None,
+    }
+}
+
+// The line `parse_portal` reads back as `portal`
+pub fn portal_line(portal: &Portal) -> String {
+    let [a, b] = portal.ends;
+    format!("{} {} {} {}", a.x, a.y, b.x, b.y)
+}
@@ -0,0 +1,83 @@
+use std::collections::VecDeque;
+
+use bracket_lib::prelude::*;
+use rock_paper_scissors::{Hand, Particle, Real, Rules};
+
+// How far back the population trend is measured, in ticks
+const TREND_TICKS: usize = 60;
+// How far ahead collisions are looked for, in ticks
+const COLLISION_HORIZON: Real = 20.0;
+// More lines than this only clutter the view
+const MAX_COLLISION_LINES: usize = 40;
+const LINE_COLOR: (u8, u8, u8) = (150, 190, 190);
+
+// The hand most likely to be wiped out next: the scarcest one of those whose
+// numbers went down over the last few seconds
+pub fn endangered(history: &VecDeque<Vec<usize>>) -> Option<Hand> {
+    let now = history.back()?;
+    let then = &history[history.len().saturating_sub(TREND_TICKS + 1)];
+    (0..now.len())
+        .filter(|&i| now[i] > 0 && now[i] < then[i])
+        .min_by_key(|&i| now[i])
+        .and_then(Hand::from_index)
+}
+
+// Pairs of particles that will touch within the next few ticks if they keep
+// their course, soonest first. Only pairs where one hand beats the other are
+// listed, as those are the ones that change the scores.
+pub fn upcoming_collisions<'a>(
+    particles: &[&'a Particle],
+    rules: &Rules,
+) -> Vec<(&'a Particle, &'a Particle)> {
+    let mut collisions = Vec::new();
+    for (i, lhs) in particles.iter().enumerate() {
+        for rhs in &particles[i + 1..] {
+            if !rules.beats(lhs.hand, rhs.hand) && !rules.beats(rhs.hand, lhs.hand) {
+                continue;
+            }
+            if let Some(time) = time_to_contact(lhs, rhs) {
+                collisions.push((time, *lhs, *rhs));
+            }
+        }
+    }
+    collisions.sort_by(|a, b| a.0.total_cmp(&b.0));
+    collisions
+        .into_iter()
+        .take(MAX_COLLISION_LINES)
+        .map(|(_, lhs, rhs)| (lhs, rhs))
+        .collect()
+}
+
+// Ticks until two particles moving in a straight line come within touching
+// distance, if that happens within the horizon and they aren't touching yet
+fn time_to_contact(lhs: &Particle, rhs: &Particle) -> Option<Real> {
+    let offset = rhs.position.minus(lhs.position);
+    let closing = rhs.velocity.minus(lhs.velocity);
+    let speed = closing.scalar_product(&closing);
+    if speed == 0.0 {
+        return None;
+    }
+    // Solves |offset + closing * t| = r1 + r2 for the first t
+    let contact = lhs.radius + rhs.radius;
+    let b = offset.scalar_product(&closing);
+    let c = offset.scalar_product(&offset) - contact * contact;
+    let discriminant = b * b - speed * c;
+    if c <= 0.0 || b >= 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let time = (-b - discriminant.sqrt()) / speed;
+    (time <= COLLISION_HORIZON).then_some(time)
+}
+
+// Draws a faint dotted line between each pair on the active console, with
+// positions relative to `origin` on screen
+pub fn draw_collision_lines(ctx: &mut BTerm, collisions: &[(&Particle, &Particle)], origin: Point) {
+    let color = RGB::from(LINE_COLOR);
+    for (lhs, rhs) in collisions {
+        let start = origin + Point::new(lhs.position.x as i32, lhs.position.y as i32);
+        let end = origin + Point::new(rhs.position.x as i32, rhs.position.y as i32);
+        for point in line2d(LineAlg::Bresenham, start, end) {
+            ctx.set(point.x, point.y, color, RGB::named(BLACK), to_cp437('.'));
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::paths;
+
+// Every profile gets a directory of its own under here, in the data
+// directory
+const PROFILES_DIR: &str = "profiles";
+const PROFILE_FILE: &str = "profile.txt";
+pub const MAX_NAME_LEN: usize = 16;
+// Quickest wins kept on a profile's leaderboard
+const LEADERBOARD_LEN: usize = 10;
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub ticks: u64,
+    // Name of the winning hand
+    pub winner: String,
+}
+
+// A named player. Anything the game saves for them (replays, recordings,
+// traces) goes in their directory, next to a profile file that holds their
+// settings and leaderboard:
+//
+//   seasonal-themes <on|off>
+//   best <ticks> <hand>
+//
+// with one `best` line per entry, quickest first. Unknown lines are skipped,
+// so older builds can read files written by newer ones.
+pub struct Profile {
+    pub name: String,
+    pub leaderboard: Vec<Entry>,
+    // Whether the arena follows the calendar's themes
+    pub seasonal_themes: bool,
+}
+
+impl Profile {
+    // Names of the profiles already on disk, in alphabetical order
+    pub fn list() -> Vec<String> {
+        let Ok(entries) = fs::read_dir(paths::data_dir().join(PROFILES_DIR)) else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join(PROFILE_FILE).is_file())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= MAX_NAME_LEN
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    }
+
+    // Loads a profile, creating it if there is none by that name yet
+    pub fn open(name: &str) -> io::Result<Self> {
+        let mut profile = Profile {
+            name: name.to_string(),
+            leaderboard: Vec::new(),
+            seasonal_themes: true,
+        };
+        match fs::read_to_string(profile.file()) {
+            Ok(contents) => profile.parse(&contents),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                fs::create_dir_all(profile.dir())?;
+                profile.save()?;
+            }
+            Err(err) => return Err(err),
+        }
+        Ok(profile)
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        paths::data_dir().join(PROFILES_DIR).join(&self.name)
+    }
+
+    // Where a file the game saves for this player goes
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.dir().join(file)
+    }
+
+    fn file(&self) -> PathBuf {
+        self.path(PROFILE_FILE)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let themes = if self.seasonal_themes { "on" } else { "off" };
+        let mut contents = format!("seasonal-themes {}\n", themes);
+        for entry in &self.leaderboard {
+            contents += &format!("best {} {}\n", entry.ticks, entry.winner);
+        }
+        fs::write(self.file(), contents)
+    }
+
+    // Adds a finished match to the leaderboard if it was quick enough, and
+    // returns its place
+    pub fn record(&mut self, entry: Entry) -> Option<usize> {
+        let place = self
+            .leaderboard
+            .partition_point(|best| best.ticks <= entry.ticks);
+        if place >= LEADERBOARD_LEN {
+            return None;
+        }
+        self.leaderboard.insert(place, entry);
+        self.leaderboard.truncate(LEADERBOARD_LEN);
+        Some(place)
+    }
+
+    fn parse(&mut self, contents: &str) {
+        for line in contents.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("best") => self.leaderboard.extend(parse_entry(words)),
+                Some("seasonal-themes") => match words.next() {
+                    Some("on") => self.seasonal_themes = true,
+                    Some("off") => self.seasonal_themes = false,
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        self.leaderboard.sort_by_key(|entry| entry.ticks);
+        self.leaderboard.truncate(LEADERBOARD_LEN);
+    }
+}
+
+fn parse_entry<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<Entry> {
+    let ticks = words.next()?.parse().ok()?;
+    let winner = words.next()?.to_string();
+    Some(Entry { ticks, winner })
+}
@@ -0,0 +1,95 @@
+// Lightweight scope profiler, compiled in with the `profiling` feature. Scopes
+// are kept in a bounded buffer and dumped as Chrome trace events, which can be
+// opened in chrome://tracing, Perfetto or speedscope.
+
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        #[cfg(feature = "profiling")]
+        let _profile_scope = $crate::profiling::Scope::new($name);
+    };
+}
+
+#[cfg(feature = "profiling")]
+pub use enabled::*;
+
+#[cfg(feature = "profiling")]
+mod enabled {
+    use std::collections::VecDeque;
+    use std::fs::File;
+    use std::io::{self, BufWriter, Write};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Mutex, OnceLock};
+    use std::time::Instant;
+
+    // Oldest scopes are forgotten past this, so long sessions stay bounded.
+    const MAX_EVENTS: usize = 1 << 20;
+
+    struct Event {
+        name: &'static str,
+        thread: u64,
+        start_us: u64,
+        duration_us: u64,
+    }
+
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    static EVENTS: Mutex<VecDeque<Event>> = Mutex::new(VecDeque::new());
+    static NEXT_THREAD: AtomicU64 = AtomicU64::new(0);
+
+    thread_local! {
+        static THREAD: u64 = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub struct Scope {
+        name: &'static str,
+        start: Instant,
+    }
+
+    impl Scope {
+        pub fn new(name: &'static str) -> Self {
+            EPOCH.get_or_init(Instant::now);
+            Scope {
+                name,
+                start: Instant::now(),
+            }
+        }
+    }
+
+    impl Drop for Scope {
+        fn drop(&mut self) {
+            let epoch = *EPOCH.get().unwrap();
+            let event = Event {
+                name: self.name,
+                thread: THREAD.with(|thread| *thread),
+                start_us: self.start.duration_since(epoch).as_micros() as u64,
+                duration_us: self.start.elapsed().as_micros() as u64,
+            };
+
+            let mut events = EVENTS.lock().unwrap();
+            if events.len() == MAX_EVENTS {
+                events.pop_front();
+            }
+            events.push_back(event);
+        }
+    }
+
+    pub fn dump_trace(path: &Path) -> io::Result<()> {
+        let events = EVENTS.lock().unwrap();
+        let mut out = BufWriter::new(File::create(path)?);
+
+        write!(out, "{{\"traceEvents\":[")?;
+        for (i, event) in events.iter().enumerate() {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            write!(
+                out,
+                "{{\"name\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{},\"dur\":{}}}",
+                event.name, event.thread, event.start_us, event.duration_us
+            )?;
+        }
+        writeln!(out, "]}}")?;
+        out.flush()
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+use std::thread;
+
+use bracket_lib::prelude::*;
+use rock_paper_scissors::obstacle::Obstacle;
+use rock_paper_scissors::pickup::Pickup;
+use rock_paper_scissors::portal::Portal;
+use rock_paper_scissors::profile_scope;
+use rock_paper_scissors::simulation::{Arena, Simulation, FLASH_TICKS, RING_TICKS};
+use rock_paper_scissors::{Hand, Particle, Real, Rules, Vec2f};
+
+use crate::camera::Camera;
+use crate::config::GlyphConfig;
+use crate::theme::Theme;
+use crate::{PARTICLE_LAYER, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Below this many particles, spinning up threads costs more than it saves.
+const PARALLEL_RENDER_THRESHOLD: usize = 2048;
+// Most threads particles are split between, as each fills a batch of its own
+// and bracket-lib only keeps so many batches around
+const MAX_RENDER_THREADS: usize = 32;
+
+// How much lighter than the floor obstacles are painted
+const OBSTACLE_LIGHTNESS: f32 = 0.3;
+// How much darker a particle is drawn for each hit it has taken, and the
+// most it gets, so it can still be told apart
+const DAMAGE_SHADE: f32 = 0.15;
+const MAX_DAMAGE_SHADE: f32 = 0.6;
+// How far a particle's colour goes towards that of the power it took
+const EFFECT_TINT: f32 = 0.5;
+// How far towards white a particle that was just won over is drawn at first,
+// and how far out the ring around the one that won it over is
+const FLASH_BRIGHTNESS: f32 = 0.9;
+const RING_RADIUS: i32 = 2;
+// Tiles between the arrows that show which way fields push, how much lighter
+// than the floor the arrows are drawn, and the force they are lightest for
+const FIELD_SPACING: i32 = 4;
+const FIELD_LIGHTNESS: f32 = 0.25;
+const FIELD_REFERENCE: Real = 0.05;
+// How far the floor of a zone goes towards the colour of the hand it favours,
+// and its name in the corner further still
+const ZONE_TINT: f32 = 0.15;
+const ZONE_NAME_TINT: f32 = 0.5;
+// Colours the ends of each portal are drawn in, in turn, so the two ends of
+// one can be told from those of the next
+const PORTAL_COLORS: [(u8, u8, u8); 4] = [
+    (80, 200, 250),
+    (250, 120, 220),
+    (250, 170, 60),
+    (150, 240, 90),
+];
+
+// Which glyphs particles are drawn with, and in what colour, indexed by hand.
+// Hands past the end, say from a replay of other rules, are drawn as a white
+// question mark.
+#[derive(Clone, Debug, Default)]
+pub struct Glyphs {
+    art: Vec<Option<FontCharType>>,
+    tiles: Vec<FontCharType>,
+    use_art: bool,
+    colors: Vec<RGB>,
+}
+
+impl Glyphs {
+    // Picks the config file's glyphs and colours for each hand of `rules`,
+    // then the theme's colours, then the rules' own
+    pub fn new(
+        rules: &Rules,
+        config: &GlyphConfig,
+        colors: &HashMap<String, RGB>,
+        theme: &Theme,
+    ) -> Self {
+        let mut glyphs = Glyphs {
+            use_art: config.use_art,
+            ..Glyphs::default()
+        };
+        for hand in rules.hands() {
+            let species = rules.species(hand);
+            let key = species.name.to_lowercase();
+            glyphs.art.push(config.art.get(&key).copied());
+            glyphs.tiles.push(
+                config
+                    .tiles
+                    .get(&key)
+                    .copied()
+                    .unwrap_or_else(|| to_cp437(species.glyph)),
+            );
+            let color = colors
+                .get(&key)
+                .copied()
+                .or_else(|| theme.hand_color(&species.name))
+                .unwrap_or_else(|| RGB::from(species.color));
+            glyphs.colors.push(color);
+        }
+        glyphs
+    }
+
+    pub fn color(&self, hand: Hand) -> RGB {
+        self.colors
+            .get(hand.index())
+            .copied()
+            .unwrap_or_else(|| RGB::named(WHITE))
+    }
+
+    fn art(&self, hand: Hand) -> Option<FontCharType> {
+        self.art
+            .get(hand.index())
+            .copied()
+            .flatten()
+            .filter(|_| self.use_art)
+    }
+
+    fn tile(&self, hand: Hand) -> FontCharType {
+        self.tiles
+            .get(hand.index())
+            .copied()
+            .unwrap_or_else(|| to_cp437('?'))
+    }
+}
+
+// One glyph of a particle, with the corner of the tile it covers in tiles,
+// which need not be whole ones
+#[derive(Copy, Clone, Debug)]
+pub struct DrawCommand {
+    pub x: f32,
+    pub y: f32,
+    pub fg: RGB,
+    pub glyph: FontCharType,
+}
+
+// Turns particles into batches of draw commands (in parallel for large swarms)
+// and queues them onto the particle layer in their original order, so the
+// output doesn't depend on how the work was split. They go on screen with the
+// rest of the batches at the end of the frame, between tiles. Positions are
+// relative to `origin` on screen.
+pub fn render_particles<'a>(
+    particles: impl Iterator<Item = &'a Particle>,
+    origin: Point,
+    glyphs: &Glyphs,
+) {
+    profile_scope!("render_particles");
+    let particles: Vec<&Particle> = particles.collect();
+
+    let batch = |chunk: &[&Particle]| {
+        let mut batch = DrawBatch::new();
+        batch_particles(&mut batch, chunk, origin, glyphs);
+        batch
+    };
+    let workers = thread::available_parallelism().map_or(1, |n| n.get().min(MAX_RENDER_THREADS));
+    let batches: Vec<_> = if particles.len() < PARALLEL_RENDER_THRESHOLD || workers < 2 {
+        vec![batch(&particles)]
+    } else {
+        let chunk_size = particles.len().div_ceil(workers);
+        thread::scope(|scope| {
+            let jobs: Vec<_> = particles
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(|| batch(chunk)))
+                .collect();
+            jobs.into_iter().map(|job| job.join().unwrap()).collect()
+        })
+    };
+
+    for (order, mut batch) in batches.into_iter().enumerate() {
+        if let Err(err) = batch.submit(order) {
+            eprintln!("Could not queue particles: {}", err);
+        }
+    }
+}
+
+fn batch_particles(batch: &mut DrawBatch, particles: &[&Particle], origin: Point, glyphs: &Glyphs) {
+    let mut commands = Vec::with_capacity(9 * particles.len());
+    for particle in particles {
+        particle_commands(particle, glyphs, &mut commands);
+    }
+
+    batch.target(PARTICLE_LAYER);
+    // Fancy consoles count rows from one further down than the others, so
+    // this lines whole positions up with the tiles around them
+    let (x, y) = (origin.x as f32, origin.y as f32 + 1.0);
+    for command in commands {
+        batch.set_fancy(
+            PointF::new(x + command.x, y + command.y),
+            0,
+            Radians::new(0.0),
+            PointF::new(1.0, 1.0),
+            ColorPair::new(command.fg, RGBA::new()),
+            command.glyph,
+        );
+    }
+}
+
+pub fn particle_commands(particle: &Particle, glyphs: &Glyphs, commands: &mut Vec<DrawCommand>) {
+    let shade = (DAMAGE_SHADE * particle.damage as f32).min(MAX_DAMAGE_SHADE);
+    let mut color = glyphs.color(particle.hand).lerp(RGB::named(BLACK), shade);
+    if let Some(effect) = particle.effect {
+        color = color.lerp(RGB::from(effect.power.color()), EFFECT_TINT);
+    }
+    if particle.flash > 0 {
+        let share = particle.flash as f32 / FLASH_TICKS as f32;
+        color = color.lerp(RGB::named(WHITE), FLASH_BRIGHTNESS * share);
+    }
+    // Glyphs are centred on the particle, half a tile in from their corner
+    let x: f32 = particle.position.x as _;
+    let y: f32 = particle.position.y as _;
+    let (x, y) = (x - 0.5, y - 0.5);
+    let Some(glyph) = glyphs.art(particle.hand) else {
+        commands.push(DrawCommand {
+            x,
+            y,
+            fg: color,
+            glyph: glyphs.tile(particle.hand),
+        });
+        return;
+    };
+
+    for dx in -1..2 {
+        for dy in -1..2 {
+            commands.push(DrawCommand {
+                x: x + dx as f32,
+                y: y - dy as f32,
+                fg: color,
+                glyph: (glyph as i32 + dx - 16 * dy) as u16,
+            });
+        }
+    }
+}
+
+// Rings each particle that just won another one over in its colour, fading
+// as the ring runs out, with positions relative to `origin` on screen. Drawn
+// before the particles, so ones next to it stay on top. The bundled fonts
+// have hand art where most line drawing characters would be, so the ring is
+// drawn in plain ones.
+pub fn draw_rings<'a>(
+    ctx: &mut BTerm,
+    particles: impl Iterator<Item = &'a Particle>,
+    origin: Point,
+    glyphs: &Glyphs,
+    theme: &Theme,
+) {
+    let r = RING_RADIUS;
+    for particle in particles.filter(|particle| particle.ring > 0) {
+        let share = particle.ring as f32 / RING_TICKS as f32;
+        let color = theme.arena.lerp(glyphs.color(particle.hand), share);
+        let center = origin + Point::new(particle.position.x as i32, particle.position.y as i32);
+        for d in -r + 1..r {
+            for (x, y, glyph) in [
+                (center.x + d, center.y - r, '-'),
+                (center.x + d, center.y + r, '-'),
+                (center.x - r, center.y + d, '|'),
+                (center.x + r, center.y + d, '|'),
+            ] {
+                ctx.set(x, y, color, RGB::named(BLACK), to_cp437(glyph));
+            }
+        }
+        for (dx, dy) in [(-r, -r), (r, -r), (-r, r), (r, r)] {
+            let (x, y) = (center.x + dx, center.y + dy);
+            ctx.set(x, y, color, RGB::named(BLACK), to_cp437('+'));
+        }
+    }
+}
+
+// Draws each pickup as its power's glyph, with positions relative to
+// `origin` on screen
+pub fn draw_pickups<'a>(ctx: &mut BTerm, pickups: impl Iterator<Item = &'a Pickup>, origin: Point) {
+    for pickup in pickups {
+        let power = pickup.power;
+        ctx.set(
+            origin.x + pickup.position.x as i32,
+            origin.y + pickup.position.y as i32,
+            RGB::from(power.color()),
+            RGB::named(BLACK),
+            to_cp437(power.glyph()),
+        );
+    }
+}
+
+// Draws both ends of each portal that `camera` sees, in the colour of its
+// pair
+pub fn draw_portals(ctx: &mut BTerm, portals: &[Portal], camera: &Camera) {
+    let origin = camera.origin();
+    for (portal, &color) in portals.iter().zip(PORTAL_COLORS.iter().cycle()) {
+        for end in portal.ends.iter().filter(|end| camera.sees(end)) {
+            ctx.set(
+                origin.x + end.x as i32,
+                origin.y + end.y as i32,
+                RGB::from(color),
+                RGB::named(BLACK),
+                to_cp437('◙'),
+            );
+        }
+    }
+}
+
+// Paints the tiles on screen whose middle is inside an obstacle after `ticks`
+// ticks onto the active console's background, with positions relative to
+// `origin` on screen
+pub fn draw_obstacles(
+    ctx: &mut BTerm,
+    obstacles: &[Obstacle],
+    ticks: u64,
+    origin: Point,
+    theme: &Theme,
+) {
+    let color = theme.arena.lerp(RGB::named(WHITE), OBSTACLE_LIGHTNESS);
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let middle = Vec2f {
+                x: (x - origin.x) as Real + 0.5,
+                y: (y - origin.y) as Real + 0.5,
+            };
+            if obstacles
+                .iter()
+                .any(|obstacle| obstacle.contains(&middle, ticks))
+            {
+                ctx.set_bg(x, y, color);
+            }
+        }
+    }
+}
+
+// Paints the tiles on screen that the walls of a shrinking arena have closed
+// in on with the theme's fog, onto the active console's background
+pub fn draw_walls(ctx: &mut BTerm, arena: &Arena, origin: Point, theme: &Theme) {
+    let (min, max) = arena.walls();
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let (ax, ay) = ((x - origin.x) as Real + 0.5, (y - origin.y) as Real + 0.5);
+            let in_arena = (0.0..arena.width).contains(&ax) && (0.0..arena.height).contains(&ay);
+            let between = (min.x..max.x).contains(&ax) && (min.y..max.y).contains(&ay);
+            if in_arena && !between {
+                ctx.set_bg(x, y, theme.fog);
+            }
+        }
+    }
+}
+
+// Draws a faint arrow every few tiles onto the active console, the way the
+// fields of `sim` push there right now, with positions relative to `origin`
+// on screen. Tiles walls or obstacles have closed off are left alone.
+pub fn draw_fields(ctx: &mut BTerm, sim: &Simulation, origin: Point, theme: &Theme) {
+    let (min, max) = sim.arena.walls();
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let (ax, ay) = (x - origin.x, y - origin.y);
+            let middle = Vec2f {
+                x: ax as Real + 0.5,
+                y: ay as Real + 0.5,
+            };
+            let on_grid = [ax, ay]
+                .iter()
+                .all(|a| a.rem_euclid(FIELD_SPACING) == FIELD_SPACING / 2);
+            let open = (min.x..max.x).contains(&middle.x)
+                && (min.y..max.y).contains(&middle.y)
+                && !sim
+                    .params
+                    .obstacles
+                    .iter()
+                    .any(|o| o.contains(&middle, sim.ticks));
+            if !on_grid || !open {
+                continue;
+            }
+
+            let force = sim
+                .params
+                .fields
+                .iter()
+                .fold(Vec2f::default(), |sum, field| {
+                    sum.plus(field.force_at(&middle, &sim.arena, sim.ticks))
+                });
+            let strength = force.norm();
+            if strength == 0.0 {
+                continue;
+            }
+            let arrow = if force.x.abs() >= force.y.abs() {
+                if force.x > 0.0 {
+                    '→'
+                } else {
+                    '←'
+                }
+            } else if force.y > 0.0 {
+                '↓'
+            } else {
+                '↑'
+            };
+            let share: f32 = (strength / FIELD_REFERENCE).min(1.0) as _;
+            let fg = theme
+                .arena
+                .lerp(RGB::named(WHITE), FIELD_LIGHTNESS * (0.3 + 0.7 * share));
+            ctx.set(x, y, fg, theme.arena, to_cp437(arrow));
+        }
+    }
+}
+
+// Tints the tiles on screen inside each zone of `sim` with the colour of the
+// hand it favours, and writes its name along its top edge if there is room,
+// onto the active console with positions relative to `origin` on screen
+pub fn draw_zones(
+    ctx: &mut BTerm,
+    sim: &Simulation,
+    glyphs: &Glyphs,
+    origin: Point,
+    theme: &Theme,
+) {
+    for zone in sim.params.zones.iter() {
+        let tint = theme.arena.lerp(glyphs.color(zone.hand), ZONE_TINT);
+        let r = &zone.region;
+        let (left, top) = (origin.x + r.x.round() as i32, origin.y + r.y.round() as i32);
+        for y in top.max(0)..(origin.y + (r.y + r.height).round() as i32).min(SCREEN_HEIGHT) {
+            for x in left.max(0)..(origin.x + (r.x + r.width).round() as i32).min(SCREEN_WIDTH) {
+                ctx.set_bg(x, y, tint);
+            }
+        }
+        if zone.name.chars().count() < r.width as usize && (0..SCREEN_HEIGHT).contains(&top) {
+            let fg = theme.arena.lerp(glyphs.color(zone.hand), ZONE_NAME_TINT);
+            ctx.print_color(left + 1, top, fg, tint, &zone.name);
+        }
+    }
+}
+
+// Draws a square frame of background colour around `center`, `radius` tiles
+// out on every side
+pub fn draw_frame(ctx: &mut BTerm, center: Point, radius: i32, color: RGB) {
+    for d in -radius..=radius {
+        for (x, y) in [
+            (center.x + d, center.y - radius),
+            (center.x + d, center.y + radius),
+            (center.x - radius, center.y + d),
+            (center.x + radius, center.y + d),
+        ] {
+            ctx.set(x, y, WHITE, color, to_cp437(' '));
+        }
+    }
+}
@@ -0,0 +1,414 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use rock_paper_scissors::Particle;
+
+use crate::export::Exporter;
+use crate::snapshot::{Frame, SnapshotDecoder, SnapshotEncoder};
+
+// Replay files are a header followed by self-describing chunks of frames and,
+// once the recording is finished, an index of where each chunk starts:
+//
+//   header: MAGIC, version u32
+//   chunk:  first frame u64, frame count u32, byte length u32, frames
+//   index:  (first frame u64, offset u64) per chunk, chunk count u32,
+//           frame count u64, INDEX_MAGIC
+//
+// Frames are snapshots (see the snapshot module) and every chunk starts with a
+// keyframe, so it can be decoded without the ones before it. All numbers are
+// little-endian. A recording that was cut short has no index;
+// its chunks can still be found by walking their headers.
+const MAGIC: &[u8; 4] = b"RPSR";
+const INDEX_MAGIC: &[u8; 4] = b"RPSI";
+const VERSION: u32 = 3;
+const HEADER_LEN: u64 = 8;
+const CHUNK_HEADER_LEN: usize = 16;
+const INDEX_ENTRY_LEN: u64 = 16;
+const INDEX_FOOTER_LEN: u64 = 16;
+
+// A chunk is handed to the writer thread once it grows past either limit.
+const CHUNK_BYTES: usize = 1 << 20;
+const CHUNK_FRAMES: u32 = 256;
+
+// First frame and file offset of every chunk
+type ChunkIndex = Vec<(u64, u64)>;
+
+// Streams a replay to disk through the exporter, so only the chunk being
+// filled is ever held in memory.
+pub struct ReplayWriter {
+    path: PathBuf,
+    encoder: SnapshotEncoder,
+    chunk: Vec<u8>,
+    chunk_first_frame: u64,
+    chunk_frames: u32,
+    frames: u64,
+    offset: u64,
+    index: ChunkIndex,
+}
+
+impl ReplayWriter {
+    pub fn create(exporter: &mut Exporter, path: &Path) -> Self {
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        exporter.write(path, header);
+
+        ReplayWriter {
+            path: path.to_path_buf(),
+            encoder: SnapshotEncoder::default(),
+            chunk: Vec::new(),
+            chunk_first_frame: 0,
+            chunk_frames: 0,
+            frames: 0,
+            offset: HEADER_LEN,
+            index: Vec::new(),
+        }
+    }
+
+    pub fn push_frame<'a>(
+        &mut self,
+        exporter: &mut Exporter,
+        tick: u64,
+        particles: impl Iterator<Item = &'a Particle>,
+    ) {
+        self.encoder.encode(tick, particles, &mut self.chunk);
+        self.chunk_frames += 1;
+        self.frames += 1;
+        if self.chunk.len() >= CHUNK_BYTES || self.chunk_frames >= CHUNK_FRAMES {
+            self.flush_chunk(exporter);
+        }
+    }
+
+    pub fn finish(mut self, exporter: &mut Exporter) {
+        self.flush_chunk(exporter);
+
+        let mut index = Vec::new();
+        for (first_frame, offset) in &self.index {
+            index.extend_from_slice(&first_frame.to_le_bytes());
+            index.extend_from_slice(&offset.to_le_bytes());
+        }
+        index.extend_from_slice(&(self.index.len() as u32).to_le_bytes());
+        index.extend_from_slice(&self.frames.to_le_bytes());
+        index.extend_from_slice(INDEX_MAGIC);
+        exporter.write(&self.path, index);
+        exporter.close(&self.path);
+    }
+
+    fn flush_chunk(&mut self, exporter: &mut Exporter) {
+        if self.chunk_frames == 0 {
+            return;
+        }
+
+        let mut bytes = Vec::with_capacity(CHUNK_HEADER_LEN + self.chunk.len());
+        bytes.extend_from_slice(&self.chunk_first_frame.to_le_bytes());
+        bytes.extend_from_slice(&self.chunk_frames.to_le_bytes());
+        bytes.extend_from_slice(&(self.chunk.len() as u32).to_le_bytes());
+        bytes.append(&mut self.chunk);
+
+        self.index.push((self.chunk_first_frame, self.offset));
+        self.offset += bytes.len() as u64;
+        self.chunk_first_frame = self.frames;
+        self.chunk_frames = 0;
+        self.encoder.force_keyframe();
+        exporter.write(&self.path, bytes);
+    }
+}
+
+// Reads frames on demand, keeping only the chunk around the current frame
+// decoded in memory.
+pub struct ReplayReader {
+    file: BufReader<File>,
+    index: ChunkIndex,
+    frames: u64,
+    cached: Option<(usize, Vec<Frame>)>,
+}
+
+impl ReplayReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut header = [0; HEADER_LEN as usize];
+        file.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a replay file"));
+        }
+        if u32::from_le_bytes(header[4..].try_into().unwrap()) != VERSION {
+            return Err(invalid("unsupported replay version"));
+        }
+
+        let (index, frames) = match read_index(&mut file)? {
+            Some(found) => found,
+            None => scan_chunks(&mut file)?,
+        };
+        if frames == 0 {
+            return Err(invalid("the replay has no frames"));
+        }
+        check_index(&index, frames)?;
+
+        Ok(ReplayReader {
+            file,
+            index,
+            frames,
+            cached: None,
+        })
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn frame(&mut self, frame: u64) -> io::Result<&Frame> {
+        let frame = frame.min(self.frames - 1);
+        let chunk = self.index.partition_point(|&(first, _)| first <= frame) - 1;
+
+        if self.cached.as_ref().map(|(cached, _)| *cached) != Some(chunk) {
+            let frames = self.read_chunk(self.index[chunk].1)?;
+            self.cached = Some((chunk, frames));
+        }
+
+        let (_, frames) = self.cached.as_ref().unwrap();
+        frames
+            .get((frame - self.index[chunk].0) as usize)
+            .ok_or_else(|| invalid("chunk is shorter than its index entry"))
+    }
+
+    fn read_chunk(&mut self, offset: u64) -> io::Result<Vec<Frame>> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0; CHUNK_HEADER_LEN];
+        self.file.read_exact(&mut header)?;
+        let frame_count = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        let byte_len = u32::from_le_bytes(header[12..16].try_into().unwrap());
+
+        let mut bytes = vec![0; byte_len as usize];
+        self.file.read_exact(&mut bytes)?;
+
+        let mut cursor = &bytes[..];
+        let mut decoder = SnapshotDecoder::default();
+        (0..frame_count)
+            .map(|_| decoder.decode(&mut cursor))
+            .collect()
+    }
+}
+
+fn read_index(file: &mut BufReader<File>) -> io::Result<Option<(ChunkIndex, u64)>> {
+    let len = file.seek(SeekFrom::End(0))?;
+    if len < HEADER_LEN + INDEX_FOOTER_LEN {
+        return Ok(None);
+    }
+
+    file.seek(SeekFrom::End(-(INDEX_FOOTER_LEN as i64)))?;
+    let mut footer = [0; INDEX_FOOTER_LEN as usize];
+    file.read_exact(&mut footer)?;
+    if &footer[12..] != INDEX_MAGIC {
+        return Ok(None);
+    }
+    let chunks = u32::from_le_bytes(footer[..4].try_into().unwrap()) as u64;
+    let frames = u64::from_le_bytes(footer[4..12].try_into().unwrap());
+    // Every chunk holds at least one frame
+    if chunks > frames || (chunks == 0) != (frames == 0) {
+        return Err(invalid("the replay index doesn't match its frame count"));
+    }
+
+    let index_len = chunks * INDEX_ENTRY_LEN + INDEX_FOOTER_LEN;
+    if index_len > len - HEADER_LEN {
+        return Ok(None);
+    }
+    file.seek(SeekFrom::End(-(index_len as i64)))?;
+    let mut index = Vec::with_capacity(chunks as usize);
+    for _ in 0..chunks {
+        let mut entry = [0; INDEX_ENTRY_LEN as usize];
+        file.read_exact(&mut entry)?;
+        index.push((
+            u64::from_le_bytes(entry[..8].try_into().unwrap()),
+            u64::from_le_bytes(entry[8..].try_into().unwrap()),
+        ));
+    }
+
+    // The last chunk has to end on the last frame the footer counts
+    if let Some(&(first_frame, offset)) = index.last() {
+        if offset + CHUNK_HEADER_LEN as u64 > len - index_len {
+            return Err(invalid("the replay index points past its chunks"));
+        }
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0; CHUNK_HEADER_LEN];
+        file.read_exact(&mut header)?;
+        let chunk_first_frame = u64::from_le_bytes(header[..8].try_into().unwrap());
+        let frame_count = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if chunk_first_frame != first_frame || first_frame + frame_count as u64 != frames {
+            return Err(invalid("the replay index doesn't match its frame count"));
+        }
+    }
+
+    Ok(Some((index, frames)))
+}
+
+// Looking a frame up goes by the chunk before it in the index, so the index
+// has to start on the first frame and go forward, chunk by chunk
+fn check_index(index: &ChunkIndex, frames: u64) -> io::Result<()> {
+    if index.first().map(|&(first_frame, _)| first_frame) != Some(0) {
+        return Err(invalid("the replay index doesn't start at the first frame"));
+    }
+    let ascending = index
+        .windows(2)
+        .all(|pair| pair[0].0 < pair[1].0 && pair[0].1 < pair[1].1);
+    if !ascending || index.iter().any(|&(first_frame, _)| first_frame >= frames) {
+        return Err(invalid("the replay index is out of order"));
+    }
+    Ok(())
+}
+
+// Rebuilds the index of a replay that was never finished, ignoring a last
+// chunk that only made it partially to disk.
+fn scan_chunks(file: &mut BufReader<File>) -> io::Result<(ChunkIndex, u64)> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut index = Vec::new();
+    let mut frames = 0;
+    let mut offset = HEADER_LEN;
+
+    while offset + CHUNK_HEADER_LEN as u64 <= len {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0; CHUNK_HEADER_LEN];
+        file.read_exact(&mut header)?;
+        let first_frame = u64::from_le_bytes(header[..8].try_into().unwrap());
+        let frame_count = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        let byte_len = u32::from_le_bytes(header[12..16].try_into().unwrap());
+
+        let end = offset + CHUNK_HEADER_LEN as u64 + byte_len as u64;
+        if first_frame != frames || end > len {
+            break;
+        }
+        index.push((first_frame, offset));
+        frames += frame_count as u64;
+        offset = end;
+    }
+
+    Ok((index, frames))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use bracket_lib::prelude::RandomNumberGenerator;
+    use rock_paper_scissors::simulation::{Arena, Params};
+
+    use super::*;
+
+    const FRAMES: u64 = 2 * CHUNK_FRAMES as u64 + 88;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("rps-{}-{}.replay", process::id(), name))
+    }
+
+    // Records a swarm drifting across the arena for FRAMES frames, finishing
+    // the replay or leaving it cut short, and hands back every frame
+    fn record(path: &Path, finish: bool) -> Vec<Vec<Particle>> {
+        let mut rng = RandomNumberGenerator::seeded(219);
+        let (arena, params) = (Arena::full_screen(), Params::default());
+        let mut particles: Vec<Particle> = (0..50)
+            .map(|_| Particle::random(&mut rng, &arena, &params))
+            .collect();
+
+        let mut exporter = Exporter::new();
+        let mut writer = ReplayWriter::create(&mut exporter, path);
+        let mut frames = Vec::new();
+        for frame in 0..FRAMES {
+            writer.push_frame(&mut exporter, frame * 3, particles.iter());
+            frames.push(particles.clone());
+            for particle in &mut particles {
+                particle.update_position();
+            }
+        }
+        if finish {
+            writer.finish(&mut exporter);
+        }
+        exporter.sync();
+        frames
+    }
+
+    fn assert_frame(reader: &mut ReplayReader, frame: u64, expected: &[Particle]) {
+        let read = reader.frame(frame).unwrap();
+        assert_eq!(read.tick, frame * 3);
+        assert_eq!(read.particles.len(), expected.len());
+        for (read, expected) in read.particles.iter().zip(expected) {
+            assert!(read.position.distance(&expected.position) < 1.0 / 64.0);
+            assert_eq!(read.hand, expected.hand);
+        }
+    }
+
+    #[test]
+    fn finished_replay_reads_back() {
+        let path = temp_path("finished");
+        let frames = record(&path, true);
+        let mut reader = ReplayReader::open(&path).unwrap();
+        assert_eq!(reader.frame_count(), FRAMES);
+        assert_eq!(reader.index.len(), 3);
+        // Across chunks, backwards too
+        for frame in [0, 255, 256, FRAMES - 1, 300, 1, 511, 512] {
+            assert_frame(&mut reader, frame, &frames[frame as usize]);
+        }
+        // Past the end is the last frame
+        assert_eq!(reader.frame(FRAMES + 10).unwrap().tick, (FRAMES - 1) * 3);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn cut_short_replay_keeps_its_whole_chunks() {
+        let path = temp_path("cut-short");
+        let frames = record(&path, false);
+        // A chunk that only made it partially to disk
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.extend_from_slice(&(2 * CHUNK_FRAMES as u64).to_le_bytes());
+        bytes.extend_from_slice(&[1, 0, 0, 0, 0, 1, 0, 0, 0]);
+        fs::write(&path, bytes).unwrap();
+
+        let mut reader = ReplayReader::open(&path).unwrap();
+        assert_eq!(reader.frame_count(), 2 * CHUNK_FRAMES as u64);
+        for frame in [0, 300, 2 * CHUNK_FRAMES as u64 - 1] {
+            assert_frame(&mut reader, frame, &frames[frame as usize]);
+        }
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn broken_index_is_an_error() {
+        let path = temp_path("broken");
+        record(&path, true);
+        let finished = fs::read(&path).unwrap();
+        let footer = finished.len() - INDEX_FOOTER_LEN as usize;
+        let entries = footer - 3 * INDEX_ENTRY_LEN as usize;
+
+        let mut broken = Vec::new();
+        // No chunks, but frames
+        let mut empty = finished[..HEADER_LEN as usize].to_vec();
+        empty.extend_from_slice(&0u32.to_le_bytes());
+        empty.extend_from_slice(&5u64.to_le_bytes());
+        empty.extend_from_slice(INDEX_MAGIC);
+        broken.push(empty);
+        // Not starting at the first frame
+        let mut late = finished.clone();
+        late[entries..entries + 8].copy_from_slice(&1u64.to_le_bytes());
+        broken.push(late);
+        // Out of order
+        let mut shuffled = finished.clone();
+        let second = entries + INDEX_ENTRY_LEN as usize;
+        shuffled[second..second + 8].copy_from_slice(&FRAMES.to_le_bytes());
+        broken.push(shuffled);
+        // A frame count the chunks don't add up to
+        let mut miscounted = finished.clone();
+        miscounted[footer + 4..footer + 12].copy_from_slice(&(FRAMES + 1).to_le_bytes());
+        broken.push(miscounted);
+
+        for bytes in broken {
+            fs::write(&path, bytes).unwrap();
+            let err = ReplayReader::open(&path).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        fs::remove_file(path).unwrap();
+    }
+}
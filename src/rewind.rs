@@ -0,0 +1,51 @@
+use std::collections::VecDeque;
+
+use rock_paper_scissors::simulation::Simulation;
+
+// A snapshot is taken every this many ticks
+const SNAPSHOT_INTERVAL: u64 = 10;
+// Snapshots kept, which makes for about half a minute of play
+const MAX_SNAPSHOTS: usize = 60;
+
+// Copies of the simulation at regular ticks, newest last, so a match can be
+// taken back to an earlier point and resumed from there.
+pub struct Rewind {
+    snapshots: VecDeque<Simulation>,
+}
+
+impl Rewind {
+    pub fn new() -> Self {
+        Rewind {
+            snapshots: VecDeque::with_capacity(MAX_SNAPSHOTS),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    // Keeps a copy of `sim` if a snapshot is due, dropping the oldest one
+    // when the buffer is full
+    pub fn record(&mut self, sim: &Simulation) {
+        if !sim.ticks.is_multiple_of(SNAPSHOT_INTERVAL) {
+            return;
+        }
+        if self.snapshots.len() == MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(sim.clone());
+    }
+
+    // Puts `sim` back to the latest snapshot before its current tick. The
+    // snapshots after it are dropped, as that future is about to be played
+    // again. Returns false once there is nothing earlier to go back to.
+    pub fn step_back(&mut self, sim: &mut Simulation) -> bool {
+        while let Some(snapshot) = self.snapshots.pop_back() {
+            if snapshot.ticks < sim.ticks {
+                *sim = snapshot;
+                return true;
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,228 @@
+// A hand by its place among the species of a rule set. It only means
+// something next to the rules it came from; the default is the first hand,
+// which every rule set has.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Hand(u8);
+
+// Most hands a rule set can have, so that a hand fits in a byte
+pub const MAX_HANDS: usize = 256;
+
+impl Hand {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn from_index(index: usize) -> Option<Hand> {
+        u8::try_from(index).ok().map(Hand)
+    }
+}
+
+// What one hand is called and how it is drawn when the font has no art for it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Species {
+    pub name: String,
+    pub glyph: char,
+    pub color: (u8, u8, u8),
+}
+
+// The hands a match is played with and which of them wins over which
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rules {
+    pub name: String,
+    species: Vec<Species>,
+    // beats[winner][loser]
+    beats: Vec<Vec<bool>>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules::classic()
+    }
+}
+
+impl Rules {
+    // Checks that the rules make sense: at least two hands, all named apart,
+    // and no hand that beats itself or a hand that beats it back
+    pub fn new(name: &str, species: Vec<Species>, beats: &[(Hand, Hand)]) -> Result<Self, String> {
+        if !(2..=MAX_HANDS).contains(&species.len()) {
+            return Err(format!(
+                "rules need 2 to {} hands, not {}",
+                MAX_HANDS,
+                species.len()
+            ));
+        }
+        for (i, lhs) in species.iter().enumerate() {
+            if species[..i]
+                .iter()
+                .any(|rhs| rhs.name.eq_ignore_ascii_case(&lhs.name))
+            {
+                return Err(format!("there are two hands called {}", lhs.name));
+            }
+        }
+
+        let mut matrix = vec![vec![false; species.len()]; species.len()];
+        for &(winner, loser) in beats {
+            let (w, l) = (winner.index(), loser.index());
+            if w >= species.len() || l >= species.len() {
+                return Err(format!("no hand number {}", w.max(l)));
+            }
+            if w == l {
+                return Err(format!("{} can't beat itself", species[w].name));
+            }
+            if matrix[l][w] {
+                return Err(format!(
+                    "{} and {} can't both beat each other",
+                    species[w].name, species[l].name
+                ));
+            }
+            matrix[w][l] = true;
+        }
+
+        Ok(Rules {
+            name: name.to_string(),
+            species,
+            beats: matrix,
+        })
+    }
+
+    pub fn classic() -> Self {
+        preset(
+            "Rock Paper Scissors",
+            &[
+                ("Rock", 'R', (160, 160, 160)),
+                ("Paper", 'P', (235, 225, 160)),
+                ("Scissors", 'S', (220, 90, 90)),
+            ],
+            &[(0, 2), (1, 0), (2, 1)],
+        )
+    }
+
+    // Every hand beats two others
+    pub fn lizard_spock() -> Self {
+        preset(
+            "Rock Paper Scissors Lizard Spock",
+            &[
+                ("Rock", 'R', (160, 160, 160)),
+                ("Paper", 'P', (235, 225, 160)),
+                ("Scissors", 'S', (220, 90, 90)),
+                ("Lizard", 'L', (110, 190, 90)),
+                ("Spock", 'V', (90, 140, 230)),
+            ],
+            &[
+                (0, 2),
+                (0, 3),
+                (1, 0),
+                (1, 4),
+                (2, 1),
+                (2, 3),
+                (3, 1),
+                (3, 4),
+                (4, 2),
+                (4, 0),
+            ],
+        )
+    }
+
+    // The rule sets built into the game
+    pub fn presets() -> Vec<Rules> {
+        vec![Rules::classic(), Rules::lizard_spock()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.species.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.species.is_empty()
+    }
+
+    pub fn hands(&self) -> impl Iterator<Item = Hand> {
+        (0..self.species.len()).map(|i| Hand(i as u8))
+    }
+
+    pub fn species(&self, hand: Hand) -> &Species {
+        &self.species[hand.index()]
+    }
+
+    pub fn name(&self, hand: Hand) -> &str {
+        &self.species[hand.index()].name
+    }
+
+    // The hand called `name`, whatever its case
+    pub fn find(&self, name: &str) -> Option<Hand> {
+        self.species
+            .iter()
+            .position(|species| species.name.eq_ignore_ascii_case(name))
+            .and_then(Hand::from_index)
+    }
+
+    pub fn beats(&self, winner: Hand, loser: Hand) -> bool {
+        self.beats[winner.index()][loser.index()]
+    }
+
+    // The hand after `hand`, going back to the first after the last
+    pub fn next(&self, hand: Hand) -> Hand {
+        Hand(((hand.index() + 1) % self.len()) as u8)
+    }
+
+    // What makes for lopsided or stuck matches, for --check-config: hands
+    // that win over more hands than they lose to or the other way around,
+    // and pairs that just bounce off each other
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for hand in self.hands() {
+            let wins = self
+                .hands()
+                .filter(|&other| self.beats(hand, other))
+                .count();
+            let losses = self
+                .hands()
+                .filter(|&other| self.beats(other, hand))
+                .count();
+            if wins == 0 {
+                warnings.push(format!(
+                    "{} beats no hand, so it can only shrink",
+                    self.name(hand)
+                ));
+            } else if losses == 0 {
+                warnings.push(format!(
+                    "no hand beats {}, so it can't lose",
+                    self.name(hand)
+                ));
+            } else if wins != losses {
+                warnings.push(format!(
+                    "{} beats {} hands but loses to {}",
+                    self.name(hand),
+                    wins,
+                    losses
+                ));
+            }
+        }
+        for lhs in self.hands() {
+            for rhs in self.hands().skip(lhs.index() + 1) {
+                if !self.beats(lhs, rhs) && !self.beats(rhs, lhs) {
+                    warnings.push(format!(
+                        "neither {} nor {} beats the other",
+                        self.name(lhs),
+                        self.name(rhs)
+                    ));
+                }
+            }
+        }
+        warnings
+    }
+}
+
+// Rules built into the game, which are known to make sense
+fn preset(name: &str, species: &[(&str, char, (u8, u8, u8))], beats: &[(u8, u8)]) -> Rules {
+    let species = species
+        .iter()
+        .map(|&(name, glyph, color)| Species {
+            name: name.to_string(),
+            glyph,
+            color,
+        })
+        .collect();
+    let beats: Vec<(Hand, Hand)> = beats.iter().map(|&(w, l)| (Hand(w), Hand(l))).collect();
+    Rules::new(name, species, &beats).unwrap()
+}
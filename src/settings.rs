@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use rock_paper_scissors::simulation::{Arena, Params, Placement, Simulation, NUM_PARTICLES};
+use rock_paper_scissors::{Real, Rules};
+
+// Milliseconds of play per physics tick in the classic game
+const FRAME_DURATION: f32 = 60.0;
+
+// What new matches are made of. The defaults are the classic game; the
+// [simulation] section of the config file and then the command line can
+// change any of it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub particles: usize,
+    // Particles each hand starts with, in the order of the rules, adding up
+    // to `particles`. Empty to draw every particle's hand at random.
+    pub populations: Vec<usize>,
+    pub placement: Placement,
+    pub arena: Arena,
+    pub params: Params,
+    // Milliseconds of play per physics tick
+    pub frame_duration: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            particles: NUM_PARTICLES,
+            populations: Vec::new(),
+            placement: Placement::Random,
+            arena: Arena::full_screen(),
+            params: Params::default(),
+            frame_duration: FRAME_DURATION,
+        }
+    }
+}
+
+impl Settings {
+    // How many particles fill `arena` as densely as `particles` fill the
+    // settings' own arena
+    pub fn population(&self, arena: &Arena) -> usize {
+        if *arena == self.arena {
+            return self.particles;
+        }
+        let share = (arena.width * arena.height) / (self.arena.width * self.arena.height);
+        (self.particles as Real * share).round() as usize
+    }
+
+    // Spawns the particles of a new match in `sim`, as many as fill its
+    // arena as densely as the settings' own
+    pub fn deal(&self, sim: &mut Simulation) {
+        let count = self.population(&sim.arena);
+        // Dealt as they always were, so a seed keeps dealing the same match
+        if self.populations.is_empty() && self.placement == Placement::Random {
+            sim.spawn_random(count);
+            return;
+        }
+        let hands = sim.params.rules.len();
+        let counts: Vec<usize> = if self.populations.is_empty() {
+            (0..hands)
+                .map(|i| count / hands + usize::from(i < count % hands))
+                .collect()
+        } else {
+            let share = count as Real / self.particles as Real;
+            self.populations
+                .iter()
+                .map(|&n| (n as Real * share).round() as usize)
+                .collect()
+        };
+        sim.spawn_populations(&counts, self.placement);
+    }
+
+    // A number that tells apart matches played with different settings, for
+    // the run history. It is worked out from the settings written out in
+    // full, with FNV-1a, so it stays the same from one build to the next.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in format!("{:?}", self).bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        hash
+    }
+
+    // Checks what no single setting can on its own: that the populations and
+    // the zones fit the hands the rules have
+    pub fn validate(&self) -> Result<(), String> {
+        let hands = self.params.rules.len();
+        if !self.populations.is_empty() && self.populations.len() != hands {
+            return Err(format!(
+                "populations has {} counts, but the rules have {} hands",
+                self.populations.len(),
+                hands
+            ));
+        }
+        if let Some(zone) = self.params.zones.iter().find(|z| z.hand.index() >= hands) {
+            return Err(format!(
+                "zone {} favours hand {}, but the rules have {} hands",
+                zone.name,
+                zone.hand.index(),
+                hands
+            ));
+        }
+        Ok(())
+    }
+
+    // Only matches of the classic game make it to the leaderboard
+    pub fn is_classic(&self) -> bool {
+        *self == Settings::default()
+    }
+
+    // One line of the settings menu
+    pub fn menu_line(&self, row: usize) -> String {
+        match row {
+            0 if self.populations.is_empty() => format!("Particles: {}", self.particles),
+            0 => {
+                let counts: Vec<String> = self.populations.iter().map(usize::to_string).collect();
+                format!("Particles: {} ({})", self.particles, counts.join("/"))
+            }
+            1 => format!("Max velocity: {:.1}", self.params.max_velocity),
+            2 => format!("Speed: {:.1}x", self.params.speed),
+            3 => format!("Arena width: {}", self.arena.width),
+            4 => format!("Arena height: {}", self.arena.height),
+            5 => format!("Rules: {}", self.params.rules.name),
+            _ => format!("Placement: {:?}", self.placement),
+        }
+    }
+
+    // Moves the setting on one row of the menu by `steps` notches, within
+    // what the config file would accept. The rules row goes through
+    // `rule_sets` in order.
+    pub fn adjust(&mut self, row: usize, steps: i32, rule_sets: &[Arc<Rules>]) {
+        let notch = |value: Real, step: Real, min: Real, max: Real| {
+            let value = (value / step).round() + steps as Real;
+            (value * step).clamp(min, max)
+        };
+        match row {
+            // Back to a random split, as the counts no longer add up
+            0 => {
+                let particles = self.particles as i64 + 5 * steps as i64;
+                self.particles = particles.clamp(1, 2000) as usize;
+                self.populations.clear();
+            }
+            1 => self.params.max_velocity = notch(self.params.max_velocity, 0.1, 0.1, 5.0),
+            2 => self.params.speed = notch(self.params.speed, 0.1, 0.1, 5.0),
+            3 => self.arena.width = notch(self.arena.width, 10.0, 20.0, 1000.0),
+            4 => self.arena.height = notch(self.arena.height, 10.0, 20.0, 1000.0),
+            5 => {
+                let Some(current) = rule_sets
+                    .iter()
+                    .position(|rules| *rules == self.params.rules)
+                else {
+                    return;
+                };
+                let next = (current as i32 + steps).rem_euclid(rule_sets.len() as i32);
+                self.params.rules = rule_sets[next as usize].clone();
+                // Counts for the hands of the old rules make no sense for new ones
+                self.populations.clear();
+            }
+            _ => {
+                let current = PLACEMENTS.iter().position(|&p| p == self.placement);
+                let next =
+                    (current.unwrap_or(0) as i32 + steps).rem_euclid(PLACEMENTS.len() as i32);
+                self.placement = PLACEMENTS[next as usize];
+            }
+        }
+    }
+}
+
+// Rows of the settings menu
+pub const MENU_ROWS: usize = 7;
+
+// Every placement, in the order the settings menu goes through them
+const PLACEMENTS: [Placement; 3] = [Placement::Random, Placement::Corners, Placement::Rings];
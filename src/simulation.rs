@@ -0,0 +1,818 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use bracket_lib::prelude::RandomNumberGenerator;
+
+use crate::collision::{self, BroadPhase, BroadPhaseKind};
+use crate::forces::{self, Field, Well};
+use crate::obstacle::Obstacle;
+use crate::physics::{self, Particle, Real, Vec2f, PARTICLE_RADIUS};
+use crate::pickup::{Effect, Pickup, Power, MAX_PICKUPS, PICKUP_RADIUS, POWERS};
+use crate::pool::{Handle, Pool};
+use crate::portal::Portal;
+use crate::rules::{Hand, Rules};
+use crate::steering;
+use crate::zone::{self, Zone};
+
+// Particles in a classic match, which fills a 60x60 screen
+pub const NUM_PARTICLES: usize = 25;
+const CLASSIC_SIZE: Real = 60.0;
+
+// Ticks of population counts kept around for the graph
+const POPULATION_HISTORY: usize = 240;
+
+// Ticks a particle that was just won over is drawn lit up, and the one that
+// won it over ringed
+pub const FLASH_TICKS: u32 = 8;
+pub const RING_TICKS: u32 = 12;
+
+// Narrowest the walls of a shrinking arena close in to, and how many times
+// the room its particles take up they leave them at least
+const MIN_SHRUNK_SIZE: Real = 10.0;
+const SHRUNK_ROOM: Real = 3.0;
+
+// Tiles particles can see each other from, when they steer
+const DEFAULT_PERCEPTION: Real = 12.0;
+
+// Tries at placing a random particle clear of the obstacles before it is left
+// inside one, for maps with little room left
+const PLACEMENT_ATTEMPTS: usize = 100;
+// How far the clusters of hands placed at corners sit from the middle of the
+// arena, and how far they spread, as shares of its shorter side
+const CORNER_DISTANCE: Real = 0.32;
+const CLUSTER_RADIUS: Real = 0.14;
+// Share of the shorter side of the arena the outermost ring reaches across
+const RINGS_SIZE: Real = 0.95;
+
+// What happens to particles at the edges of the arena
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Boundary {
+    // They bounce off walls
+    #[default]
+    Walls,
+    // They leave by one edge and come back in by the opposite one, so the
+    // arena is the surface of a torus and has no edges to hide against
+    Wrap,
+}
+
+// Where the particles of a new match start out
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Placement {
+    // Anywhere in the arena, whatever their hand
+    #[default]
+    Random,
+    // Each hand in a cluster of its own, at the corners of a shape with as
+    // many corners as there are hands: a triangle in the classic game
+    Corners,
+    // Each hand in a ring of its own around the middle of the arena, the
+    // first hand innermost
+    Rings,
+}
+
+// The area particles move in, from the origin to (width, height)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Arena {
+    pub width: Real,
+    pub height: Real,
+    pub boundary: Boundary,
+    // How far the walls have closed in from every edge, in a shrinking arena
+    pub inset: Real,
+}
+
+impl Arena {
+    pub fn full_screen() -> Self {
+        Arena {
+            width: CLASSIC_SIZE,
+            height: CLASSIC_SIZE,
+            boundary: Boundary::Walls,
+            inset: 0.0,
+        }
+    }
+
+    pub fn wraps(&self) -> bool {
+        self.boundary == Boundary::Wrap
+    }
+
+    // Where the walls are now, as the top left and bottom right corners
+    // between them. A wrapping arena has none to close in.
+    pub fn walls(&self) -> (Vec2f, Vec2f) {
+        let inset = if self.wraps() { 0.0 } else { self.inset };
+        (
+            Vec2f { x: inset, y: inset },
+            Vec2f {
+                x: self.width - inset,
+                y: self.height - inset,
+            },
+        )
+    }
+
+    // Moves the walls `distance` further in on every side, until they are
+    // `narrowest` apart
+    pub fn close_in(&mut self, distance: Real, narrowest: Real) {
+        let furthest = ((self.width.min(self.height) - narrowest) / 2.0).max(0.0);
+        self.inset = (self.inset + distance).min(furthest);
+    }
+
+    // The shortest way from `from` to `to`. In a wrapping arena that can be
+    // out through one edge and in through the opposite one.
+    pub fn offset(&self, from: Vec2f, to: Vec2f) -> Vec2f {
+        let offset = to.minus(from);
+        if !self.wraps() {
+            return offset;
+        }
+        let shortest = |d: Real, size: Real| d - size * (d / size).round();
+        Vec2f {
+            x: shortest(offset.x, self.width),
+            y: shortest(offset.y, self.height),
+        }
+    }
+
+    // Where `position` ends up once brought back inside a wrapping arena
+    pub fn wrap(&self, position: Vec2f) -> Vec2f {
+        // A hair below zero comes out as the size itself, which is outside
+        let wrap = |v: Real, size: Real| {
+            let v = v.rem_euclid(size);
+            if v < size {
+                v
+            } else {
+                0.0
+            }
+        };
+        Vec2f {
+            x: wrap(position.x, self.width),
+            y: wrap(position.y, self.height),
+        }
+    }
+
+    // How many particles fill this arena as densely as the classic game
+    // fills the screen
+    pub fn population(&self) -> usize {
+        let screen = Arena::full_screen();
+        let share = (self.width * self.height) / (screen.width * screen.height);
+        (NUM_PARTICLES as Real * share).round() as usize
+    }
+
+    pub fn contains(&self, position: &Vec2f) -> bool {
+        (0.0..self.width).contains(&position.x) && (0.0..self.height).contains(&position.y)
+    }
+}
+
+// A particle that changed hands, and the one that won it over
+#[derive(Copy, Clone, Debug)]
+pub struct Conversion {
+    pub position: Vec2f,
+    pub winner: Handle,
+    pub loser: Handle,
+    // What the loser played before
+    pub lost: Hand,
+}
+
+// Knobs on the rules of the simulation. The defaults are the classic game;
+// lab mode changes them while it runs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Params {
+    // Multiplier on every particle's velocity
+    pub speed: Real,
+    // Chance that a hand beating another one converts it
+    pub conversion_chance: Real,
+    // Ticks a freshly converted particle can't be converted again
+    pub immunity_ticks: u32,
+    // Hits from hands that beat it a particle takes before it is converted
+    pub hit_points: u32,
+    // Fastest new particles move along each axis, before `speed`
+    pub max_velocity: Real,
+    // Radius of new particles
+    pub radius: Real,
+    // How far the radii of randomly placed particles vary either side of
+    // `radius`, as a share of it. Their mass goes with their size.
+    pub size_spread: Real,
+    // Share of their closing speed two particles keep when they bounce off
+    // each other: 1 for perfectly elastic collisions, less to have them lose
+    // energy and clump together
+    pub restitution: Real,
+    pub broad_phase: BroadPhaseKind,
+    // Which hands particles play and which wins over which. Shared, as a
+    // rule set can be large and every match of a run plays by the same one.
+    pub rules: Arc<Rules>,
+    // What particles bounce off inside the arena, from a map file. Shared
+    // like the rules.
+    pub obstacles: Arc<Vec<Obstacle>>,
+    // Tiles the walls close in by on every side each tick, for matches where
+    // everyone is driven towards the middle
+    pub shrink: Real,
+    // How much particles speed up each tick towards what they beat and away
+    // from what beats them, and how far they can see it from. No steering
+    // at all unless above zero.
+    pub steering: Real,
+    pub perception: Real,
+    // How much particles flock with the ones of their own hand they can see:
+    // matching their velocity, heading for the middle of them and keeping
+    // their distance. No flocking unless some are above zero.
+    pub alignment: Real,
+    pub cohesion: Real,
+    pub separation: Real,
+    // Chance that a particle takes up another hand at random each tick. Any
+    // at all keeps hands from dying out for good, so such matches never end.
+    pub mutation: Real,
+    // Forces pushing on every particle in their part of the arena each tick
+    pub fields: Arc<Vec<Field>>,
+    // Parts of the arena where one hand moves faster or slower, from a map
+    // file like the obstacles
+    pub zones: Arc<Vec<Zone>>,
+    // Pairs of tiles particles go in one of and come out of the other, from a
+    // map file like the obstacles
+    pub portals: Arc<Vec<Portal>>,
+    // Chance that a pickup turns up on the field each tick, while there is
+    // room for one
+    pub pickup_chance: Real,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            speed: 1.0,
+            conversion_chance: 1.0,
+            immunity_ticks: 0,
+            hit_points: 1,
+            max_velocity: 1.0,
+            radius: PARTICLE_RADIUS,
+            size_spread: 0.0,
+            restitution: 1.0,
+            broad_phase: BroadPhaseKind::default(),
+            rules: Arc::new(Rules::default()),
+            obstacles: Arc::new(Vec::new()),
+            shrink: 0.0,
+            steering: 0.0,
+            perception: DEFAULT_PERCEPTION,
+            alignment: 0.0,
+            cohesion: 0.0,
+            separation: 0.0,
+            mutation: 0.0,
+            fields: Arc::new(Vec::new()),
+            zones: Arc::new(Vec::new()),
+            portals: Arc::new(Vec::new()),
+            pickup_chance: 0.0,
+        }
+    }
+}
+
+// One match on its own: the particles, the rules they play by and everything
+// needed to advance them. Several can run side by side.
+#[derive(Clone)]
+pub struct Simulation {
+    pub arena: Arena,
+    pub particles: Pool<Particle>,
+    pub params: Params,
+    pub history: VecDeque<Vec<usize>>,
+    pub ticks: u64,
+    // Where particles changed hands during the last tick
+    pub conversions: Vec<Conversion>,
+    // Powers waiting on the field to be taken
+    pub pickups: Vec<Pickup>,
+    // Wells pulling on the particles, like the one the mouse holds down. They
+    // stay until taken away.
+    pub wells: Vec<Well>,
+    // Where each particle was before the last tick, for drawing in between
+    previous: HashMap<Handle, Vec2f>,
+    rng: RandomNumberGenerator,
+    broad_phase: BroadPhase,
+}
+
+impl Simulation {
+    pub fn new(arena: Arena, params: Params, rng: RandomNumberGenerator) -> Self {
+        Simulation {
+            arena,
+            particles: Pool::with_capacity(0),
+            broad_phase: BroadPhase::new(params.broad_phase),
+            params,
+            history: VecDeque::with_capacity(POPULATION_HISTORY),
+            ticks: 0,
+            conversions: Vec::new(),
+            pickups: Vec::new(),
+            wells: Vec::new(),
+            previous: HashMap::new(),
+            rng,
+        }
+    }
+
+    // Adds `count` particles with random positions, velocities and hands, all
+    // drawn from the simulation's generator.
+    pub fn spawn_random(&mut self, count: usize) {
+        self.particles.reserve(count);
+        for _ in 0..count {
+            let particle = self.random_particle();
+            self.particles.insert(particle);
+        }
+    }
+
+    // Adds one particle exactly where and how the caller wants it, sized by
+    // the current params
+    pub fn spawn(&mut self, hand: Hand, position: Vec2f, velocity: Vec2f) -> Handle {
+        self.particles.insert(Particle {
+            position,
+            velocity,
+            hand,
+            immunity: 0,
+            damage: 0,
+            flash: 0,
+            ring: 0,
+            effect: None,
+            radius: self.params.radius,
+            mass: physics::mass_of(self.params.radius),
+        })
+    }
+
+    // Adds a particle of `hand` at `position`, heading off in a random
+    // direction like the ones spawned at the start
+    pub fn spawn_moving(&mut self, hand: Hand, position: Vec2f) -> Handle {
+        let velocity = self.random_particle().velocity;
+        self.spawn(hand, position, velocity)
+    }
+
+    // Removes a particle for good. Handles are never reused, so anything
+    // still holding this one just finds it gone.
+    pub fn despawn(&mut self, handle: Handle) -> Option<Particle> {
+        self.previous.remove(&handle);
+        self.particles.remove(handle)
+    }
+
+    // A particle drawn afresh until it lands clear of the obstacles, so
+    // matches without any deal the same particles they always did
+    fn random_particle(&mut self) -> Particle {
+        let mut particle = Particle::random(&mut self.rng, &self.arena, &self.params);
+        for _ in 1..PLACEMENT_ATTEMPTS {
+            let obstacles = &self.params.obstacles;
+            if !obstacles
+                .iter()
+                .any(|o| o.contains(&particle.position, self.ticks))
+            {
+                break;
+            }
+            particle = Particle::random(&mut self.rng, &self.arena, &self.params);
+        }
+        particle.velocity = particle.velocity.product(self.params.speed);
+        particle
+    }
+
+    // Spawns `counts[i]` particles of the i-th hand, placed by `placement`.
+    // They head off in random directions like the ones of spawn_random.
+    pub fn spawn_populations(&mut self, counts: &[usize], placement: Placement) {
+        let hands: Vec<Hand> = self.params.rules.hands().collect();
+        self.particles.reserve(counts.iter().sum());
+        for (i, (&hand, &count)) in hands.iter().zip(counts).enumerate() {
+            for _ in 0..count {
+                let mut particle = self.random_particle();
+                particle.hand = hand;
+                if let Some(position) = self.placed(placement, i, hands.len()) {
+                    particle.position = position;
+                }
+                self.particles.insert(particle);
+            }
+        }
+    }
+
+    // A spot in the part of the arena `placement` gives the i-th of `hands`
+    // hands, clear of the obstacles if one comes up soon enough. None if
+    // every hand goes anywhere.
+    fn placed(&mut self, placement: Placement, i: usize, hands: usize) -> Option<Vec2f> {
+        let middle = Vec2f {
+            x: self.arena.width / 2.0,
+            y: self.arena.height / 2.0,
+        };
+        let side = self.arena.width.min(self.arena.height);
+        let share = std::f64::consts::TAU as Real / hands as Real;
+        let mut position = middle;
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            let angle = self.rng.range(0.0, std::f64::consts::TAU as Real);
+            let (center, inner, outer) = match placement {
+                Placement::Random => return None,
+                // The first corner is straight up, and the rest follow
+                // clockwise
+                Placement::Corners => {
+                    let corner = share * i as Real - std::f64::consts::FRAC_PI_2 as Real;
+                    let offset = Vec2f {
+                        x: corner.cos(),
+                        y: corner.sin(),
+                    };
+                    let center = middle.plus(offset.product(CORNER_DISTANCE * side));
+                    (center, 0.0, CLUSTER_RADIUS * side)
+                }
+                Placement::Rings => {
+                    let width = RINGS_SIZE * side / 2.0 / hands as Real;
+                    (middle, width * i as Real, width * (i + 1) as Real)
+                }
+            };
+            // Even over the area between the two radii
+            let t: Real = self.rng.range(0.0, 1.0);
+            let distance = (inner * inner + t * (outer * outer - inner * inner)).sqrt();
+            position = center.plus(Vec2f {
+                x: angle.cos() * distance,
+                y: angle.sin() * distance,
+            });
+            if !self
+                .params
+                .obstacles
+                .iter()
+                .any(|o| o.contains(&position, self.ticks))
+            {
+                break;
+            }
+        }
+        Some(position)
+    }
+
+    // Spawns or removes random particles of `hand` until there are `count`
+    pub fn set_population(&mut self, hand: Hand, count: usize) {
+        let mut handles: Vec<Handle> = self
+            .particles
+            .iter()
+            .filter(|(_, particle)| particle.hand == hand)
+            .map(|(handle, _)| handle)
+            .collect();
+
+        while handles.len() > count {
+            let i = self.rng.range(0, handles.len());
+            self.despawn(handles.swap_remove(i));
+        }
+        for _ in handles.len()..count {
+            let mut particle = self.random_particle();
+            particle.hand = hand;
+            self.particles.insert(particle);
+        }
+    }
+
+    // Starts the random numbers over from a seed drawn from them, and returns
+    // it. A match rebuilt from its particles and that seed plays on exactly
+    // like this one, as the broad phase starts over too.
+    pub fn reseed(&mut self) -> u64 {
+        let seed = self.rng.next_u64();
+        self.rng = RandomNumberGenerator::seeded(seed);
+        self.broad_phase = BroadPhase::new(self.params.broad_phase);
+        seed
+    }
+
+    // Rescales every velocity, so the change shows up right away
+    pub fn set_speed(&mut self, speed: Real) {
+        let ratio = speed / self.params.speed;
+        for particle in self.particles.values_mut() {
+            particle.velocity = particle.velocity.product(ratio);
+        }
+        self.params.speed = speed;
+    }
+
+    // Advances the simulation by one physics tick and returns how many
+    // particles play each hand afterwards
+    pub fn step(&mut self) -> Vec<usize> {
+        profile_scope!("step");
+
+        // Before anything else, so the broad phase knows where the walls are.
+        // They stop closing in while the particles still have room to move.
+        if self.params.shrink > 0.0 {
+            let taken: Real = self
+                .particles
+                .values()
+                .map(|p| std::f64::consts::PI as Real * p.radius * p.radius)
+                .sum();
+            let narrowest = (SHRUNK_ROOM * taken).sqrt().max(MIN_SHRUNK_SIZE);
+            self.arena.close_in(self.params.shrink, narrowest);
+        }
+        if steering::steers(&self.params) {
+            steering::steer(&mut self.particles, &self.arena, &self.params);
+        }
+        if !self.wells.is_empty() || !self.params.fields.is_empty() {
+            let (arena, params) = (&self.arena, &self.params);
+            forces::apply(&mut self.particles, arena, params, &self.wells, self.ticks);
+        }
+
+        self.previous.clear();
+        self.previous.extend(
+            self.particles
+                .iter()
+                .map(|(handle, p)| (handle, p.position)),
+        );
+
+        // Particles in a zone of their hand go faster for the tick, by where
+        // they start it, and back to their own speed once they have moved.
+        // The broad phase then knows how far they can get.
+        let boosted: Vec<(Handle, Real)> = if self.params.zones.is_empty() {
+            Vec::new()
+        } else {
+            let zones = &self.params.zones;
+            self.particles
+                .iter()
+                .map(|(handle, p)| (handle, zone::boost(zones, p)))
+                .filter(|&(_, boost)| boost != 1.0)
+                .collect()
+        };
+        for &(handle, boost) in &boosted {
+            let particle = &mut self.particles[handle];
+            particle.velocity = particle.velocity.product(boost);
+        }
+
+        // Candidates are gathered before anything moves, narrowed down to
+        // the pairs within reach this tick, and only those are tested for
+        // contact (possibly in parallel) once everyone has moved. Contacts
+        // are then resolved in pair order.
+        if self.broad_phase.kind() != self.params.broad_phase {
+            self.broad_phase = BroadPhase::new(self.params.broad_phase);
+        }
+        self.broad_phase.update(&self.particles, &self.arena);
+        let candidates =
+            collision::prune_out_of_reach(&self.particles, &self.arena, self.broad_phase.pairs());
+
+        // Obstacles that move are where they are by the end of the tick,
+        // which is where they are drawn
+        let now = self.ticks + 1;
+        {
+            profile_scope!("movement");
+            for particle in self.particles.values_mut() {
+                particle.update_position();
+                for obstacle in self.params.obstacles.iter() {
+                    obstacle.bounce(particle, now);
+                }
+                particle.check_wall_collision(&self.arena);
+                particle.immunity = particle.immunity.saturating_sub(1);
+                particle.flash = particle.flash.saturating_sub(1);
+                particle.ring = particle.ring.saturating_sub(1);
+                if let Some(effect) = &mut particle.effect {
+                    effect.ticks = effect.ticks.saturating_sub(1);
+                    if effect.ticks == 0 {
+                        let power = effect.power;
+                        particle.effect = None;
+                        power.wear_off(particle);
+                    }
+                }
+            }
+            for &(handle, boost) in &boosted {
+                let particle = &mut self.particles[handle];
+                particle.velocity = particle.velocity.product(1.0 / boost);
+            }
+        }
+
+        let contacts =
+            collision::find_contacts(&self.particles, &self.previous, &self.arena, &candidates);
+        let resolved = collision::resolve_contacts(
+            &mut self.particles,
+            &self.previous,
+            &self.arena,
+            &contacts,
+            self.params.restitution,
+        );
+        // Collisions can push particles into obstacles, which push them back
+        if !self.params.obstacles.is_empty() {
+            for &(lhs, rhs) in &resolved {
+                for handle in [lhs, rhs] {
+                    let particle = &mut self.particles[handle];
+                    for obstacle in self.params.obstacles.iter() {
+                        obstacle.bounce(particle, now);
+                    }
+                    particle.check_wall_collision(&self.arena);
+                }
+            }
+        }
+        self.conversions.clear();
+        for (lhs, rhs) in resolved {
+            self.apply_match(lhs, rhs);
+        }
+        if !self.params.portals.is_empty() {
+            self.teleport();
+        }
+        if self.params.mutation > 0.0 {
+            self.mutate();
+        }
+        if self.params.pickup_chance > 0.0 || !self.pickups.is_empty() {
+            self.update_pickups();
+        }
+
+        self.ticks += 1;
+        let counts = self.counts();
+        if self.history.len() == POPULATION_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(counts.clone());
+        counts
+    }
+
+    // Moves every particle that went into a portal this tick out of the other
+    // end. Where it was before the tick moves with it, so it is drawn coming
+    // out rather than crossing the arena.
+    fn teleport(&mut self) {
+        for handle in self.particles.handles() {
+            let particle = &mut self.particles[handle];
+            let Some(start) = self.previous.get_mut(&handle) else {
+                continue;
+            };
+            let exit = self.params.portals.iter().find_map(|portal| {
+                portal.exit(*start, particle.position, particle.radius, &self.arena)
+            });
+            if let Some(exit) = exit {
+                *start = exit.minus(self.arena.offset(*start, particle.position));
+                particle.position = exit;
+                particle.check_wall_collision(&self.arena);
+            }
+        }
+    }
+
+    // Settles a collision between two particles: the loser takes on the
+    // winner's hand, unless it is still immune from its last conversion or
+    // shielded, or the conversion roll fails.
+    fn apply_match(&mut self, lhs: Handle, rhs: Handle) {
+        let (l, r) = (self.particles[lhs].hand, self.particles[rhs].hand);
+        let rules = &self.params.rules;
+        let (hand, winner, loser) = if rules.beats(l, r) {
+            (l, lhs, rhs)
+        } else if rules.beats(r, l) {
+            (r, rhs, lhs)
+        } else {
+            return;
+        };
+
+        if !self.particles[loser].can_be_hit() {
+            return;
+        }
+        if self.params.conversion_chance < 1.0
+            && self.rng.range(0.0, 1.0) >= self.params.conversion_chance
+        {
+            return;
+        }
+        let particle = &mut self.particles[loser];
+        particle.damage += 1;
+        if particle.damage < self.params.hit_points {
+            return;
+        }
+        self.convert(winner, loser, hand);
+    }
+
+    // Has `loser` take up `hand`, won over by `winner`
+    fn convert(&mut self, winner: Handle, loser: Handle, hand: Hand) {
+        let particle = &mut self.particles[loser];
+        let lost = particle.hand;
+        particle.hand = hand;
+        particle.immunity = self.params.immunity_ticks;
+        particle.damage = 0;
+        particle.flash = FLASH_TICKS;
+        let position = particle.position;
+        self.particles[winner].ring = RING_TICKS;
+        self.conversions.push(Conversion {
+            position,
+            winner,
+            loser,
+            lost,
+        });
+    }
+
+    // Puts a new pickup on the field now and then, and gives each one a
+    // particle touches to the first of them
+    fn update_pickups(&mut self) {
+        if self.pickups.len() < MAX_PICKUPS && self.rng.range(0.0, 1.0) < self.params.pickup_chance
+        {
+            let power = POWERS[self.rng.range(0, POWERS.len())];
+            let position = self.free_spot();
+            self.pickups.push(Pickup { position, power });
+        }
+
+        let mut i = 0;
+        while i < self.pickups.len() {
+            let pickup = self.pickups[i];
+            let taker = self.particles.iter().find(|(_, particle)| {
+                let offset = self.arena.offset(particle.position, pickup.position);
+                offset.norm() < particle.radius + PICKUP_RADIUS
+            });
+            match taker {
+                Some((handle, _)) => {
+                    self.pickups.remove(i);
+                    self.empower(handle, pickup.power);
+                }
+                None => i += 1,
+            }
+        }
+    }
+
+    // A random spot between the walls, clear of the obstacles if one comes
+    // up soon enough
+    fn free_spot(&mut self) -> Vec2f {
+        let (min, max) = self.arena.walls();
+        let mut spot = min;
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            spot = Vec2f {
+                x: self.rng.range(min.x, max.x),
+                y: self.rng.range(min.y, max.y),
+            };
+            if !self
+                .params
+                .obstacles
+                .iter()
+                .any(|o| o.contains(&spot, self.ticks))
+            {
+                break;
+            }
+        }
+        spot
+    }
+
+    // Gives `power` to a particle, in place of whatever it had before
+    fn empower(&mut self, handle: Handle, power: Power) {
+        let particle = &mut self.particles[handle];
+        if let Some(effect) = particle.effect.take() {
+            effect.power.wear_off(particle);
+        }
+        power.take(particle);
+        if power.duration() > 0 {
+            particle.effect = Some(Effect {
+                power,
+                ticks: power.duration(),
+            });
+        }
+
+        let Some(reach) = power.aura() else {
+            return;
+        };
+        let (hand, position) = (particle.hand, particle.position);
+        let rules = &self.params.rules;
+        let won: Vec<Handle> = self
+            .particles
+            .iter()
+            .filter(|(_, other)| rules.beats(hand, other.hand) && other.can_be_hit())
+            .filter(|(_, other)| self.arena.offset(position, other.position).norm() < reach)
+            .map(|(other, _)| other)
+            .collect();
+        for loser in won {
+            self.convert(handle, loser, hand);
+        }
+    }
+
+    // Gives each particle its chance to take up one of the other hands
+    fn mutate(&mut self) {
+        let hands = self.params.rules.len();
+        if hands < 2 {
+            return;
+        }
+        for particle in self.particles.values_mut() {
+            if self.rng.range(0.0, 1.0) >= self.params.mutation {
+                continue;
+            }
+            let other = (particle.hand.index() + self.rng.range(1, hands)) % hands;
+            particle.hand = Hand::from_index(other).unwrap();
+            particle.damage = 0;
+        }
+    }
+
+    // The hand that won the match, once every particle plays it. Never while
+    // particles can mutate, as the others can always come back.
+    pub fn winner(&self, counts: &[usize]) -> Option<Hand> {
+        if self.params.mutation > 0.0 {
+            return None;
+        }
+        winner(counts)
+    }
+
+    // The particle drawn over `position`, if any
+    pub fn particle_at(&self, position: &Vec2f) -> Option<Handle> {
+        self.particles
+            .iter()
+            .find(|(_, particle)| particle.position.distance(position) < particle.radius)
+            .map(|(handle, _)| handle)
+    }
+
+    // The particles `alpha` of the way through the last tick, from where
+    // they were before it (0) to where they are now (1). Particles that
+    // weren't there before it are drawn where they are.
+    pub fn interpolated(&self, alpha: Real) -> Vec<Particle> {
+        self.particles
+            .iter()
+            .map(|(handle, particle)| {
+                let mut particle = *particle;
+                if let Some(&previous) = self.previous.get(&handle) {
+                    // Across the edge of a wrapping arena, rather than back
+                    // through the middle
+                    let moved = self.arena.offset(previous, particle.position);
+                    particle.position = previous.plus(moved.product(alpha));
+                    if self.arena.wraps() {
+                        particle.position = self.arena.wrap(particle.position);
+                    }
+                }
+                particle
+            })
+            .collect()
+    }
+
+    // How many particles play each hand of the rules
+    pub fn counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.params.rules.len()];
+
+        self.particles.values().for_each(|p| {
+            counts[p.hand.index()] += 1;
+        });
+
+        counts
+    }
+}
+
+// The hand left standing, once every particle plays it
+pub fn winner(counts: &[usize]) -> Option<Hand> {
+    let total: usize = counts.iter().sum();
+    counts
+        .iter()
+        .position(|&n| n == total)
+        .and_then(Hand::from_index)
+}
@@ -0,0 +1,370 @@
+use std::io;
+
+use rock_paper_scissors::{physics, Hand, Particle, Real, Vec2f};
+
+// Compact encoding of a stream of simulation snapshots. Positions and radii
+// are quantized to 1/QUANTUM of a tile; a keyframe stores them in full and the
+// frames after it only store how far each particle moved and which particles
+// changed hands. A new keyframe is written periodically, whenever the number
+// of particles changes, and whenever a particle moved too far for a delta or
+// changed size.
+//
+//   keyframe: KEYFRAME u8, tick u64, count u32,
+//             (x i32, y i32, radius u16, hand u8) each
+//   delta:    DELTA u8, tick u64, (dx i8, dy i8) each, changes u32,
+//             (particle u32, hand u8) per change
+//
+// All numbers are little-endian.
+const QUANTUM: Real = 64.0;
+const KEYFRAME_INTERVAL: u32 = 64;
+
+const KEYFRAME: u8 = 0;
+const DELTA: u8 = 1;
+
+pub struct Frame {
+    pub tick: u64,
+    pub particles: Vec<Particle>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct Quantized {
+    x: i32,
+    y: i32,
+    radius: u16,
+    hand: u8,
+}
+
+impl Quantized {
+    fn new(particle: &Particle) -> Self {
+        // Arenas and particles are far smaller than these can hold, so
+        // nothing is clamped in practice
+        let quantize = |v: Real| (v * QUANTUM).round() as i32;
+        Quantized {
+            x: quantize(particle.position.x),
+            y: quantize(particle.position.y),
+            radius: (particle.radius * QUANTUM).round() as u16,
+            hand: particle.hand.index() as u8,
+        }
+    }
+
+    fn particle(&self) -> io::Result<Particle> {
+        let radius = self.radius as Real / QUANTUM;
+        Ok(Particle {
+            position: Vec2f {
+                x: self.x as Real / QUANTUM,
+                y: self.y as Real / QUANTUM,
+            },
+            velocity: Vec2f { x: 0.0, y: 0.0 },
+            hand: Hand::from_index(self.hand as usize).ok_or_else(|| invalid("unknown hand"))?,
+            immunity: 0,
+            damage: 0,
+            flash: 0,
+            ring: 0,
+            effect: None,
+            radius,
+            mass: physics::mass_of(radius),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct SnapshotEncoder {
+    previous: Vec<Quantized>,
+    since_keyframe: Option<u32>,
+}
+
+impl SnapshotEncoder {
+    // Makes the next frame a keyframe, e.g. so it can be decoded on its own
+    pub fn force_keyframe(&mut self) {
+        self.since_keyframe = None;
+    }
+
+    pub fn encode<'a>(
+        &mut self,
+        tick: u64,
+        particles: impl Iterator<Item = &'a Particle>,
+        out: &mut Vec<u8>,
+    ) {
+        let current: Vec<Quantized> = particles.map(Quantized::new).collect();
+
+        let deltas = match self.since_keyframe {
+            Some(frames) if frames < KEYFRAME_INTERVAL && current.len() == self.previous.len() => {
+                deltas(&self.previous, &current)
+            }
+            _ => None,
+        };
+
+        match deltas {
+            Some(deltas) => {
+                out.push(DELTA);
+                out.extend_from_slice(&tick.to_le_bytes());
+                for (dx, dy) in deltas {
+                    out.extend_from_slice(&dx.to_le_bytes());
+                    out.extend_from_slice(&dy.to_le_bytes());
+                }
+
+                let changes: Vec<(u32, u8)> = self
+                    .previous
+                    .iter()
+                    .zip(&current)
+                    .enumerate()
+                    .filter(|(_, (before, after))| before.hand != after.hand)
+                    .map(|(i, (_, after))| (i as u32, after.hand))
+                    .collect();
+                out.extend_from_slice(&(changes.len() as u32).to_le_bytes());
+                for (i, hand) in changes {
+                    out.extend_from_slice(&i.to_le_bytes());
+                    out.push(hand);
+                }
+
+                self.since_keyframe = self.since_keyframe.map(|frames| frames + 1);
+            }
+            None => {
+                out.push(KEYFRAME);
+                out.extend_from_slice(&tick.to_le_bytes());
+                out.extend_from_slice(&(current.len() as u32).to_le_bytes());
+                for particle in &current {
+                    out.extend_from_slice(&particle.x.to_le_bytes());
+                    out.extend_from_slice(&particle.y.to_le_bytes());
+                    out.extend_from_slice(&particle.radius.to_le_bytes());
+                    out.push(particle.hand);
+                }
+
+                self.since_keyframe = Some(0);
+            }
+        }
+
+        self.previous = current;
+    }
+}
+
+// Per-particle movement since the previous frame, if every particle's fits
+// and none of them changed size
+fn deltas(previous: &[Quantized], current: &[Quantized]) -> Option<Vec<(i8, i8)>> {
+    previous
+        .iter()
+        .zip(current)
+        .map(|(before, after)| {
+            if before.radius != after.radius {
+                return None;
+            }
+            let dx = i8::try_from(after.x.checked_sub(before.x)?).ok()?;
+            let dy = i8::try_from(after.y.checked_sub(before.y)?).ok()?;
+            Some((dx, dy))
+        })
+        .collect()
+}
+
+#[derive(Default)]
+pub struct SnapshotDecoder {
+    previous: Option<Vec<Quantized>>,
+}
+
+impl SnapshotDecoder {
+    pub fn decode(&mut self, cursor: &mut &[u8]) -> io::Result<Frame> {
+        let [kind] = take(cursor)?;
+        let tick = u64::from_le_bytes(take(cursor)?);
+
+        let current = match kind {
+            KEYFRAME => {
+                let count = u32::from_le_bytes(take(cursor)?);
+                let mut current = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let x = i32::from_le_bytes(take(cursor)?);
+                    let y = i32::from_le_bytes(take(cursor)?);
+                    let radius = u16::from_le_bytes(take(cursor)?);
+                    let [hand] = take(cursor)?;
+                    current.push(Quantized { x, y, radius, hand });
+                }
+                current
+            }
+            DELTA => {
+                let mut current = self
+                    .previous
+                    .take()
+                    .ok_or_else(|| invalid("delta frame without a keyframe"))?;
+                for particle in current.iter_mut() {
+                    let [dx, dy] = take(cursor)?;
+                    particle.x = particle.x.wrapping_add(dx as i8 as i32);
+                    particle.y = particle.y.wrapping_add(dy as i8 as i32);
+                }
+
+                let changes = u32::from_le_bytes(take(cursor)?);
+                for _ in 0..changes {
+                    let i = u32::from_le_bytes(take(cursor)?) as usize;
+                    let [hand] = take(cursor)?;
+                    current
+                        .get_mut(i)
+                        .ok_or_else(|| invalid("hand change for an unknown particle"))?
+                        .hand = hand;
+                }
+                current
+            }
+            _ => return Err(invalid("unknown frame kind")),
+        };
+
+        let particles = current
+            .iter()
+            .map(Quantized::particle)
+            .collect::<io::Result<_>>()?;
+        self.previous = Some(current);
+        Ok(Frame { tick, particles })
+    }
+}
+
+fn take<const N: usize>(cursor: &mut &[u8]) -> io::Result<[u8; N]> {
+    if cursor.len() < N {
+        return Err(invalid("frame data is truncated"));
+    }
+    let (head, tail) = cursor.split_at(N);
+    *cursor = tail;
+    Ok(head.try_into().unwrap())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use rock_paper_scissors::PARTICLE_RADIUS;
+
+    use super::*;
+
+    // A particle where snapshots keep it exactly, with every position a
+    // whole number of quanta
+    fn particle(x: Real, y: Real, hand: usize) -> Particle {
+        Particle {
+            position: Vec2f { x, y },
+            velocity: Vec2f { x: 0.0, y: 0.0 },
+            hand: Hand::from_index(hand).unwrap(),
+            immunity: 0,
+            damage: 0,
+            effect: None,
+            flash: 0,
+            ring: 0,
+            radius: PARTICLE_RADIUS,
+            mass: physics::mass_of(PARTICLE_RADIUS),
+        }
+    }
+
+    // What a snapshot keeps of a particle
+    fn kept(particle: &Particle) -> (Real, Real, Real, usize) {
+        (
+            particle.position.x,
+            particle.position.y,
+            particle.radius,
+            particle.hand.index(),
+        )
+    }
+
+    // Encodes `frames` one after another, then decodes them all back and
+    // checks they come out as they went in. Returns each frame's kind.
+    fn round_trip(frames: &[Vec<Particle>]) -> Vec<u8> {
+        let mut encoder = SnapshotEncoder::default();
+        let mut encoded = Vec::new();
+        for (tick, particles) in frames.iter().enumerate() {
+            let mut out = Vec::new();
+            encoder.encode(tick as u64, particles.iter(), &mut out);
+            encoded.push(out);
+        }
+
+        let mut decoder = SnapshotDecoder::default();
+        for (tick, (particles, bytes)) in frames.iter().zip(&encoded).enumerate() {
+            let mut cursor = bytes.as_slice();
+            let frame = decoder.decode(&mut cursor).unwrap();
+            assert!(cursor.is_empty(), "frame {} left bytes over", tick);
+            assert_eq!(frame.tick, tick as u64);
+            let decoded: Vec<_> = frame.particles.iter().map(kept).collect();
+            let expected: Vec<_> = particles.iter().map(kept).collect();
+            assert_eq!(decoded, expected, "frame {}", tick);
+        }
+        encoded.iter().map(|bytes| bytes[0]).collect()
+    }
+
+    #[test]
+    fn small_moves_are_deltas() {
+        let frames: Vec<Vec<Particle>> = (0..10)
+            .map(|tick| {
+                let step = tick as Real * 0.25;
+                vec![
+                    particle(10.0 + step, 20.0, 0),
+                    particle(30.0, 5.0 - step, 1),
+                    // Changes hands halfway through
+                    particle(40.0, 40.0, if tick < 5 { 2 } else { 0 }),
+                ]
+            })
+            .collect();
+        let mut kinds = vec![KEYFRAME];
+        kinds.extend([DELTA; 9]);
+        assert_eq!(round_trip(&frames), kinds);
+    }
+
+    #[test]
+    fn keyframes_come_back_periodically() {
+        let frames: Vec<Vec<Particle>> = (0..=KEYFRAME_INTERVAL + 1)
+            .map(|tick| vec![particle(tick as Real / QUANTUM, 0.0, 0)])
+            .collect();
+        let kinds = round_trip(&frames);
+        let keyframes: Vec<usize> = (0..kinds.len()).filter(|&i| kinds[i] == KEYFRAME).collect();
+        assert_eq!(keyframes, [0, KEYFRAME_INTERVAL as usize + 1]);
+    }
+
+    #[test]
+    fn long_move_forces_a_keyframe() {
+        // 2 tiles is 128 quanta, one more than a delta holds
+        let frames = [
+            vec![particle(10.0, 10.0, 0), particle(20.0, 20.0, 1)],
+            vec![particle(10.5, 10.0, 0), particle(20.0, 20.0, 1)],
+            vec![particle(10.5, 10.0, 0), particle(20.0, 22.0, 1)],
+            vec![
+                particle(10.5, 10.0, 0),
+                particle(20.0, 22.0 - 127.0 / QUANTUM, 1),
+            ],
+        ];
+        assert_eq!(round_trip(&frames), [KEYFRAME, DELTA, KEYFRAME, DELTA]);
+    }
+
+    #[test]
+    fn count_change_forces_a_keyframe() {
+        let frames = [
+            vec![particle(10.0, 10.0, 0), particle(20.0, 20.0, 1)],
+            vec![particle(10.0, 10.0, 0)],
+            vec![particle(10.0, 10.5, 0)],
+            vec![particle(10.0, 10.5, 0), particle(5.0, 5.0, 2)],
+        ];
+        assert_eq!(round_trip(&frames), [KEYFRAME, KEYFRAME, DELTA, KEYFRAME]);
+    }
+
+    #[test]
+    fn radius_change_forces_a_keyframe() {
+        let mut grown = particle(10.0, 10.0, 0);
+        grown.radius = 2.0;
+        let frames = [vec![particle(10.0, 10.0, 0)], vec![grown], vec![grown]];
+        assert_eq!(round_trip(&frames), [KEYFRAME, KEYFRAME, DELTA]);
+    }
+
+    #[test]
+    fn bad_input_is_an_error() {
+        let mut encoder = SnapshotEncoder::default();
+        let mut keyframe = Vec::new();
+        encoder.encode(0, [particle(1.0, 2.0, 0)].iter(), &mut keyframe);
+        let mut delta = Vec::new();
+        encoder.encode(1, [particle(1.0, 2.5, 0)].iter(), &mut delta);
+
+        let decode = |bytes: &[u8]| SnapshotDecoder::default().decode(&mut &bytes[..]);
+        for end in 0..keyframe.len() {
+            let err = decode(&keyframe[..end]).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        let mut unknown = keyframe.clone();
+        unknown[0] = 7;
+        assert!(decode(&unknown).is_err());
+        // A delta means nothing without the keyframe before it
+        assert!(decode(&delta).is_err());
+
+        let mut decoder = SnapshotDecoder::default();
+        decoder.decode(&mut keyframe.as_slice()).unwrap();
+        assert!(decoder.decode(&mut &delta[..delta.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use bracket_lib::prelude::*;
+use rock_paper_scissors::Vec2f;
+
+use crate::camera::Camera;
+
+// Sparks thrown out by each conversion, and the most flying at once, oldest
+// dropped first
+const SPARKS_PER_BURST: usize = 6;
+const MAX_SPARKS: usize = 600;
+// How long a spark flies for, and its speed in tiles per second
+const SPARK_MS: f32 = 400.0;
+const MIN_SPARK_SPEED: f32 = 6.0;
+const MAX_SPARK_SPEED: f32 = 14.0;
+// What a spark looks like as it burns out, from fresh to nearly gone
+const SPARK_GLYPHS: [char; 3] = ['*', '+', '·'];
+
+struct Spark {
+    x: f32,
+    y: f32,
+    dx: f32,
+    dy: f32,
+    // Time it has left
+    ms: f32,
+    color: RGB,
+}
+
+// Little glyphs that burst out of conversions and fade away. They are only
+// for show: the match never sees them, and they keep flying while paused.
+pub struct Sparks {
+    sparks: Vec<Spark>,
+    // Their own numbers, so the match's stay untouched
+    rng: RandomNumberGenerator,
+}
+
+impl Sparks {
+    pub fn new() -> Self {
+        Sparks {
+            sparks: Vec::new(),
+            rng: RandomNumberGenerator::new(),
+        }
+    }
+
+    // Throws a handful of sparks of `color` out of `position` every which way
+    pub fn burst(&mut self, position: Vec2f, color: RGB) {
+        let (x, y): (f32, f32) = (position.x as _, position.y as _);
+        for _ in 0..SPARKS_PER_BURST {
+            let angle = self.rng.range(0.0, std::f32::consts::TAU);
+            let speed = self.rng.range(MIN_SPARK_SPEED, MAX_SPARK_SPEED);
+            self.sparks.push(Spark {
+                x,
+                y,
+                dx: angle.cos() * speed,
+                dy: angle.sin() * speed,
+                ms: SPARK_MS,
+                color,
+            });
+        }
+        if self.sparks.len() > MAX_SPARKS {
+            let extra = self.sparks.len() - MAX_SPARKS;
+            self.sparks.drain(..extra);
+        }
+    }
+
+    // Moves every spark on by a frame of `frame_ms`, and lets the burnt out
+    // ones go
+    pub fn update(&mut self, frame_ms: f32) {
+        let seconds = frame_ms / 1000.0;
+        for spark in &mut self.sparks {
+            spark.x += spark.dx * seconds;
+            spark.y += spark.dy * seconds;
+            spark.ms -= frame_ms;
+        }
+        self.sparks.retain(|spark| spark.ms > 0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.sparks.clear();
+    }
+
+    // Draws the sparks `camera` sees onto the active console, fading into
+    // `floor` as they burn out
+    pub fn draw(&self, ctx: &mut BTerm, camera: &Camera, floor: RGB) {
+        let origin = camera.origin();
+        for spark in &self.sparks {
+            let position = Vec2f {
+                x: spark.x as _,
+                y: spark.y as _,
+            };
+            if !camera.sees(&position) {
+                continue;
+            }
+            let burnt = 1.0 - spark.ms / SPARK_MS;
+            let stage = (burnt * SPARK_GLYPHS.len() as f32) as usize;
+            let glyph = SPARK_GLYPHS[stage.min(SPARK_GLYPHS.len() - 1)];
+            ctx.set(
+                origin.x + spark.x as i32,
+                origin.y + spark.y as i32,
+                spark.color.lerp(floor, burnt),
+                RGB::named(BLACK),
+                to_cp437(glyph),
+            );
+        }
+    }
+}
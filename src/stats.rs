@@ -0,0 +1,259 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use rock_paper_scissors::simulation::Arena;
+use rock_paper_scissors::{Hand, Rules};
+
+// Every finished match is appended to this file in the player's directory
+pub const RUNS_FILE: &str = "runs.txt";
+
+// Matches listed on the history screen, newest first, and best scores
+const RECENT_RUNS: usize = 15;
+const BEST_RUNS: usize = 5;
+
+// One finished match, as kept in the run history:
+//
+//   run <ticks> <winner> <width> <height> <eliminations> <foreseen> <seed> <settings> <score>
+//
+// where `foreseen` counts the eliminations of a hand the prediction overlay
+// would have marked as endangered right before, `seed` is `-` for matches
+// saved or loaded midway, which no seed replays, `settings` is the
+// fingerprint of the settings it was played with in hex and `score` the
+// points its bet won. Matches from before the last three were kept have none
+// of them. Unknown lines are skipped.
+#[derive(Clone, Debug)]
+pub struct Run {
+    pub ticks: u64,
+    // Name of the winning hand
+    pub winner: String,
+    pub arena: Arena,
+    pub eliminations: u32,
+    pub foreseen: u32,
+    pub seed: Option<u64>,
+    pub settings: Option<u64>,
+    pub score: u32,
+}
+
+impl Run {
+    pub fn append(&self, path: &Path) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        write!(
+            file,
+            "run {} {} {} {} {} {}",
+            self.ticks,
+            self.winner,
+            self.arena.width,
+            self.arena.height,
+            self.eliminations,
+            self.foreseen
+        )?;
+        if let Some(settings) = self.settings {
+            let seed = self.seed.map_or("-".to_string(), |seed| seed.to_string());
+            write!(file, " {} {:016x} {}", seed, settings, self.score)?;
+        }
+        writeln!(file)
+    }
+
+    // Reads the run history, which is empty until a match finishes
+    pub fn load(path: &Path) -> io::Result<Vec<Run>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(contents.lines().filter_map(parse_run).collect()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn parse_run(line: &str) -> Option<Run> {
+    let mut words = line.split_whitespace();
+    if words.next()? != "run" {
+        return None;
+    }
+    let ticks = words.next()?.parse().ok()?;
+    let winner = words.next()?.to_string();
+    let arena = Arena {
+        width: words.next()?.parse().ok()?,
+        height: words.next()?.parse().ok()?,
+        ..Arena::full_screen()
+    };
+    let eliminations = words.next()?.parse().ok()?;
+    let foreseen = words.next()?.parse().ok()?;
+    let (seed, settings, score) = match words.next() {
+        Some(seed) => (
+            match seed {
+                "-" => None,
+                seed => Some(seed.parse().ok()?),
+            },
+            Some(u64::from_str_radix(words.next()?, 16).ok()?),
+            words.next()?.parse().ok()?,
+        ),
+        None => (None, None, 0),
+    };
+    Some(Run {
+        ticks,
+        winner,
+        arena,
+        eliminations,
+        foreseen,
+        seed,
+        settings,
+        score,
+    })
+}
+
+// Running totals of an endless loop of rounds, indexed by hand
+pub struct Tally {
+    pub wins: Vec<usize>,
+    pub ticks: u64,
+}
+
+impl Tally {
+    pub fn new(rules: &Rules) -> Self {
+        Tally {
+            wins: vec![0; rules.len()],
+            ticks: 0,
+        }
+    }
+
+    pub fn record(&mut self, winner: Hand, ticks: u64) {
+        self.wins[winner.index()] += 1;
+        self.ticks += ticks;
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.wins.iter().sum()
+    }
+
+    pub fn average_ticks(&self) -> u64 {
+        self.ticks / self.rounds().max(1) as u64
+    }
+}
+
+// Totals over the whole run history
+pub struct Summary {
+    pub rounds: usize,
+    // By hand name, the classic hands first
+    pub wins: Vec<(String, usize)>,
+    // Round lengths only compare within the classic arena
+    pub longest: Option<u64>,
+    pub fastest: Option<u64>,
+    pub eliminations: u32,
+    pub foreseen: u32,
+}
+
+impl Summary {
+    pub fn new(runs: &[Run]) -> Self {
+        let classic = Rules::classic();
+        let mut wins: Vec<(String, usize)> = classic
+            .hands()
+            .map(|hand| (classic.name(hand).to_string(), 0))
+            .collect();
+        for run in runs {
+            match wins.iter_mut().find(|(name, _)| *name == run.winner) {
+                Some((_, count)) => *count += 1,
+                None => wins.push((run.winner.clone(), 1)),
+            }
+        }
+        let classic = runs.iter().filter(|run| run.arena == Arena::full_screen());
+        Summary {
+            rounds: runs.len(),
+            wins,
+            longest: classic.clone().map(|run| run.ticks).max(),
+            fastest: classic.map(|run| run.ticks).min(),
+            eliminations: runs.iter().map(|run| run.eliminations).sum(),
+            foreseen: runs.iter().map(|run| run.foreseen).sum(),
+        }
+    }
+
+    // Share of rounds a hand won, in percent
+    pub fn win_rate(&self, wins: usize) -> f32 {
+        if self.rounds == 0 {
+            return 0.0;
+        }
+        100.0 * wins as f32 / self.rounds as f32
+    }
+
+    // Share of eliminations the prediction overlay saw coming, in percent
+    pub fn prediction_accuracy(&self) -> Option<f32> {
+        (self.eliminations > 0).then(|| 100.0 * self.foreseen as f32 / self.eliminations as f32)
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Rounds watched: {}", self.rounds)];
+        // Other hands only show up once they have won something
+        for (name, wins) in &self.wins {
+            lines.push(format!(
+                "{} wins: {} ({:.0}%)",
+                name,
+                wins,
+                self.win_rate(*wins)
+            ));
+        }
+        let ticks = |ticks: Option<u64>| ticks.map_or("-".to_string(), |t| format!("{} ticks", t));
+        lines.push(format!("Longest round: {}", ticks(self.longest)));
+        lines.push(format!("Fastest sweep: {}", ticks(self.fastest)));
+        lines.push(match self.prediction_accuracy() {
+            Some(accuracy) => format!("Prediction accuracy: {:.0}%", accuracy),
+            None => "Prediction accuracy: -".to_string(),
+        });
+        lines
+    }
+}
+
+// The latest matches of the run history and the ones whose bets won the most
+pub struct History {
+    recent: Vec<Run>,
+    best: Vec<Run>,
+}
+
+impl History {
+    pub fn new(runs: &[Run]) -> Self {
+        let recent = runs.iter().rev().take(RECENT_RUNS).cloned().collect();
+        // Quicker matches first among equal scores
+        let mut best: Vec<Run> = runs.iter().filter(|run| run.score > 0).cloned().collect();
+        best.sort_by(|a, b| b.score.cmp(&a.score).then(a.ticks.cmp(&b.ticks)));
+        best.truncate(BEST_RUNS);
+        History { recent, best }
+    }
+
+    // A header and one row per recent match, in columns
+    pub fn recent_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{:<10} {:>6} {:>5}  {:<8}  {}",
+            "Winner", "Ticks", "Score", "Settings", "Seed"
+        )];
+        for run in &self.recent {
+            // Only the start of the fingerprint, which is plenty to tell
+            // apart the settings one player uses
+            let settings = run.settings.map_or("-".to_string(), |settings| {
+                format!("{:08x}", settings >> 32)
+            });
+            let seed = run.seed.map_or("-".to_string(), |seed| seed.to_string());
+            lines.push(format!(
+                "{:<10.10} {:>6} {:>5}  {:<8}  {}",
+                run.winner, run.ticks, run.score, settings, seed
+            ));
+        }
+        lines
+    }
+
+    pub fn best_lines(&self) -> Vec<String> {
+        if self.best.is_empty() {
+            return vec!["No bets won yet".to_string()];
+        }
+        self.best
+            .iter()
+            .enumerate()
+            .map(|(i, run)| {
+                format!(
+                    "{}. {} points, {} in {} ticks",
+                    i + 1,
+                    run.score,
+                    run.winner,
+                    run.ticks
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use crate::physics::{Particle, Real, Vec2f};
+use crate::pool::{Handle, Pool};
+use crate::simulation::{Arena, Params};
+
+// Particles bucketed into cells as wide as they can see, so the ones in sight
+// of a particle are all in its cell or the ones around it. Cells of a
+// wrapping arena are stretched to fit it, like the spatial hash's.
+struct Neighbourhood {
+    cells: HashMap<(i64, i64), Vec<Handle>>,
+    cell_width: Real,
+    cell_height: Real,
+    // Cells across and down a wrapping arena, None between walls
+    wrap: Option<(i64, i64)>,
+}
+
+impl Neighbourhood {
+    fn new(particles: &Pool<Particle>, arena: &Arena, sight: Real) -> Self {
+        let fit = |size: Real| ((size / sight).floor() as i64).max(1);
+        let (columns, rows) = (fit(arena.width), fit(arena.height));
+        let mut neighbourhood = if arena.wraps() {
+            Neighbourhood {
+                cells: HashMap::new(),
+                cell_width: arena.width / columns as Real,
+                cell_height: arena.height / rows as Real,
+                wrap: Some((columns, rows)),
+            }
+        } else {
+            Neighbourhood {
+                cells: HashMap::new(),
+                cell_width: sight,
+                cell_height: sight,
+                wrap: None,
+            }
+        };
+        for (handle, particle) in particles.iter() {
+            let key = neighbourhood.cell(&particle.position);
+            neighbourhood.cells.entry(key).or_default().push(handle);
+        }
+        neighbourhood
+    }
+
+    fn cell(&self, position: &Vec2f) -> (i64, i64) {
+        let key = (
+            (position.x / self.cell_width).floor() as i64,
+            (position.y / self.cell_height).floor() as i64,
+        );
+        match self.wrap {
+            Some((columns, rows)) => (key.0.clamp(0, columns - 1), key.1.clamp(0, rows - 1)),
+            None => key,
+        }
+    }
+
+    // The particles in the cell of `position` and the ones around it, each
+    // once, in the same order every time
+    fn near(&self, position: &Vec2f) -> impl Iterator<Item = Handle> + '_ {
+        let (x, y) = self.cell(position);
+        let mut keys = Vec::with_capacity(9);
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                keys.push(match self.wrap {
+                    Some((columns, rows)) => {
+                        ((x + dx).rem_euclid(columns), (y + dy).rem_euclid(rows))
+                    }
+                    None => (x + dx, y + dy),
+                });
+            }
+        }
+        // A wrapping arena only a cell or two across comes back around
+        keys.sort_unstable();
+        keys.dedup();
+        keys.into_iter()
+            .filter_map(|key| self.cells.get(&key))
+            .flatten()
+            .copied()
+    }
+}
+
+// The nearest particle in sight, by how far away it is and then by handle
+#[derive(Copy, Clone)]
+struct Sighting {
+    distance: Real,
+    handle: Handle,
+    // The way to it
+    offset: Vec2f,
+}
+
+impl Sighting {
+    fn closer_than(&self, other: &Option<Sighting>) -> bool {
+        other.is_none_or(|other| (self.distance, self.handle) < (other.distance, other.handle))
+    }
+}
+
+// What the particles of one hand in sight of a particle add up to, for
+// flocking with them
+#[derive(Default)]
+struct Flock {
+    count: usize,
+    // Sums of the ways to them and of their velocities
+    offsets: Vec2f,
+    velocities: Vec2f,
+    // Away from each of them, the more the closer it is
+    crowding: Vec2f,
+}
+
+// Whether particles steer at all with these params
+pub fn steers(params: &Params) -> bool {
+    params.steering > 0.0
+        || params.alignment > 0.0
+        || params.cohesion > 0.0
+        || params.separation > 0.0
+}
+
+// Turns every particle a little each tick by what it sees around it:
+//
+// - Hunters and cowards: it speeds up towards the nearest particle in sight
+//   that it beats and away from the nearest one that beats it, by
+//   `params.steering` each.
+// - Flocks: it matches the velocity of the particles of its own hand in sight
+//   by `params.alignment`, heads for the middle of them by `params.cohesion`
+//   and keeps its distance from them by `params.separation`, so a hand moves
+//   as a swarm.
+//
+// No particle is sped up past the fastest new ones start out, or its own
+// speed if it is already going faster.
+pub fn steer(particles: &mut Pool<Particle>, arena: &Arena, params: &Params) {
+    profile_scope!("steering");
+    let sight = params.perception;
+    let neighbourhood = Neighbourhood::new(particles, arena, sight);
+    let rules = &params.rules;
+
+    let pushes: Vec<(Handle, Vec2f)> = particles
+        .iter()
+        .map(|(handle, particle)| {
+            let mut prey: Option<Sighting> = None;
+            let mut predator: Option<Sighting> = None;
+            let mut flock = Flock::default();
+            for other in neighbourhood.near(&particle.position) {
+                let seen = &particles[other];
+                let offset = arena.offset(particle.position, seen.position);
+                let distance = offset.norm();
+                if other == handle || distance >= sight || distance == 0.0 {
+                    continue;
+                }
+                let sighting = Sighting {
+                    distance,
+                    handle: other,
+                    offset,
+                };
+                if seen.hand == particle.hand {
+                    flock.count += 1;
+                    flock.offsets = flock.offsets.plus(offset);
+                    flock.velocities = flock.velocities.plus(seen.velocity);
+                    let closeness = 1.0 - distance / sight;
+                    flock.crowding = flock.crowding.minus(offset.product(closeness / distance));
+                } else if rules.beats(particle.hand, seen.hand) && sighting.closer_than(&prey) {
+                    prey = Some(sighting);
+                } else if rules.beats(seen.hand, particle.hand) && sighting.closer_than(&predator) {
+                    predator = Some(sighting);
+                }
+            }
+
+            let mut hunt = Vec2f { x: 0.0, y: 0.0 };
+            if let Some(prey) = prey {
+                hunt = hunt.plus(prey.offset.product(1.0 / prey.distance));
+            }
+            if let Some(predator) = predator {
+                hunt = hunt.minus(predator.offset.product(1.0 / predator.distance));
+            }
+            let mut push = hunt.product(params.steering);
+
+            if flock.count > 0 {
+                let share = 1.0 / flock.count as Real;
+                let alignment = flock.velocities.product(share).minus(particle.velocity);
+                // Scaled by how far particles see, so the weights mean about
+                // as much whatever that is
+                let cohesion = flock.offsets.product(share / sight);
+                push = push
+                    .plus(alignment.product(params.alignment))
+                    .plus(cohesion.product(params.cohesion))
+                    .plus(flock.crowding.product(params.separation));
+            }
+            (handle, push)
+        })
+        .collect();
+
+    let top_speed = params.max_velocity * params.speed * (2.0 as Real).sqrt();
+    for (handle, push) in pushes {
+        let particle = &mut particles[handle];
+        let limit = particle.velocity.norm().max(top_speed);
+        particle.velocity = particle.velocity.plus(push);
+        let speed = particle.velocity.norm();
+        if speed > limit {
+            particle.velocity = particle.velocity.product(limit / speed);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+use bracket_lib::prelude::{RandomNumberGenerator, RGB};
+use rock_paper_scissors::simulation::Simulation;
+use rock_paper_scissors::Real;
+
+use crate::headless::STALL_TICKS;
+use crate::render::Glyphs;
+use crate::settings::Settings;
+use crate::ARENA_COLOR;
+
+// Characters the arena is drawn across and down. Terminal characters are
+// about twice as tall as they are wide, so each one covers two rows of tiles
+// of the classic arena, and bigger arenas are squeezed in.
+const COLUMNS: usize = 60;
+const ROWS: usize = 30;
+
+// Plays a match in the terminal it was started from, with plain ANSI escape
+// codes for colour and none of the window's input, so it works over SSH and
+// wherever there is no window system. It runs until one hand is left or
+// `ticks` ticks have passed or it stalls, and ends on the result like
+// --headless does.
+pub fn run(settings: &Settings, glyphs: &Glyphs, ticks: Option<u64>, seed: u64) -> io::Result<()> {
+    let mut sim = Simulation::new(
+        settings.arena,
+        settings.params.clone(),
+        RandomNumberGenerator::seeded(seed),
+    );
+    settings.deal(&mut sim);
+    let tick = Duration::from_secs_f32(settings.frame_duration / 1000.0);
+
+    let mut out = io::stdout().lock();
+    write!(out, "\x1b[2J")?;
+    let mut counts = sim.counts();
+    let mut quiet = 0;
+    loop {
+        out.write_all(frame(&sim, &counts, glyphs).as_bytes())?;
+        out.flush()?;
+        if sim.winner(&counts).is_some()
+            || ticks.is_some_and(|t| sim.ticks >= t)
+            || quiet >= STALL_TICKS
+        {
+            break;
+        }
+        thread::sleep(tick);
+        counts = sim.step();
+        quiet = if sim.conversions.is_empty() {
+            quiet + 1
+        } else {
+            0
+        };
+    }
+
+    let rules = &sim.params.rules;
+    if let Some(winner) = sim.winner(&counts) {
+        writeln!(out, "{} wins", rules.name(winner))?;
+    } else if quiet >= STALL_TICKS {
+        writeln!(
+            out,
+            "No winner after {} ticks without a conversion",
+            STALL_TICKS
+        )?;
+    }
+    writeln!(out, "Seed {}", seed)
+}
+
+// The whole screen, drawn from the top left corner over the last one
+fn frame(sim: &Simulation, counts: &[usize], glyphs: &Glyphs) -> String {
+    let rules = &sim.params.rules;
+    let mut cells: Vec<Option<(char, RGB)>> = vec![None; COLUMNS * ROWS];
+    for particle in sim.particles.values() {
+        let column = particle.position.x * COLUMNS as Real / sim.arena.width;
+        let row = particle.position.y * ROWS as Real / sim.arena.height;
+        let (column, row) = (column as usize, row as usize);
+        if column < COLUMNS && row < ROWS {
+            let glyph = rules.species(particle.hand).glyph;
+            cells[row * COLUMNS + column] = Some((glyph, glyphs.color(particle.hand)));
+        }
+    }
+
+    let mut frame = "\x1b[H".to_string();
+    let (r, g, b) = ARENA_COLOR;
+    for row in cells.chunks(COLUMNS) {
+        frame += &format!("\x1b[48;2;{};{};{}m", r, g, b);
+        for cell in row {
+            match cell {
+                Some((glyph, color)) => frame += &format!("{}{}", foreground(*color), glyph),
+                None => frame.push(' '),
+            }
+        }
+        frame += "\x1b[0m\n";
+    }
+
+    let mut status = format!("Tick {}:", sim.ticks);
+    for hand in rules.hands() {
+        status += &format!(
+            " {}{} {}\x1b[0m",
+            foreground(glyphs.color(hand)),
+            rules.name(hand),
+            counts[hand.index()]
+        );
+    }
+    // Clears whatever was left of a longer line before it
+    frame + &status + "\x1b[K\n"
+}
+
+fn foreground(color: RGB) -> String {
+    let channel = |value: f32| (value * 255.0).round() as u8;
+    format!(
+        "\x1b[38;2;{};{};{}m",
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    )
+}
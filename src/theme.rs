@@ -0,0 +1,133 @@
+use bracket_lib::prelude::*;
+
+use crate::{unix_timestamp, ARENA_COLOR, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const FOG_COLOR: (u8, u8, u8) = (16, 38, 38);
+
+const SNOWFLAKES: usize = 80;
+// Tiles a snowflake falls per second, before its own variation
+const SNOW_FALL_SPEED: f32 = 3.0;
+const SNOW_COLOR: (u8, u8, u8) = (225, 235, 245);
+
+// Colours the arena is painted with. The classic one is used all year, unless
+// seasonal themes are on and the calendar says otherwise.
+#[derive(Copy, Clone, Debug)]
+pub struct Theme {
+    pub name: &'static str,
+    pub arena: RGB,
+    pub fog: RGB,
+    // Hand colours by hand name, over the rules' own
+    hands: &'static [(&'static str, (u8, u8, u8))],
+    // Whether snow drifts over the arena
+    pub snow: bool,
+}
+
+impl Theme {
+    pub fn classic() -> Self {
+        Theme {
+            name: "Classic",
+            arena: RGB::from(ARENA_COLOR),
+            fog: RGB::from(FOG_COLOR),
+            hands: &[],
+            snow: false,
+        }
+    }
+
+    // The theme for this time of the year
+    pub fn seasonal() -> Self {
+        match current_month() {
+            10 => Theme {
+                name: "Pumpkin",
+                arena: RGB::from_u8(92, 44, 14),
+                fog: RGB::from_u8(34, 16, 8),
+                hands: &[
+                    ("Rock", (120, 110, 100)),
+                    ("Paper", (250, 160, 40)),
+                    ("Scissors", (150, 70, 180)),
+                    ("Lizard", (110, 150, 50)),
+                    ("Spock", (200, 60, 30)),
+                ],
+                snow: false,
+            },
+            12 => Theme {
+                name: "Winter",
+                arena: RGB::from_u8(52, 86, 120),
+                fog: RGB::from_u8(18, 30, 46),
+                snow: true,
+                ..Theme::classic()
+            },
+            _ => Theme::classic(),
+        }
+    }
+
+    // The theme's colour for the hand called `name`, if it has one
+    pub fn hand_color(&self, name: &str) -> Option<RGB> {
+        self.hands
+            .iter()
+            .find(|(hand, _)| hand.eq_ignore_ascii_case(name))
+            .map(|&(_, color)| RGB::from(color))
+    }
+}
+
+struct Snowflake {
+    x: f32,
+    y: f32,
+    speed: f32,
+}
+
+// Snowflakes falling down the screen, drawn on their own under the particles.
+// They are cosmetic only and never touch the simulation.
+pub struct Snow {
+    flakes: Vec<Snowflake>,
+    rng: RandomNumberGenerator,
+}
+
+impl Snow {
+    pub fn new() -> Self {
+        let mut rng = RandomNumberGenerator::new();
+        let flakes = (0..SNOWFLAKES)
+            .map(|_| Snowflake {
+                x: rng.range(0.0, SCREEN_WIDTH as f32),
+                y: rng.range(0.0, SCREEN_HEIGHT as f32),
+                speed: rng.range(0.5, 1.5),
+            })
+            .collect();
+        Snow { flakes, rng }
+    }
+
+    // Lets the snow fall for `frame_time_ms` and draws it on the active
+    // console. Flakes that reach the bottom start over from the top.
+    pub fn draw(&mut self, ctx: &mut BTerm, frame_time_ms: f32) {
+        let fall = SNOW_FALL_SPEED * frame_time_ms / 1000.0;
+        let color = RGB::from(SNOW_COLOR);
+        for flake in self.flakes.iter_mut() {
+            flake.y += fall * flake.speed;
+            flake.x += fall * 0.3 * (flake.y * 0.5).sin();
+            if flake.y >= SCREEN_HEIGHT as f32 {
+                flake.y = 0.0;
+                flake.x = self.rng.range(0.0, SCREEN_WIDTH as f32);
+            }
+            let glyph = if flake.speed > 1.2 { '*' } else { '.' };
+            ctx.set(
+                flake.x as i32,
+                flake.y as i32,
+                color,
+                RGB::named(BLACK),
+                to_cp437(glyph),
+            );
+        }
+    }
+}
+
+// The month of the year, 1 to 12, in UTC
+fn current_month() -> u32 {
+    // Civil calendar from days since the epoch, after Howard Hinnant's
+    // `civil_from_days`
+    let days = (unix_timestamp() / 86_400) as i64 + 719_468;
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    (if month < 10 { month + 3 } else { month - 9 }) as u32
+}
@@ -0,0 +1,135 @@
+use std::io;
+
+use bracket_lib::prelude::*;
+use rock_paper_scissors::Hand;
+
+use crate::render::Glyphs;
+use crate::replay::ReplayReader;
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// The bar runs along the bottom of the screen, under a row of markers
+const BAR_HEIGHT: i32 = 4;
+const BAR_Y: i32 = SCREEN_HEIGHT - BAR_HEIGHT;
+const MARKER_Y: i32 = BAR_Y - 1;
+const EVENT_COLOR: (u8, u8, u8) = (250, 230, 90);
+
+// Population of every frame of a replay, and the frames where something
+// decisive happened, for drawing a scrubbable timeline
+pub struct Timeline {
+    counts: Vec<Vec<usize>>,
+    // Frames where a hand was wiped out, in order
+    events: Vec<u64>,
+}
+
+impl Timeline {
+    // Decodes the whole replay once, a chunk at a time. Replays don't say
+    // what rules they were played by, so there are `hands` hands to count,
+    // or more if the replay has them.
+    pub fn new(reader: &mut ReplayReader, hands: usize) -> io::Result<Self> {
+        let mut counts: Vec<Vec<usize>> = Vec::with_capacity(reader.frame_count() as usize);
+        let mut events = Vec::new();
+        for frame in 0..reader.frame_count() {
+            let mut count = vec![0; hands];
+            for particle in &reader.frame(frame)?.particles {
+                let i = particle.hand.index();
+                if i >= count.len() {
+                    count.resize(i + 1, 0);
+                }
+                count[i] += 1;
+            }
+            let wiped_out = |last: &Vec<usize>| {
+                let gone = |(i, &n)| n > 0 && count.get(i).is_none_or(|&now| now == 0);
+                last.iter().enumerate().any(gone)
+            };
+            if counts.last().is_some_and(wiped_out) {
+                events.push(frame);
+            }
+            counts.push(count);
+        }
+        Ok(Timeline { counts, events })
+    }
+
+    fn frames(&self) -> u64 {
+        self.counts.len() as u64
+    }
+
+    // The first event after `frame`, or the last frame if there is none
+    pub fn next_event(&self, frame: u64) -> u64 {
+        let i = self.events.partition_point(|&event| event <= frame);
+        self.events.get(i).copied().unwrap_or(self.frames() - 1)
+    }
+
+    // The last event before `frame`, or the first frame if there is none
+    pub fn previous_event(&self, frame: u64) -> u64 {
+        let i = self.events.partition_point(|&event| event < frame);
+        if i == 0 {
+            0
+        } else {
+            self.events[i - 1]
+        }
+    }
+
+    // The frame under the mouse, if it was clicked on the bar
+    pub fn clicked(&self, ctx: &BTerm) -> Option<u64> {
+        if !ctx.left_click {
+            return None;
+        }
+        let (x, y) = ctx.mouse_pos();
+        if !(MARKER_Y..SCREEN_HEIGHT).contains(&y) || !(0..SCREEN_WIDTH).contains(&x) {
+            return None;
+        }
+        Some(self.column_frame(x))
+    }
+
+    fn column_frame(&self, column: i32) -> u64 {
+        column as u64 * self.frames() / SCREEN_WIDTH as u64
+    }
+
+    fn frame_column(&self, frame: u64) -> i32 {
+        (frame * SCREEN_WIDTH as u64 / self.frames()) as i32
+    }
+
+    // Draws the bar on the active console: each column is split between the
+    // hands by their share of the population at that point of the replay
+    pub fn draw(&self, ctx: &mut BTerm, frame: u64, glyphs: &Glyphs) {
+        for column in 0..SCREEN_WIDTH {
+            let counts = &self.counts[self.column_frame(column) as usize];
+            let total: usize = counts.iter().sum::<usize>().max(1);
+            let mut y = SCREEN_HEIGHT;
+            let mut covered = 0;
+            for (i, &count) in counts.iter().enumerate() {
+                let Some(hand) = Hand::from_index(i) else {
+                    break;
+                };
+                covered += count;
+                let top = SCREEN_HEIGHT - (covered * BAR_HEIGHT as usize).div_ceil(total) as i32;
+                for row in top..y {
+                    ctx.set(column, row, WHITE, glyphs.color(hand), to_cp437(' '));
+                }
+                y = y.min(top);
+            }
+        }
+
+        let black = RGB::named(BLACK);
+        for x in 0..SCREEN_WIDTH {
+            ctx.set(x, MARKER_Y, WHITE, black, to_cp437(' '));
+        }
+        for &event in &self.events {
+            let color = RGB::from(EVENT_COLOR);
+            ctx.set(
+                self.frame_column(event),
+                MARKER_Y,
+                color,
+                black,
+                to_cp437('!'),
+            );
+        }
+        ctx.set(
+            self.frame_column(frame),
+            MARKER_Y,
+            WHITE,
+            black,
+            to_cp437('v'),
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::{HashMap, VecDeque};
+
+use bracket_lib::prelude::*;
+use rock_paper_scissors::pool::Handle;
+use rock_paper_scissors::simulation::Simulation;
+use rock_paper_scissors::{Particle, Vec2f};
+
+use crate::camera::Camera;
+use crate::render::Glyphs;
+use crate::theme::Theme;
+
+// Dots fainter than this share of their particle's colour aren't drawn
+const FAINTEST: f32 = 0.05;
+
+// Where each particle has been over the last few ticks, newest last, drawn
+// as dots fading into the floor behind it
+pub struct Trails {
+    length: usize,
+    fade: f32,
+    points: HashMap<Handle, VecDeque<Vec2f>>,
+}
+
+impl Trails {
+    pub fn new(length: usize, fade: f32) -> Self {
+        Trails {
+            length,
+            fade,
+            points: HashMap::new(),
+        }
+    }
+
+    // Adds where every particle is after the tick that just ran, and forgets
+    // the ones gone since
+    pub fn record(&mut self, sim: &Simulation) {
+        if self.length == 0 {
+            return;
+        }
+        self.points
+            .retain(|&handle, _| sim.particles.get(handle).is_some());
+        for (handle, particle) in sim.particles.iter() {
+            let points = self.points.entry(handle).or_default();
+            if points.len() == self.length {
+                points.pop_front();
+            }
+            points.push_back(particle.position);
+        }
+    }
+
+    // Forgets every trail, as when the match jumps to another point in time
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    // Draws the trails `camera` sees of the particles `shows` lets through
+    // onto the active console, in the colour of each particle's hand, in the
+    // order of the particles
+    pub fn draw(
+        &self,
+        ctx: &mut BTerm,
+        sim: &Simulation,
+        shows: impl Fn(&Particle) -> bool,
+        glyphs: &Glyphs,
+        camera: &Camera,
+        theme: &Theme,
+    ) {
+        let origin = camera.origin();
+        for (handle, particle) in sim.particles.iter() {
+            let Some(points) = self.points.get(&handle).filter(|_| shows(particle)) else {
+                continue;
+            };
+            let color = glyphs.color(particle.hand);
+            // Oldest first, so newer dots on the same tile win
+            for (age, point) in points.iter().rev().enumerate().rev() {
+                let share = (1.0 - self.fade).powi(age as i32 + 1);
+                if share < FAINTEST || !camera.sees(point) {
+                    continue;
+                }
+                ctx.set(
+                    origin.x + point.x as i32,
+                    origin.y + point.y as i32,
+                    theme.arena.lerp(color, share),
+                    RGB::named(BLACK),
+                    to_cp437('·'),
+                );
+            }
+        }
+    }
+}
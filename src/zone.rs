@@ -0,0 +1,61 @@
+use crate::forces::{self, Region};
+use crate::physics::{Particle, Real};
+use crate::rules::Hand;
+
+// Part of a map that favours one hand: its particles move faster in there, or
+// slower for a speed under 1, and everyone else moves as anywhere else
+#[derive(Clone, Debug, PartialEq)]
+pub struct Zone {
+    // What the map calls it, like "quarry"
+    pub name: String,
+    pub hand: Hand,
+    pub speed: Real,
+    pub region: Region,
+}
+
+// How many times faster than its velocity `particle` moves where it is, with
+// the speeds of every zone it is in for its hand multiplied together
+pub fn boost(zones: &[Zone], particle: &Particle) -> Real {
+    zones
+        .iter()
+        .filter(|zone| zone.hand == particle.hand && zone.region.contains(&particle.position))
+        .map(|zone| zone.speed)
+        .product()
+}
+
+// One zone, after the `zone` that starts its line of a map, as
+//
+//   <name> <hand> <speed> <x> <y> <width> <height>
+//
+// with the hand by its number in the rules, from 0. None if it isn't a zone.
+pub fn parse_zone(line: &str) -> Option<Zone> {
+    let mut words = line.split_whitespace();
+    let name = words.next()?.to_string();
+    let hand = Hand::from_index(words.next()?.parse().ok()?)?;
+    let speed: Real = words.next()?.parse().ok()?;
+    if speed <= 0.0 {
+        return None;
+    }
+    let region = forces::parse_region(&words.collect::<Vec<_>>().join(" "))?;
+    Some(Zone {
+        name,
+        hand,
+        speed,
+        region,
+    })
+}
+
+// The line `parse_zone` reads back as `zone`
+pub fn zone_line(zone: &Zone) -> String {
+    let r = &zone.region;
+    format!(
+        "{} {} {} {} {} {} {}",
+        zone.name,
+        zone.hand.index(),
+        zone.speed,
+        r.x,
+        r.y,
+        r.width,
+        r.height
+    )
+}